@@ -0,0 +1,123 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use kucoin_arbitrage::model::fixed_orderbook::FixedDepthOrderbook;
+use kucoin_arbitrage::model::orderbook::{Orderbook, OrderbookBackend};
+use ordered_float::OrderedFloat;
+
+const TICK: f64 = 0.01;
+const DEPTH: usize = 64;
+
+/// (sequence, asks, bids)
+type Delta = (u64, Vec<(f64, f64)>, Vec<(f64, f64)>);
+
+/// 20 levels each side around 100.00, similar to the L20 REST snapshot
+fn snapshot() -> Orderbook {
+    let mut orderbook = Orderbook::new();
+    for i in 1..=20 {
+        let offset = i as f64 * TICK;
+        orderbook
+            .ask
+            .insert(OrderedFloat(100.0 + offset), OrderedFloat(i as f64));
+        orderbook
+            .bid
+            .insert(OrderedFloat(100.0 - offset), OrderedFloat(i as f64));
+    }
+    orderbook
+}
+
+/// deltas alternating between inserting, updating and removing levels near the top of book
+fn deltas() -> Vec<Delta> {
+    (0..256u64)
+        .map(|i| {
+            let offset = (i % 8) as f64 * TICK;
+            let volume = if i % 3 == 0 {
+                0.0
+            } else {
+                (i % 5) as f64 + 1.0
+            };
+            (
+                i + 1,
+                vec![(100.01 + offset, volume)],
+                vec![(99.99 - offset, volume)],
+            )
+        })
+        .collect()
+}
+
+/// delta as the BTreeMap orderbook, as produced by the translator
+fn delta_orderbook(sequence: u64, asks: &[(f64, f64)], bids: &[(f64, f64)]) -> Orderbook {
+    let mut orderbook = Orderbook::new();
+    orderbook.sequence = sequence;
+    for &(price, volume) in asks {
+        orderbook
+            .ask
+            .insert(OrderedFloat(price), OrderedFloat(volume));
+    }
+    for &(price, volume) in bids {
+        orderbook
+            .bid
+            .insert(OrderedFloat(price), OrderedFloat(volume));
+    }
+    orderbook
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let deltas = deltas();
+    let delta_orderbooks: Vec<Orderbook> = deltas
+        .iter()
+        .map(|(sequence, asks, bids)| delta_orderbook(*sequence, asks, bids))
+        .collect();
+
+    let mut group = c.benchmark_group("merge");
+    group.bench_function("btreemap_merge", |b| {
+        b.iter_batched(
+            || (snapshot(), delta_orderbooks.clone()),
+            |(mut orderbook, delta_orderbooks)| {
+                for delta in delta_orderbooks {
+                    black_box(orderbook.merge(delta).unwrap());
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("btreemap_apply", |b| {
+        b.iter_batched(
+            snapshot,
+            |mut orderbook| {
+                for (sequence, asks, bids) in &deltas {
+                    black_box(orderbook.apply(*sequence, asks, bids).unwrap());
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    // as applied by the orderbook sync task, from the change produced by the translator
+    group.bench_function("fixed_depth_apply", |b| {
+        let initial = FixedDepthOrderbook::<DEPTH>::from_orderbook(&snapshot(), TICK).unwrap();
+        b.iter_batched(
+            || initial,
+            |mut orderbook| {
+                for (sequence, asks, bids) in &deltas {
+                    black_box(orderbook.apply(*sequence, asks, bids).unwrap());
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_best_price(c: &mut Criterion) {
+    let btreemap = snapshot();
+    let fixed = FixedDepthOrderbook::<DEPTH>::from_orderbook(&btreemap, TICK).unwrap();
+    let mut group = c.benchmark_group("best_price");
+    group.bench_function("btreemap", |b| {
+        b.iter(|| black_box((btreemap.best_ask(), btreemap.best_bid())))
+    });
+    group.bench_function("fixed_depth", |b| {
+        b.iter(|| black_box((fixed.best_ask(), fixed.best_bid())))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_merge, bench_best_price);
+criterion_main!(benches);
@@ -0,0 +1,274 @@
+/// Terminal dashboard attached to the status API of a running kucoin_arbitrage,
+/// refreshing top of book, chances, channel rates, open orders and PnL in place
+use eyre::Result;
+use hyper::client::HttpConnector;
+use hyper::{body, Client};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// chances listed, most profitable first
+const TOP_CHANCES: usize = 10;
+/// wait for a key press between redraws
+const KEY_POLL: Duration = Duration::from_millis(100);
+
+/// State polled from the status API
+#[derive(Debug, Default)]
+struct Dashboard {
+    /// e.g. http://127.0.0.1:9185
+    url: String,
+    symbols: Vec<String>,
+    ready: Value,
+    books: Value,
+    chances: Vec<Value>,
+    orders: Value,
+    /// messages per second of each channel, from the change of the counter totals
+    rates: BTreeMap<String, f64>,
+    totals: BTreeMap<String, u64>,
+    realised_pnl: f64,
+    polled: Option<Instant>,
+    /// error of the last poll, e.g. when the system is not running
+    error: Option<String>,
+}
+
+impl Dashboard {
+    async fn poll(&mut self, client: &Client<HttpConnector>) {
+        match self.fetch(client).await {
+            Ok(()) => self.error = None,
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    async fn fetch(&mut self, client: &Client<HttpConnector>) -> Result<()> {
+        self.ready = get(client, format!("{}/ready", self.url)).await?;
+        let symbols = self.symbols.join(",");
+        self.books = get(client, format!("{}/books?symbols={symbols}", self.url)).await?;
+        self.orders = get(client, format!("{}/orders", self.url)).await?;
+
+        let mut chances = get(client, format!("{}/chances", self.url)).await?;
+        let mut chances = chances
+            .as_array_mut()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        chances.sort_by(|a, b| profit(b).total_cmp(&profit(a)));
+        chances.truncate(TOP_CHANCES);
+        self.chances = chances;
+
+        let metrics = get(client, format!("{}/counters", self.url)).await?;
+        let now = Instant::now();
+        let elapsed = self.polled.map(|polled| (now - polled).as_secs_f64());
+        for counter in metrics["counters"].as_array().into_iter().flatten() {
+            let Some(channel) = counter["labels"]["channel"].as_str() else {
+                continue;
+            };
+            if counter["name"] != "messages" {
+                continue;
+            }
+            let total = counter["total"].as_u64().unwrap_or_default();
+            let last = self.totals.insert(channel.to_string(), total);
+            if let (Some(last), Some(elapsed)) = (last, elapsed) {
+                let rate = total.saturating_sub(last) as f64 / elapsed;
+                self.rates.insert(channel.to_string(), rate);
+            }
+        }
+        self.realised_pnl = metrics["gauges"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|gauge| gauge["name"] == "realised_pnl")
+            .and_then(|gauge| gauge["value"].as_f64())
+            .unwrap_or_default();
+        self.polled = Some(now);
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, top, chances, orders, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(self.symbols.len().max(self.rates.len()) as u16 + 3),
+            Constraint::Length(TOP_CHANCES as u16 + 3),
+            Constraint::Min(4),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [books, rates] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(top);
+
+        frame.render_widget(Paragraph::new(self.header()), header);
+        self.draw_books(frame, books);
+        self.draw_rates(frame, rates);
+        self.draw_chances(frame, chances);
+        self.draw_orders(frame, orders);
+        frame.render_widget(Paragraph::new("q: quit".dark_gray()), footer);
+    }
+
+    fn header(&self) -> Line<'_> {
+        if let Some(error) = &self.error {
+            return Line::from(format!("{}: {error}", self.url)).red();
+        }
+        let state = match (
+            self.ready["ready"].as_bool(),
+            self.ready["paused"].as_bool(),
+        ) {
+            (_, Some(true)) => "PAUSED".yellow(),
+            (Some(true), _) => "READY".green(),
+            _ => "STARTING".yellow(),
+        };
+        let pnl = format!("{:+.4}", self.realised_pnl);
+        let pnl = if self.realised_pnl < 0.0 {
+            pnl.red()
+        } else {
+            pnl.green()
+        };
+        Line::from(vec![
+            state,
+            format!(
+                "  sessions {}/{}  books {}/{}  session PnL ",
+                self.ready["ws_sessions"]["connected"],
+                self.ready["ws_sessions"]["expected"],
+                self.ready["books"]["synced"],
+                self.ready["books"]["expected"],
+            )
+            .into(),
+            pnl,
+        ])
+    }
+
+    fn draw_books(&self, frame: &mut Frame, area: Rect) {
+        let level = |level: &Value, i: usize| level[i].as_f64().map(|x| x.to_string());
+        let rows = self.symbols.iter().map(|symbol| {
+            let book = &self.books[symbol];
+            Row::new([
+                symbol.clone(),
+                level(&book["bid"], 1).unwrap_or_default(),
+                level(&book["bid"], 0).unwrap_or_else(|| "-".to_string()),
+                level(&book["ask"], 0).unwrap_or_else(|| "-".to_string()),
+                level(&book["ask"], 1).unwrap_or_default(),
+            ])
+        });
+        let table = Table::new(rows, [Constraint::Ratio(1, 5); 5])
+            .header(Row::new(["symbol", "bid vol", "bid", "ask", "ask vol"]).bold())
+            .block(Block::bordered().title("Top of book"));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_rates(&self, frame: &mut Frame, area: Rect) {
+        let rows = self
+            .rates
+            .iter()
+            .map(|(channel, rate)| Row::new([channel.clone(), format!("{rate:.1}")]));
+        let table = Table::new(
+            rows,
+            [Constraint::Percentage(60), Constraint::Percentage(40)],
+        )
+        .header(Row::new(["channel", "msg/s"]).bold())
+        .block(Block::bordered().title("Channels"));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_chances(&self, frame: &mut Frame, area: Rect) {
+        let rows = self.chances.iter().map(|chance| {
+            let actions: Vec<&str> = chance["actions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            Row::new([
+                format!("{:.6}", profit(chance)),
+                chance["time"].as_str().unwrap_or_default().to_string(),
+                actions.join(" -> "),
+            ])
+        });
+        let widths = [
+            Constraint::Length(12),
+            Constraint::Length(34),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["profit", "found", "actions"]).bold())
+            .block(Block::bordered().title("Best recent chances"));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_orders(&self, frame: &mut Frame, area: Rect) {
+        let orders = self.orders.as_object().into_iter().flatten();
+        let rows = orders.map(|(order_id, symbol)| {
+            Row::new([
+                order_id.clone(),
+                symbol.as_str().unwrap_or_default().to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [Constraint::Percentage(60), Constraint::Percentage(40)],
+        )
+        .header(Row::new(["order id", "symbol"]).bold())
+        .block(Block::bordered().title("Open orders"));
+        frame.render_widget(table, area);
+    }
+}
+
+/// predicted profit of a chance record
+fn profit(chance: &Value) -> f64 {
+    chance["profit"].as_f64().unwrap_or_default()
+}
+
+/// JSON body of a GET, including error statuses such as 503 from /ready
+async fn get(client: &Client<HttpConnector>, url: String) -> Result<Value> {
+    let response = client.get(url.parse()?).await?;
+    let bytes = body::to_bytes(response.into_body()).await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    mut dashboard: Dashboard,
+    refresh: Duration,
+) -> Result<()> {
+    let client = Client::new();
+    let mut next_poll = Instant::now();
+    loop {
+        if Instant::now() >= next_poll {
+            dashboard.poll(&client).await;
+            next_poll = Instant::now() + refresh;
+        }
+        terminal.draw(|frame| dashboard.draw(frame))?;
+        if event::poll(KEY_POLL)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press
+                    && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    // no logging, the dashboard owns the terminal
+    let config = kucoin_arbitrage::config::load()?;
+    let port = config
+        .behaviour
+        .status_port
+        .ok_or_else(|| eyre::eyre!("behaviour.status_port is required by the dashboard"))?;
+    let dashboard = Dashboard {
+        url: format!("http://{}:{port}", config.dashboard.host),
+        symbols: config.dashboard.symbols,
+        ..Default::default()
+    };
+    let refresh = Duration::from_millis(config.dashboard.refresh_ms as u64);
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, dashboard, refresh).await;
+    ratatui::restore();
+    result
+}
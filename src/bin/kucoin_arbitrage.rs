@@ -1,57 +1,118 @@
+use clap::Parser;
 use eyre::Result;
 /// Executes triangular arbitrage
-use kucoin_api::client::{Kucoin, KucoinEnv};
-use kucoin_arbitrage::broker::gatekeeper::kucoin::task_gatekeep_chances;
-use kucoin_arbitrage::broker::order::kucoin::task_place_order;
+use kucoin_api::client::{Credentials, Kucoin, KucoinEnv};
+use kucoin_arbitrage::broker::account::internal::task_rebalance_inventory;
+use kucoin_arbitrage::broker::account::kucoin::task_sync_balances;
+use kucoin_arbitrage::broker::gatekeeper::kucoin::{task_gatekeep_chances, Funding};
+use kucoin_arbitrage::broker::margin::kucoin::task_margin;
+use kucoin_arbitrage::broker::order::internal::task_track_open_orders;
+use kucoin_arbitrage::broker::order::kucoin::{cancel_open_orders, task_place_order};
+use kucoin_arbitrage::broker::order::kucoin_code::RetryPolicy;
 use kucoin_arbitrage::broker::orderbook::internal::task_sync_orderbook;
 use kucoin_arbitrage::broker::orderbook::kucoin::{
-    task_get_initial_orderbooks, task_pub_orderbook_event,
+    task_get_initial_orderbooks, task_pub_orderbook_event, task_resync_orderbooks,
 };
+use kucoin_arbitrage::broker::reconcile::kucoin::reconcile;
+use kucoin_arbitrage::broker::risk::internal::{task_manage_risk, TradingState};
 use kucoin_arbitrage::broker::symbol::filter::{symbol_with_quotes, vector_to_hash};
-use kucoin_arbitrage::broker::symbol::kucoin::{format_subscription_list, get_symbols};
+use kucoin_arbitrage::broker::symbol::kucoin::{format_subscription_list_feed, get_symbols};
 use kucoin_arbitrage::broker::trade::kucoin::task_pub_trade_event;
+use kucoin_arbitrage::config::{Args, Config, ExecutionMode, OrderbookFeed};
 use kucoin_arbitrage::event::{
-    chance::ChanceEvent, order::OrderEvent, orderbook::OrderbookEvent, trade::TradeEvent,
+    chance::ChanceEvent,
+    cycle::CycleEvent,
+    order::{OrderEvent, OrderResultEvent},
+    orderbook::OrderbookEvent,
+    trade::TradeEvent,
 };
+use kucoin_arbitrage::journal::store::Journal;
+use kucoin_arbitrage::journal::task::task_journal_events;
+use kucoin_arbitrage::model::inventory::Balances;
+use kucoin_arbitrage::model::margin::Loans;
+use kucoin_arbitrage::model::order::OpenOrders;
 use kucoin_arbitrage::model::orderbook::FullOrderbook;
-use kucoin_arbitrage::monitor::counter::Counter;
-use kucoin_arbitrage::monitor::task::{task_log_mps, task_monitor_channel_mps};
+use kucoin_arbitrage::monitor::exporter::task_serve_metrics;
+use kucoin_arbitrage::monitor::registry;
+use kucoin_arbitrage::monitor::status_api::{task_serve_status, StatusSources};
+use kucoin_arbitrage::monitor::task::{task_log_latency, task_log_mps};
+use kucoin_arbitrage::shutdown::{join_within, ShutdownCoordinator, ShutdownPhase};
 use kucoin_arbitrage::strategy::all_taker_btc_usd::task_pub_chance_all_taker_btc_usd;
-use kucoin_arbitrage::system_event::task_signal_handle;
+use kucoin_arbitrage::system_event::{task_reload_config, task_signal_handle};
+use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::broadcast::channel;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, watch, Mutex};
 use tokio::task::JoinSet;
+use tokio::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // logging format
     tracing::info!("Log setup");
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let args = Args::parse();
+    let config = args.load()?;
+    config.validate_private()?;
     // only discard worker_goard when terminating file logging
     let _worker_guard = kucoin_arbitrage::logger::setup_logs(&config.log)?;
     tracing::info!("Log setup");
-    tokio::select! {
-        _ = task_signal_handle() => tracing::error!("received external signal, terminating program"),
-        res = core(config) => tracing::error!("core ended first {res:?}"),
-    };
 
+    // API endpoints
+    let api = Kucoin::new(KucoinEnv::Live, Some(Credentials::from(&config.kucoin)))
+        .map_err(|e| eyre::eyre!(e))?;
+    tracing::info!("Credentials setup");
+
+    // config reloaded on SIGHUP
+    let (tx_config, rx_config) = watch::channel(config.clone());
+    tokio::spawn(task_reload_config(args, tx_config));
+
+    // external signal starts the graceful shutdown, a second one exits at once
+    let shutdown = Arc::new(ShutdownCoordinator::new());
+    let shutdown_signal = shutdown.clone();
+    tokio::spawn(async move {
+        if let Err(e) = task_signal_handle().await {
+            tracing::error!("failed handling external signal: {e}");
+            return;
+        }
+        shutdown_signal.advance(ShutdownPhase::StopChances);
+        if task_signal_handle().await.is_ok() {
+            tracing::error!("Second signal received, exiting without cleanup");
+            std::process::exit(1);
+        }
+    });
+
+    let res = core(config, rx_config, api, shutdown).await;
+    match &res {
+        Ok(()) => tracing::info!("core ended"),
+        Err(e) => tracing::error!("core ended with {e}"),
+    }
+
+    // logs are flushed when the worker guard is dropped, exiting non-zero on failure
     println!("Good bye!");
-    Ok(())
+    res
 }
 
-async fn core(config: kucoin_arbitrage::config::Config) -> Result<()> {
+async fn core(
+    config: Config,
+    rx_config: watch::Receiver<Config>,
+    api: Kucoin,
+    shutdown: Arc<ShutdownCoordinator>,
+) -> Result<()> {
     // config parameters
-    let budget = config.behaviour.usd_cyclic_arbitrage;
     let monitor_interval = config.behaviour.monitor_interval_sec;
-
-    // API endpoints
-    let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
-        .map_err(|e| eyre::eyre!(e))?;
-    tracing::info!("Credentials setup");
+    let orderbook_feed = config.behaviour.orderbook_feed;
+    let retry = RetryPolicy::new(
+        config.behaviour.order_max_retries,
+        config.behaviour.order_retry_backoff_ms as u64,
+    );
+    let grace = Duration::from_secs(config.behaviour.shutdown_grace_sec as u64);
 
     // get all symbols concurrently
-    let symbol_list = get_symbols(api.clone()).await?;
+    let Some(symbol_list) = unless_shutdown(&shutdown, get_symbols(api.clone())).await else {
+        return Ok(());
+    };
+    let symbol_list = symbol_list?;
     tracing::info!("Total exchange symbols: {:?}", symbol_list.len());
 
     // filter with either btc or usdt as quote
@@ -60,21 +121,21 @@ async fn core(config: kucoin_arbitrage::config::Config) -> Result<()> {
     tracing::info!("Total symbols in scope: {:?}", symbol_infos.len());
 
     // list subscription using the filtered symbols
-    let subs = format_subscription_list(&symbol_infos);
-    tracing::info!("Total orderbook WS sessions: {:?}", subs.len());
+    let subs = format_subscription_list_feed(&symbol_infos, orderbook_feed);
+    tracing::info!(
+        "Total orderbook WS sessions: {:?} ({orderbook_feed:?})",
+        subs.len()
+    );
 
-    // create broadcast channels
-
-    // system mps counters
-    let cx_orderbook = Arc::new(Mutex::new(Counter::new("orderbook")));
+    // create broadcast channels, the published messages are counted in the global registry
     let tx_orderbook = channel::<OrderbookEvent>(1024 * 2).0;
-    let cx_orderbook_best = Arc::new(Mutex::new(Counter::new("best_price")));
     let tx_orderbook_best = channel::<OrderbookEvent>(512).0;
-    let cx_chance = Arc::new(Mutex::new(Counter::new("chance")));
     let tx_chance = channel::<ChanceEvent>(64).0;
-    let cx_order = Arc::new(Mutex::new(Counter::new("order")));
+    let tx_chance_approved = channel::<ChanceEvent>(64).0;
+    let tx_rebalance = channel::<OrderEvent>(16).0;
+    let tx_cycle = channel::<CycleEvent>(16).0;
     let tx_order = channel::<OrderEvent>(16).0;
-    let cx_trade = Arc::new(Mutex::new(Counter::new("trade")));
+    let tx_order_result = channel::<OrderResultEvent>(16).0;
     let tx_trade = channel::<TradeEvent>(128).0;
     tracing::info!("Broadcast channels setup");
 
@@ -82,85 +143,296 @@ async fn core(config: kucoin_arbitrage::config::Config) -> Result<()> {
     let full_orderbook = Arc::new(Mutex::new(FullOrderbook::new()));
     tracing::info!("Local empty full orderbook setup");
 
-    // infrastructure tasks
-    let mut taskpool_infrastructure: JoinSet<Result<()>> = JoinSet::new();
-    taskpool_infrastructure.spawn(task_sync_orderbook(
-        tx_orderbook.subscribe(),
-        tx_orderbook_best.clone(),
-        full_orderbook.clone(),
-    ));
-    taskpool_infrastructure.spawn(task_pub_chance_all_taker_btc_usd(
+    // orders placed by this process, canceled before exiting
+    let open_orders = Arc::new(Mutex::new(OpenOrders::new()));
+
+    // journal of the previous runs, also used to recognise the orders they left open
+    let journal = config
+        .journal
+        .enabled
+        .then(|| Journal::open(&config.journal.path))
+        .transpose()?;
+
+    // orders and positions left by a previous run, settled before any chance is taken
+    if config.reconcile.enabled {
+        let symbol_map = vector_to_hash(&symbol_list);
+        let reconciling = reconcile(
+            api.clone(),
+            journal.as_ref(),
+            open_orders.clone(),
+            &symbol_map,
+            config.reconcile,
+            retry,
+        );
+        let Some(reconciliation) = unless_shutdown(&shutdown, reconciling).await else {
+            tracing::warn!("Reconciliation interrupted, check the open orders manually");
+            return Ok(());
+        };
+        tracing::info!("Reconciled with the account: {:?}", reconciliation?);
+    }
+
+    // trading paused by the operator through the status API, halted by the risk manager
+    let (paused, rx_paused) = watch::channel(false);
+    let (tx_halted, rx_halted) = watch::channel(false);
+    let trading = TradingState {
+        halted: rx_halted.clone(),
+        paused: rx_paused.clone(),
+    };
+
+    // balances checked before placing the legs at once or borrowing, not tracked otherwise
+    let simultaneous = config.behaviour.execution_mode == ExecutionMode::Simultaneous;
+    let inventory =
+        (simultaneous || config.margin.enabled).then(|| Arc::new(Mutex::new(Balances::new())));
+    let funding = Funding {
+        balances: inventory.clone(),
+        simultaneous,
+        borrow: config.margin.enabled,
+    };
+
+    // chance tasks, stopped first on shutdown
+    let mut taskpool_chance: JoinSet<kucoin_arbitrage::error::Result<()>> = JoinSet::new();
+    taskpool_chance.spawn(task_pub_chance_all_taker_btc_usd(
         tx_orderbook_best.subscribe(),
         tx_chance.clone(),
         full_orderbook.clone(),
         hash_symbols,
-        budget as f64,
+        rx_config,
+        rx_halted,
+        shutdown.token(ShutdownPhase::StopChances),
     ));
-    taskpool_infrastructure.spawn(task_gatekeep_chances(
+    taskpool_chance.spawn(task_manage_risk(
         tx_chance.subscribe(),
+        tx_chance_approved.clone(),
+        tx_rebalance.subscribe(),
+        tx_cycle.subscribe(),
+        tx_order.clone(),
+        config.risk.clone(),
+        tx_halted,
+        rx_paused,
+        // records the outcome of the in-flight cycle
+        shutdown.token(ShutdownPhase::CancelCycles),
+    ));
+    taskpool_chance.spawn(task_gatekeep_chances(
+        tx_chance_approved.subscribe(),
         tx_trade.subscribe(),
         tx_order.clone(),
+        tx_order_result.subscribe(),
+        tx_cycle.clone(),
+        shutdown.token(ShutdownPhase::StopChances),
+        shutdown.token(ShutdownPhase::CancelCycles),
+        funding,
+        Duration::from_secs(config.behaviour.leg_timeout_sec as u64),
+        config.risk.taker_fee,
+        trading.clone(),
     ));
-    taskpool_infrastructure.spawn(task_place_order(tx_order.subscribe(), api.clone()));
+    // restores the target inventory consumed by the simultaneous legs
+    if let (true, Some(inventory)) = (simultaneous, &inventory) {
+        if config.inventory.targets.is_empty() {
+            tracing::warn!("No target inventory declared, rebalancing disabled");
+        } else {
+            taskpool_chance.spawn(task_rebalance_inventory(
+                tx_rebalance.clone(),
+                inventory.clone(),
+                full_orderbook.clone(),
+                vector_to_hash(&symbol_list),
+                config.inventory.clone(),
+                trading,
+                shutdown.token(ShutdownPhase::StopChances),
+            ));
+        }
+    }
 
-    // monitor tasks
-    let mut taskpool_monitor = JoinSet::new();
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_orderbook.subscribe(),
-        cx_orderbook.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_orderbook_best.subscribe(),
-        cx_orderbook_best.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_chance.subscribe(),
-        cx_chance.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
+    // order task, stopped once no cycle is in flight
+    let mut taskpool_order: JoinSet<kucoin_arbitrage::error::Result<()>> = JoinSet::new();
+    taskpool_order.spawn(task_place_order(
         tx_order.subscribe(),
-        cx_order.clone(),
+        tx_order_result.clone(),
+        api.clone(),
+        retry,
+        open_orders.clone(),
+        shutdown.token(ShutdownPhase::CancelCycles),
     ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
+    if config.margin.enabled {
+        taskpool_order.spawn(task_margin(
+            tx_order.subscribe(),
+            tx_order_result.clone(),
+            api.clone(),
+            config.margin.clone(),
+            Arc::new(Mutex::new(Loans::new())),
+            shutdown.token(ShutdownPhase::CancelCycles),
+        ));
+    }
+
+    // infrastructure tasks
+    let mut taskpool_infrastructure: JoinSet<kucoin_arbitrage::error::Result<()>> = JoinSet::new();
+    // only level2 changes are lost for good when dropped, snapshots are replaced by the next one
+    let tx_resync = (orderbook_feed == OrderbookFeed::Level2 && config.behaviour.resync_on_lag)
+        .then(|| {
+            let (tx_resync, rx_resync) = mpsc::unbounded_channel();
+            taskpool_infrastructure.spawn(task_resync_orderbooks(
+                api.clone(),
+                symbol_infos.clone(),
+                tx_orderbook.clone(),
+                rx_resync,
+                shutdown.token(ShutdownPhase::Flush),
+            ));
+            tx_resync
+        });
+    taskpool_infrastructure.spawn(task_sync_orderbook(
+        tx_orderbook.subscribe(),
+        tx_orderbook_best.clone(),
+        full_orderbook.clone(),
+        symbol_infos
+            .iter()
+            .map(|info| (info.symbol.clone(), *info.price_increment))
+            .collect(),
+        tx_resync,
+        shutdown.token(ShutdownPhase::Flush),
+    ));
+    taskpool_infrastructure.spawn(task_track_open_orders(
         tx_trade.subscribe(),
-        cx_trade.clone(),
+        open_orders.clone(),
+        shutdown.token(ShutdownPhase::Flush),
+    ));
+    if let Some(journal) = journal {
+        tracing::info!("Journaling events into {}", config.journal.path);
+        taskpool_infrastructure.spawn(task_journal_events(
+            journal,
+            tx_chance.subscribe(),
+            tx_order.subscribe(),
+            tx_order_result.subscribe(),
+            tx_trade.subscribe(),
+            tx_cycle.subscribe(),
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+    }
+    if let Some(inventory) = inventory.clone() {
+        taskpool_infrastructure.spawn(task_sync_balances(
+            api.clone(),
+            inventory,
+            Duration::from_secs(5),
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+    }
+
+    // monitor tasks
+    let mut taskpool_monitor = JoinSet::new();
+    if let Some(port) = config.behaviour.metrics_port {
+        taskpool_monitor.spawn(task_serve_metrics(
+            SocketAddr::from((config.behaviour.metrics_host, port)),
+            registry::global(),
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+    }
+    if let Some(port) = config.behaviour.status_port {
+        let sources = StatusSources {
+            config: config.clone(),
+            balances: inventory.clone(),
+            open_orders: open_orders.clone(),
+            full_orderbook: full_orderbook.clone(),
+            books_expected: symbol_infos.len(),
+            // one session per orderbook subscription, and the private one
+            sessions_expected: subs.len() + 1,
+            paused: Arc::new(paused),
+            tx_order: tx_order.clone(),
+        };
+        taskpool_monitor.spawn(task_serve_status(
+            SocketAddr::from((config.behaviour.status_host, port)),
+            sources,
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+    }
+    taskpool_monitor.spawn(task_log_latency(
+        monitor_interval as u64,
+        shutdown.token(ShutdownPhase::Flush),
     ));
     taskpool_monitor.spawn(task_log_mps(
-        vec![
-            cx_orderbook.clone(),
-            cx_orderbook_best.clone(),
-            cx_chance.clone(),
-            cx_order.clone(),
-            cx_trade.clone(),
-        ],
+        registry::global(),
         monitor_interval as u64,
+        shutdown.token(ShutdownPhase::Flush),
     ));
 
-    // Initial orderbook states from REST
-    task_get_initial_orderbooks(api.clone(), symbol_infos, full_orderbook).await?;
-    tracing::info!("Aggregated all the symbols");
+    // Initial orderbook states from REST, snapshot feeds do not need one
+    if orderbook_feed == OrderbookFeed::Level2 {
+        let fetching = task_get_initial_orderbooks(api.clone(), symbol_infos, full_orderbook);
+        // the spawned tasks go through the shutdown sequence when interrupted
+        if let Some(res) = unless_shutdown(&shutdown, fetching).await {
+            res?;
+            tracing::info!("Aggregated all the symbols");
+        }
+    }
 
     // websocket subscription tasks
     let mut taskpool_subscription = JoinSet::new();
     // publishes tradeEvent from private API
-    taskpool_subscription.spawn(task_pub_trade_event(api.clone(), tx_trade));
+    taskpool_subscription.spawn(task_pub_trade_event(
+        api.clone(),
+        tx_trade,
+        shutdown.token(ShutdownPhase::Flush),
+    ));
     // publishes OrderBookEvent from public API
     for (i, sub) in subs.iter().enumerate() {
         taskpool_subscription.spawn(task_pub_orderbook_event(
             api.clone(),
             sub.to_vec(),
             tx_orderbook.clone(),
+            shutdown.token(ShutdownPhase::Flush),
         ));
         tracing::info!("{i:?}-th session of WS subscription setup");
     }
 
-    // terminate if any taskpool failed
-    let message: String = tokio::select! {
+    // run until shutdown is requested or any taskpool failed
+    let mut shutdown_requested = shutdown.token(ShutdownPhase::StopChances);
+    let mut failure: Option<String> = tokio::select! {
+        _ = shutdown_requested.cancelled() => None,
+        res = taskpool_chance.join_next() => Some(format!("Chance task pool error [{res:?}]")),
+        res = taskpool_order.join_next() => Some(format!("Order task pool error [{res:?}]")),
         res = taskpool_infrastructure.join_next() =>
-            format!("Infrastructure task pool error [{res:?}]"),
+            Some(format!("Infrastructure task pool error [{res:?}]")),
         res = taskpool_monitor.join_next() =>
-            format!("Monitor task pool error [{res:?}]"),
-        res = taskpool_subscription.join_next() => format!("Subscription task pool error [{res:?}]"),
+            Some(format!("Monitor task pool error [{res:?}]")),
+        res = taskpool_subscription.join_next() => Some(format!("Subscription task pool error [{res:?}]")),
     };
-    eyre::bail!("core error: [{message}]")
+    if let Some(message) = &failure {
+        tracing::error!("{message}, shutting down");
+    }
+
+    // stop generating chances, letting the in-flight cycle finish
+    shutdown.advance(ShutdownPhase::StopChances);
+    join_within(&mut taskpool_chance, grace).await;
+    // abandon the in-flight cycle, then cancel the orders left open
+    shutdown.advance(ShutdownPhase::CancelCycles);
+    join_within(&mut taskpool_chance, grace).await;
+    join_within(&mut taskpool_order, grace).await;
+    // orders possibly left open fail the run even when it was stopped on request
+    if let Err(e) = cancel_open_orders(api, open_orders, retry, grace).await {
+        tracing::error!("check the open orders manually: {e}");
+        failure.get_or_insert(format!("Shutdown cancellation error [{e}]"));
+    }
+    // stop the subscriptions and report the last counts
+    shutdown.advance(ShutdownPhase::Flush);
+    join_within(&mut taskpool_infrastructure, grace).await;
+    join_within(&mut taskpool_subscription, grace).await;
+    join_within(&mut taskpool_monitor, grace).await;
+    shutdown.advance(ShutdownPhase::Exit);
+
+    match failure {
+        Some(message) => eyre::bail!("core error: [{message}]"),
+        None => Ok(()),
+    }
+}
+
+/// runs the startup step, None when the shutdown is requested first
+async fn unless_shutdown<T>(
+    shutdown: &ShutdownCoordinator,
+    step: impl Future<Output = T>,
+) -> Option<T> {
+    let mut shutdown_requested = shutdown.token(ShutdownPhase::StopChances);
+    tokio::select! {
+        _ = shutdown_requested.cancelled() => {
+            tracing::info!("Shutdown requested during startup");
+            None
+        }
+        res = step => Some(res),
+    }
 }
@@ -7,7 +7,7 @@ async fn main() -> Result<()> {
     // provide logging format
     // kucoin_arbitrage::logger::setup_logs(tracing::Leve::INFO, l)?;
     tracing::info!("Hello world");
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
     let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
         .map_err(|e| eyre::eyre!(e))?;
 
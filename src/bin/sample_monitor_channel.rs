@@ -1,15 +1,15 @@
 use eyre::Result;
 /// Executes triangular arbitrage
 use kucoin_api::client::{Kucoin, KucoinEnv};
+use kucoin_arbitrage::broker::orderbook::kucoin::task_pub_orderbook_event;
 use kucoin_arbitrage::broker::symbol::filter::symbol_with_quotes;
 use kucoin_arbitrage::broker::symbol::kucoin::{format_subscription_list, get_symbols};
 use kucoin_arbitrage::event;
-use kucoin_arbitrage::monitor::counter;
-use kucoin_arbitrage::monitor::task::{task_log_mps, task_monitor_channel_mps};
+use kucoin_arbitrage::monitor::registry;
+use kucoin_arbitrage::monitor::task::task_log_mps;
+use kucoin_arbitrage::shutdown::{ShutdownCoordinator, ShutdownPhase};
 use kucoin_arbitrage::system_event::task_signal_handle;
-use std::sync::Arc;
 use tokio::sync::broadcast::channel;
-use tokio::sync::Mutex;
 use tokio::task::JoinSet;
 
 #[tokio::main]
@@ -19,7 +19,7 @@ async fn main() -> Result<()> {
     tracing::info!("Log setup");
 
     // credentials
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
 
     tokio::select! {
         _ = task_signal_handle() => println!("received external signal, terminating program"),
@@ -51,36 +51,26 @@ async fn core(config: kucoin_arbitrage::config::Config) -> Result<()> {
     let subs = format_subscription_list(&symbol_infos);
     tracing::info!("Total orderbook WS sessions: {:?}", subs.len());
 
-    // broadcast channels and counters
-    let cx_orderbook = Arc::new(Mutex::new(counter::Counter::new("orderbook")));
+    // broadcast channel, published messages are counted in the global registry
     let tx_orderbook = channel::<event::orderbook::OrderbookEvent>(1024 * 2).0;
-    let cx_orderbook_best = Arc::new(Mutex::new(counter::Counter::new("best_price")));
-    let tx_orderbook_best = channel::<event::orderbook::OrderbookEvent>(512).0;
-    let cx_chance = Arc::new(Mutex::new(counter::Counter::new("chance")));
-    let tx_chance = channel::<event::chance::ChanceEvent>(64).0;
     tracing::info!("Broadcast channels setup");
 
     // MPS monitor tasks
+    // tasks only stop when this program ends
+    let shutdown = ShutdownCoordinator::new();
     let mut taskpool_monitor = JoinSet::new();
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_orderbook.subscribe(),
-        cx_orderbook.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_orderbook_best.subscribe(),
-        cx_orderbook_best.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_chance.subscribe(),
-        cx_chance.clone(),
-    ));
+    for sub in subs {
+        taskpool_monitor.spawn(task_pub_orderbook_event(
+            api.clone(),
+            sub,
+            tx_orderbook.clone(),
+            shutdown.token(ShutdownPhase::Exit),
+        ));
+    }
     taskpool_monitor.spawn(task_log_mps(
-        vec![
-            cx_orderbook.clone(),
-            cx_orderbook_best.clone(),
-            cx_chance.clone(),
-        ],
+        registry::global(),
         monitor_interval as u64,
+        shutdown.token(ShutdownPhase::Exit),
     ));
 
     // terminate if taskpools failed
@@ -8,7 +8,7 @@ async fn main() -> Result<()> {
     // kucoin_arbitrage::logger::log_init()?;
     tracing::info!("Hello world");
 
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
     let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
         .map_err(|e| eyre::eyre!(e))?;
 
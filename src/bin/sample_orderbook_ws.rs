@@ -9,19 +9,20 @@ use kucoin_api::{
 use kucoin_arbitrage::broker::symbol::filter::symbol_with_quotes;
 use kucoin_arbitrage::broker::symbol::kucoin::get_symbols;
 use kucoin_arbitrage::model::symbol::SymbolInfo;
-use kucoin_arbitrage::monitor::counter;
+use kucoin_arbitrage::monitor::counter::Counter;
+use kucoin_arbitrage::monitor::registry;
+use kucoin_arbitrage::shutdown::{ShutdownCoordinator, ShutdownPhase};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // provide logging format
     // kucoin_arbitrage::logger::log_init()?;
     tracing::info!("Log setup");
-    let counter = Arc::new(Mutex::new(counter::Counter::new("api_input")));
+    let counter = registry::global().counter("api_input", &[]);
 
     // config
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
     let monitor_interval = config.behaviour.monitor_interval_sec;
 
     let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
@@ -54,17 +55,16 @@ async fn main() -> Result<()> {
         tokio::spawn(sync_tickers(ws, counter.clone()));
         tracing::info!("{i:?}-th session of WS subscription setup");
     }
+    let shutdown = ShutdownCoordinator::new();
     let _res = tokio::join!(kucoin_arbitrage::monitor::task::task_log_mps(
-        vec![counter.clone(),],
-        monitor_interval as u64
+        registry::global(),
+        monitor_interval as u64,
+        shutdown.token(ShutdownPhase::Exit)
     ));
     panic!("Program should not arrive here")
 }
 
-async fn sync_tickers(
-    mut ws: KucoinWebsocket,
-    counter: Arc<Mutex<counter::Counter>>,
-) -> Result<()> {
+async fn sync_tickers(mut ws: KucoinWebsocket, counter: Arc<Counter>) -> Result<()> {
     while let Some(msg) = ws.try_next().await.map_err(|e| eyre::eyre!(e))? {
         // add matches for multi-subscribed sockets handling
         match msg {
@@ -76,7 +76,7 @@ async fn sync_tickers(
             }
             KucoinWebsocketMsg::OrderBookMsg(msg) => {
                 let _ = msg.data;
-                counter::increment(counter.clone()).await;
+                counter.increment();
             }
             _ => {
                 panic!("unexpected msgs received: {msg:?}")
@@ -1,3 +1,4 @@
+use kucoin_arbitrage::shutdown::{ShutdownCoordinator, ShutdownPhase};
 use std::sync::Arc;
 
 use eyre::Result;
@@ -5,16 +6,19 @@ use eyre::Result;
 /// Places extreme order in REST, receive extreme order in private channel
 /// Please configure the buy price to either the current market price or lower for testing purpose
 use kucoin_api::client::{Kucoin, KucoinEnv};
-use kucoin_arbitrage::broker::gatekeeper::kucoin::task_gatekeep_chances;
+use kucoin_arbitrage::broker::gatekeeper::kucoin::{task_gatekeep_chances, Funding};
 use kucoin_arbitrage::broker::order::kucoin::task_place_order;
+use kucoin_arbitrage::broker::order::kucoin_code::RetryPolicy;
+use kucoin_arbitrage::broker::risk::internal::TradingState;
 use kucoin_arbitrage::broker::symbol::kucoin::{format_subscription_list, get_symbols};
 use kucoin_arbitrage::broker::trade::kucoin::task_pub_trade_event;
 use kucoin_arbitrage::event::chance::ChanceEvent;
-use kucoin_arbitrage::event::order::OrderEvent;
+use kucoin_arbitrage::event::order::{OrderEvent, OrderResultEvent};
 use kucoin_arbitrage::event::trade::TradeEvent;
 use kucoin_arbitrage::model::chance::{ActionInfo, TriangularArbitrageChance};
-use kucoin_arbitrage::monitor::counter::Counter;
-use kucoin_arbitrage::monitor::task::{task_log_mps, task_monitor_channel_mps};
+use kucoin_arbitrage::model::order::OpenOrders;
+use kucoin_arbitrage::monitor::registry;
+use kucoin_arbitrage::monitor::task::task_log_mps;
 use kucoin_arbitrage::system_event::task_signal_handle;
 use kucoin_arbitrage::{broker::symbol::filter::symbol_with_quotes, monitor};
 use ordered_float::OrderedFloat;
@@ -28,7 +32,14 @@ async fn main() -> Result<()> {
     tracing::info!("Log setup");
 
     // config
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
+    config.validate_private()?;
+    let retry = RetryPolicy::new(
+        config.behaviour.order_max_retries,
+        config.behaviour.order_retry_backoff_ms as u64,
+    );
+    let leg_timeout = tokio::time::Duration::from_secs(config.behaviour.leg_timeout_sec as u64);
+    let taker_fee = config.risk.taker_fee;
 
     let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
         .map_err(|e| eyre::eyre!(e))?;
@@ -47,41 +58,49 @@ async fn main() -> Result<()> {
     tracing::info!("Total orderbook WS sessions: {:?}", subs.len());
 
     // Creates broadcast channels
-    let cx_chance = Arc::new(Mutex::new(Counter::new("chance")));
     let tx_chance = broadcast::channel::<ChanceEvent>(32).0;
-    let cx_order = Arc::new(Mutex::new(Counter::new("order")));
     let tx_order = broadcast::channel::<OrderEvent>(16).0;
-    let cx_trade = Arc::new(Mutex::new(Counter::new("trade")));
+    let tx_order_result = broadcast::channel::<OrderResultEvent>(16).0;
     let tx_trade = broadcast::channel::<TradeEvent>(32).0;
     tracing::info!("Broadcast channels setup");
 
     // monitor tasks
+    // tasks only stop when this program ends
+    let shutdown = ShutdownCoordinator::new();
     let mut taskpool_monitor = JoinSet::new();
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_chance.subscribe(),
-        cx_chance.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_order.subscribe(),
-        cx_order.clone(),
-    ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_trade.subscribe(),
-        cx_trade.clone(),
-    ));
     taskpool_monitor.spawn(task_log_mps(
-        vec![cx_chance.clone(), cx_order.clone(), cx_trade.clone()],
+        registry::global(),
         10,
+        shutdown.token(ShutdownPhase::Exit),
     ));
 
-    let mut taskpool_infrastructure: JoinSet<Result<()>> = JoinSet::new();
-    taskpool_infrastructure.spawn(task_place_order(tx_order.subscribe(), api.clone()));
+    let mut taskpool_infrastructure: JoinSet<kucoin_arbitrage::error::Result<()>> = JoinSet::new();
+    taskpool_infrastructure.spawn(task_place_order(
+        tx_order.subscribe(),
+        tx_order_result.clone(),
+        api.clone(),
+        retry,
+        Arc::new(Mutex::new(OpenOrders::new())),
+        shutdown.token(ShutdownPhase::Exit),
+    ));
     taskpool_infrastructure.spawn(task_gatekeep_chances(
         tx_chance.subscribe(),
         tx_trade.subscribe(),
         tx_order.clone(),
+        tx_order_result.subscribe(),
+        broadcast::channel(16).0,
+        shutdown.token(ShutdownPhase::Exit),
+        shutdown.token(ShutdownPhase::Exit),
+        Funding::default(),
+        leg_timeout,
+        taker_fee,
+        TradingState::default(),
+    ));
+    taskpool_infrastructure.spawn(task_pub_trade_event(
+        api.clone(),
+        tx_trade.clone(),
+        shutdown.token(ShutdownPhase::Exit),
     ));
-    taskpool_infrastructure.spawn(task_pub_trade_event(api.clone(), tx_trade.clone()));
 
     tracing::info!("All application tasks setup");
     monitor::timer::start("order_placement_network".to_string()).await;
@@ -109,6 +128,7 @@ async fn task_pub_chance_periodically(
                 ActionInfo::buy("ETH-BTC".to_string(), OrderedFloat(0.1), OrderedFloat(0.1)),
                 ActionInfo::sell("ETH-USDT".to_string(), OrderedFloat(0.1), OrderedFloat(0.1)),
             ],
+            receive_time: chrono::Utc::now().timestamp_micros() as u64,
         };
         let event = ChanceEvent::AllTaker(chance);
         tx_chance.send(event.clone())?;
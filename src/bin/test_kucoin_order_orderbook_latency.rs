@@ -19,7 +19,8 @@ async fn main() -> Result<()> {
     tracing::info!("Testing Kucoin REST-to-WS latency");
 
     // config
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
+    config.validate_private()?;
 
     // Initialize the Kucoin API struct
     let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
@@ -58,17 +59,13 @@ async fn main() -> Result<()> {
     while let Some(msg) = ws.try_next().await.map_err(|e| eyre::eyre!(e))? {
         match msg {
             KucoinWebsocketMsg::OrderBookMsg(msg) => {
-                let (symbol, data) = msg.data.to_internal(serial);
+                let (symbol, data) = msg.data.to_internal(serial)?;
                 // match symbol
                 if symbol.ne(test_symbol) {
                     continue;
                 }
                 // BTC-USDT now, check bid volume
-                if data
-                    .bid
-                    .get(&ordered_float::OrderedFloat(test_price))
-                    .is_some()
-                {
+                if data.bids.iter().any(|&(price, _)| price == test_price) {
                     // price
                     tracing::info!("data: {:#?}", data);
                     // volume might not be equal, as they are cumulative with other previous orders
@@ -1,3 +1,4 @@
+use kucoin_arbitrage::shutdown::{ShutdownCoordinator, ShutdownPhase};
 use std::sync::Arc;
 
 use eyre::Result;
@@ -6,13 +7,14 @@ use eyre::Result;
 /// Please configure the buy price to either the current market price or lower for testing purpose
 use kucoin_api::client::{Kucoin, KucoinEnv};
 use kucoin_arbitrage::broker::order::kucoin::task_place_order;
+use kucoin_arbitrage::broker::order::kucoin_code::RetryPolicy;
 use kucoin_arbitrage::broker::symbol::kucoin::{format_subscription_list, get_symbols};
 use kucoin_arbitrage::broker::trade::kucoin::task_pub_trade_event;
-use kucoin_arbitrage::event::order::OrderEvent;
+use kucoin_arbitrage::event::order::{OrderEvent, OrderResultEvent};
 use kucoin_arbitrage::event::trade::TradeEvent;
-use kucoin_arbitrage::model::order::{LimitOrder, OrderSide, OrderType};
-use kucoin_arbitrage::monitor::counter::Counter;
-use kucoin_arbitrage::monitor::task::{task_log_mps, task_monitor_channel_mps};
+use kucoin_arbitrage::model::order::{LimitOrder, OpenOrders, OrderSide, OrderType};
+use kucoin_arbitrage::monitor::registry;
+use kucoin_arbitrage::monitor::task::task_log_mps;
 use kucoin_arbitrage::system_event::task_signal_handle;
 use kucoin_arbitrage::{broker::symbol::filter::symbol_with_quotes, monitor};
 use tokio::sync::{broadcast, Mutex};
@@ -26,7 +28,12 @@ async fn main() -> Result<()> {
     tracing::info!("Log setup");
 
     // config
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
+    config.validate_private()?;
+    let retry = RetryPolicy::new(
+        config.behaviour.order_max_retries,
+        config.behaviour.order_retry_backoff_ms as u64,
+    );
 
     let api = Kucoin::new(KucoinEnv::Live, Some(config.kucoin_credentials()))
         .map_err(|e| eyre::eyre!(e))?;
@@ -45,27 +52,35 @@ async fn main() -> Result<()> {
     tracing::info!("Total orderbook WS sessions: {:?}", subs.len());
 
     // Creates broadcast channels
-    let cx_order = Arc::new(Mutex::new(Counter::new("order")));
     let tx_order = broadcast::channel::<OrderEvent>(16).0;
-    let cx_trade = Arc::new(Mutex::new(Counter::new("trade")));
+    let tx_order_result = broadcast::channel::<OrderResultEvent>(16).0;
     let tx_trade = broadcast::channel::<TradeEvent>(128).0;
     tracing::info!("Broadcast channels setup");
 
     // monitor tasks
+    // tasks only stop when this program ends
+    let shutdown = ShutdownCoordinator::new();
     let mut taskpool_monitor = JoinSet::new();
-    taskpool_monitor.spawn(task_monitor_channel_mps(
+    taskpool_monitor.spawn(task_log_mps(
+        registry::global(),
+        10,
+        shutdown.token(ShutdownPhase::Exit),
+    ));
+
+    let mut taskpool_infrastructure: JoinSet<kucoin_arbitrage::error::Result<()>> = JoinSet::new();
+    taskpool_infrastructure.spawn(task_place_order(
         tx_order.subscribe(),
-        cx_order.clone(),
+        tx_order_result.clone(),
+        api.clone(),
+        retry,
+        Arc::new(Mutex::new(OpenOrders::new())),
+        shutdown.token(ShutdownPhase::Exit),
     ));
-    taskpool_monitor.spawn(task_monitor_channel_mps(
-        tx_trade.subscribe(),
-        cx_trade.clone(),
+    taskpool_infrastructure.spawn(task_pub_trade_event(
+        api.clone(),
+        tx_trade.clone(),
+        shutdown.token(ShutdownPhase::Exit),
     ));
-    taskpool_monitor.spawn(task_log_mps(vec![cx_order.clone(), cx_trade.clone()], 10));
-
-    let mut taskpool_infrastructure: JoinSet<Result<()>> = JoinSet::new();
-    taskpool_infrastructure.spawn(task_place_order(tx_order.subscribe(), api.clone()));
-    taskpool_infrastructure.spawn(task_pub_trade_event(api.clone(), tx_trade.clone()));
 
     tracing::info!("All application tasks setup");
     monitor::timer::start("order_placement_network".to_string()).await;
@@ -96,6 +111,7 @@ async fn task_place_order_periodically(
             symbol: "BTC-USDT".to_string(),
             amount: 0.0001.to_string(),
             price: 35000.0.to_string(),
+            ..Default::default()
         });
         tx_order.send(event.clone())?;
         tokio::time::sleep(tokio::time::Duration::from_secs_f64(interval_s)).await;
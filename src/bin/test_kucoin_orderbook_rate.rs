@@ -7,20 +7,21 @@ use kucoin_api::{
     model::websocket::{KucoinWebsocketMsg, WSTopic, WSType},
     websocket::KucoinWebsocket,
 };
-use kucoin_arbitrage::monitor::counter;
+use kucoin_arbitrage::monitor::counter::Counter;
+use kucoin_arbitrage::monitor::registry;
+use kucoin_arbitrage::shutdown::{ShutdownCoordinator, ShutdownPhase};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 /// main function
 #[tokio::main]
 async fn main() -> Result<()> {
     // provide logging format
     // kucoin_arbitrage::logger::log_init()?;
-    let counter = Arc::new(Mutex::new(counter::Counter::new("api_input")));
+    let counter = registry::global().counter("api_input", &[]);
     tracing::info!("Testing Kucoin WS Message Rate");
 
     // config
-    let config = kucoin_arbitrage::config::from_file("config.toml")?;
+    let config = kucoin_arbitrage::config::load()?;
     let monitor_interval: u32 = config.behaviour.monitor_interval_sec;
 
     // Initialize the Kucoin API struct
@@ -41,22 +42,21 @@ async fn main() -> Result<()> {
 
     tracing::info!("Async polling");
     tokio::spawn(sync_tickers(ws, counter.clone()));
+    let shutdown = ShutdownCoordinator::new();
     let _res = tokio::join!(kucoin_arbitrage::monitor::task::task_log_mps(
-        vec![counter.clone()],
-        monitor_interval as u64
+        registry::global(),
+        monitor_interval as u64,
+        shutdown.token(ShutdownPhase::Exit)
     ));
     panic!("Program should not arrive here")
 }
 
-async fn sync_tickers(
-    mut ws: KucoinWebsocket,
-    counter: Arc<Mutex<counter::Counter>>,
-) -> Result<()> {
+async fn sync_tickers(mut ws: KucoinWebsocket, counter: Arc<Counter>) -> Result<()> {
     while let Some(msg) = ws.try_next().await.map_err(|e| eyre::eyre!(e))? {
         match msg {
             KucoinWebsocketMsg::OrderBookMsg(_msg) => {
                 // TODO make counter more generic
-                counter::reset(counter.clone()).await;
+                counter.reset();
             }
             KucoinWebsocketMsg::PongMsg(_) => continue,
             KucoinWebsocketMsg::WelcomeMsg(_) => continue,
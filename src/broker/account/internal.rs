@@ -0,0 +1,111 @@
+use crate::broker::risk::internal::TradingState;
+use crate::config::InventoryConfig;
+use crate::error::Result;
+use crate::event::order::OrderEvent;
+use crate::model::inventory::{rebalances, Balances};
+use crate::model::order::{MarketOrder, OrderSide};
+use crate::model::orderbook::{FullOrderbook, OrderbookBackend};
+use crate::model::symbol::SymbolInfo;
+use crate::monitor::status;
+use crate::shutdown::ShutdownToken;
+use crate::strategy::all_taker_btc_usd::adjust_amount;
+use crate::strings::symbol_to_string;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::broadcast::Sender;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+use uuid::Uuid;
+
+/// Task to restore the target inventory with market orders sent through the risk manager (tx_rebalance),
+/// idle while trading is blocked or a cycle is in flight
+pub async fn task_rebalance_inventory(
+    tx_rebalance: Sender<OrderEvent>,
+    balances: Arc<Mutex<Balances>>,
+    full_orderbook: Arc<Mutex<FullOrderbook>>,
+    symbol_map: BTreeMap<String, SymbolInfo>,
+    config: InventoryConfig,
+    trading: TradingState,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    let interval = Duration::from_secs(config.rebalance_interval_sec as u64);
+    let board = status::global();
+    let mut rebalanced: Option<Balances> = None;
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            _ = time::sleep(interval) => {}
+        }
+        if let Some(state) = trading.blocked() {
+            tracing::debug!("Not rebalancing: trading {state}");
+            continue;
+        }
+        if !board.cycles().is_empty() {
+            tracing::debug!("Not rebalancing: cycle in flight");
+            continue;
+        }
+        let balances = balances.lock().await.clone();
+        if balances.is_empty() || rebalanced.as_ref() == Some(&balances) {
+            // not synced yet, or not refreshed since the last rebalance
+            continue;
+        }
+        let trades = rebalances(
+            &balances,
+            &config.targets,
+            &config.rebalance_quote,
+            config.drift_threshold,
+        );
+        // spent by the buys of this round
+        let mut quote = balances
+            .get(&config.rebalance_quote)
+            .copied()
+            .unwrap_or_default();
+        for trade in trades {
+            let symbol = symbol_to_string(&trade.currency, &config.rebalance_quote);
+            let Some(info) = symbol_map.get(&symbol) else {
+                tracing::warn!(
+                    "Cannot rebalance [{}], [{symbol}] not traded",
+                    trade.currency
+                );
+                continue;
+            };
+            let (available, ask) = match trade.side {
+                OrderSide::Buy => {
+                    let ask = full_orderbook
+                        .lock()
+                        .await
+                        .get(&symbol)
+                        .and_then(|orderbook| orderbook.best_ask());
+                    let Some((ask, _)) = ask else {
+                        tracing::warn!(
+                            "Cannot rebalance [{}], no ask on [{symbol}]",
+                            trade.currency
+                        );
+                        continue;
+                    };
+                    (quote / ask, ask)
+                }
+                OrderSide::Sell => (trade.amount, 0.0),
+            };
+            let size = adjust_amount(
+                trade.amount,
+                *info.base_min,
+                *info.base_increment,
+                available,
+            );
+            if size <= 0.0 {
+                continue;
+            }
+            quote -= size * ask;
+            tracing::info!("Rebalancing: {} {size} of [{symbol}]", trade.side);
+            let order = MarketOrder::with_size(
+                &Uuid::new_v4().to_string(),
+                trade.side,
+                &symbol,
+                &format!("{size:.9}"),
+            );
+            tx_rebalance.send(OrderEvent::PlaceMarketOrder(order))?;
+        }
+        rebalanced = Some(balances);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::error::{Error, Result};
+use crate::model::inventory::Balances;
+use crate::shutdown::ShutdownToken;
+use kucoin_api::client::Kucoin;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+
+/// Obtains the available balance of each currency in the trade account
+pub async fn get_balances(api: Kucoin) -> Result<Balances> {
+    let res = api.get_accounts_list(None, Some("trade")).await?;
+    Error::check_kucoin_code(&res.code, res.msg.as_deref())?;
+    let mut balances = Balances::new();
+    for account in res.data.unwrap_or_default() {
+        match account.available.parse::<f64>() {
+            Ok(available) => {
+                balances.insert(account.currency, available);
+            }
+            Err(_) => tracing::warn!(
+                "skipping invalid balance [{}] of [{}]",
+                account.available,
+                account.currency
+            ),
+        }
+    }
+    Ok(balances)
+}
+
+/// Task to refresh the balances periodically, keeping the previous ones on failure
+pub async fn task_sync_balances(
+    api: Kucoin,
+    balances: Arc<Mutex<Balances>>,
+    interval: Duration,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    loop {
+        match get_balances(api.clone()).await {
+            Ok(latest) => *balances.lock().await = latest,
+            Err(e) => tracing::warn!("failed refreshing balances: {e}"),
+        }
+        tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            _ = time::sleep(interval) => {}
+        }
+    }
+}
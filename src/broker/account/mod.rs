@@ -0,0 +1,4 @@
+/// Inventory rebalancing
+pub mod internal;
+/// Account balances using KuCoin private REST API
+pub mod kucoin;
@@ -1,9 +1,25 @@
+use crate::broker::risk::internal::TradingState;
+use crate::error::{Error, Result};
 use crate::event::chance::ChanceEvent;
-use crate::event::order::OrderEvent;
+use crate::event::cycle::{CycleEvent, CycleResult};
+use crate::event::order::{OrderEvent, OrderResultEvent};
 use crate::event::trade::TradeEvent;
-use crate::model::order::{LimitOrder, OrderType};
-use eyre::Result;
+use crate::model::chance::ActionInfo;
+use crate::model::cycle::CycleId;
+use crate::model::inventory::{add_fee, add_fill, costs, covers, spent, Balances};
+use crate::model::margin::BorrowOrder;
+use crate::model::order::{LimitOrder, OrderType, TimeInForce};
+use crate::model::trade::TradeInfo;
+use crate::monitor::channel::Subscriber;
+use crate::monitor::latency::{self, Stage};
+use crate::monitor::registry;
+use crate::monitor::status;
+use crate::shutdown::ShutdownToken;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use tokio::sync::broadcast::{Receiver, Sender};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
 use uuid::Uuid;
 
 // TODO implement when all_taker_btc_usdt is done
@@ -15,75 +31,654 @@ use uuid::Uuid;
 /// - transaction formatted to tradeable digits
 /// - 45 orders per 3 seconds
 /// - 200 active order at once
+///
+/// Legs are placed at once when funding covers all of them, one after the other otherwise,
+/// and fail the cycle when not filled within leg_timeout. Fills are booked less the taker_fee.
+#[allow(clippy::too_many_arguments)]
 pub async fn task_gatekeep_chances(
-    mut rx_chance: Receiver<ChanceEvent>,
-    mut rx_trade: Receiver<TradeEvent>,
+    rx_chance: Receiver<ChanceEvent>,
+    rx_trade: Receiver<TradeEvent>,
     tx_order: Sender<OrderEvent>,
+    rx_order_result: Receiver<OrderResultEvent>,
+    tx_cycle: Sender<CycleEvent>,
+    mut stop_chances: ShutdownToken,
+    mut cancel_cycles: ShutdownToken,
+    funding: Funding,
+    leg_timeout: Duration,
+    taker_fee: f64,
+    trading: TradingState,
 ) -> Result<()> {
+    let mut rx_chance = Subscriber::new(rx_chance, "chance", "gatekeeper");
+    let mut rx_trade = Subscriber::new(rx_trade, "trade", "gatekeeper");
+    let mut rx_order_result = Subscriber::new(rx_order_result, "order_result", "gatekeeper");
+    let board = status::global();
     loop {
-        let status = rx_chance
-            .recv()
-            .await
-            .map(|e| eyre::bail!("gatekeep chance parsing error {e:?}"))?;
-        let event: ChanceEvent = status.unwrap();
-        // TODO timeout mechanism
+        let event: ChanceEvent = tokio::select! {
+            _ = stop_chances.cancelled() => return Ok(()),
+            event = rx_chance.recv() => event?,
+        };
+        if let Some(state) = trading.blocked() {
+            tracing::info!("Dropping queued chance: trading {state}");
+            continue;
+        }
         match event {
             ChanceEvent::AllTaker(chance) => {
-                tracing::info!("All taker chance found!");
+                // shared by the client order ids of the legs
+                let cycle = CycleId::new();
+                tracing::info!("All taker chance found, cycle [{cycle}]");
                 tracing::info!("profit: {}", chance.profit);
                 for action in &chance.actions {
                     tracing::info!("{action:?}");
                 }
+                let symbols: Vec<String> =
+                    chance.actions.iter().map(|a| a.ticker.clone()).collect();
+                board.start_cycle(cycle, symbols.clone());
+                let currency = spent(&chance.actions[0])
+                    .map(|(currency, _)| currency)
+                    .unwrap_or_default();
+                let mut result = CycleResult {
+                    id: Some(cycle),
+                    started: chrono::Utc::now(),
+                    costs: costs(&currency, &chance.actions),
+                    currency,
+                    symbols,
+                    flows: BTreeMap::new(),
+                };
+                // symbol and reason of the leg the cycle failed at
+                let mut failure: Option<(String, String)> = None;
+                let balances = match &funding.balances {
+                    Some(balances) => balances.lock().await.clone(),
+                    None => Balances::new(),
+                };
+                if funding.simultaneous && covers(&balances, &chance.actions) {
+                    let legs: Vec<LimitOrder> = chance
+                        .actions
+                        .iter()
+                        .enumerate()
+                        .map(|(i, action)| leg_order(cycle.leg_oid(i), action, false))
+                        .collect();
+                    // client_oid to the action of the legs not filled, rejected or canceled yet
+                    let mut pending: HashMap<String, usize> = legs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, leg)| (leg.id.clone(), i))
+                        .collect();
+                    let mut filled = [0.0; 3];
+                    latency::record_since(Stage::OrderSend, chance.receive_time);
+                    tx_order.send(OrderEvent::PlaceBatch(legs.clone()))?;
+                    let deadline = Instant::now() + leg_timeout;
+                    while !pending.is_empty() {
+                        let trade_event = tokio::select! {
+                            _ = tokio::time::sleep_until(deadline) => {
+                                tracing::warn!("Legs unresolved after {leg_timeout:?}, canceling them");
+                                for &i in pending.values() {
+                                    tx_order.send(OrderEvent::CancelOrder(legs[i].clone()))?;
+                                    failure.get_or_insert((
+                                        chance.actions[i].ticker.clone(),
+                                        "timeout".to_string(),
+                                    ));
+                                }
+                                break;
+                            }
+                            _ = cancel_cycles.cancelled() => {
+                                tracing::warn!("Shutting down, abandoning simultaneous cycle");
+                                let symbol = pending
+                                    .values()
+                                    .next()
+                                    .map(|i| chance.actions[*i].ticker.clone())
+                                    .unwrap_or_default();
+                                failure.get_or_insert((symbol, "shutdown".to_string()));
+                                break;
+                            }
+                            trade_event = rx_trade.recv() => trade_event?,
+                            order_result = rx_order_result.recv() => {
+                                match order_result? {
+                                    OrderResultEvent::Accepted { client_oid, exchange_oid, latency, .. }
+                                        if pending.contains_key(&client_oid) =>
+                                    {
+                                        tracing::info!("Leg accepted [{exchange_oid}] in {latency:?}");
+                                    }
+                                    OrderResultEvent::Rejected { client_oid, reason }
+                                        if pending.contains_key(&client_oid) =>
+                                    {
+                                        tracing::error!("Leg rejected [{client_oid}]: {reason}");
+                                        let action = &chance.actions[pending[&client_oid]];
+                                        failure.get_or_insert((action.ticker.clone(), reason));
+                                        pending.remove(&client_oid);
+                                    }
+                                    _ => {}
+                                }
+                                continue;
+                            }
+                        };
+                        match trade_event {
+                            TradeEvent::TradeMatch(info)
+                                if pending.contains_key(&info.client_oid) =>
+                            {
+                                let i = pending[&info.client_oid];
+                                book_fill(
+                                    &mut result,
+                                    &chance.actions[i],
+                                    &mut filled[i],
+                                    &info,
+                                    taker_fee,
+                                )?;
+                            }
+                            TradeEvent::TradeFilled(info)
+                                if pending.contains_key(&info.client_oid) =>
+                            {
+                                let i = pending[&info.client_oid];
+                                book_fill(
+                                    &mut result,
+                                    &chance.actions[i],
+                                    &mut filled[i],
+                                    &info,
+                                    taker_fee,
+                                )?;
+                                tracing::info!(
+                                    "Filled [{}/{}] of {:?}",
+                                    filled[i],
+                                    chance.actions[i].volume,
+                                    info.symbol
+                                );
+                                pending.remove(&info.client_oid);
+                            }
+                            TradeEvent::TradeCanceled(info)
+                                if pending.contains_key(&info.client_oid) =>
+                            {
+                                // IOC remainder expired, the cycle is left unbalanced
+                                let i = pending[&info.client_oid];
+                                book_fill(
+                                    &mut result,
+                                    &chance.actions[i],
+                                    &mut filled[i],
+                                    &info,
+                                    taker_fee,
+                                )?;
+                                tracing::warn!(
+                                    "Leg canceled [{}] at [{}/{}]",
+                                    info.client_oid,
+                                    filled[i],
+                                    chance.actions[i].volume
+                                );
+                                failure.get_or_insert((
+                                    chance.actions[i].ticker.clone(),
+                                    "canceled".to_string(),
+                                ));
+                                pending.remove(&info.client_oid);
+                            }
+                            _ => {}
+                        }
+                    }
+                    if failure.is_none() {
+                        tracing::info!("cycle [{cycle}] placed simultaneously!")
+                    }
+                    board.end_cycle(cycle);
+                    publish_cycle(&tx_cycle, result, failure);
+                    continue;
+                }
+                // the cycle runs on margin when the trade account lacks the currency spent by the
+                // first leg, the margin task borrowing what the margin account lacks of it
+                let mut borrowed = None;
+                if funding.borrow {
+                    if let Some((currency, amount)) = spent(&chance.actions[0]) {
+                        if balances.get(&currency).copied().unwrap_or_default() < amount {
+                            let order = BorrowOrder {
+                                id: Uuid::new_v4().to_string(),
+                                currency: currency.clone(),
+                                size: format!("{amount:.9}"),
+                            };
+                            let order_id = order.id.clone();
+                            tx_order.send(OrderEvent::PlaceBorrowOrder(order))?;
+                            let accepted =
+                                wait_result(&mut rx_order_result, &mut cancel_cycles, &order_id)
+                                    .await?;
+                            if !accepted {
+                                tracing::warn!("Skipping chance, failed borrowing [{currency}]");
+                                board.end_cycle(cycle);
+                                continue;
+                            }
+                            borrowed = Some(currency);
+                        }
+                    }
+                }
                 // i is [0, 1, 2]
-                for i in 0..3 {
-                    let uuid = Uuid::new_v4();
-                    // TODO check if the is any problem with the DP format with API
-                    let order: LimitOrder = LimitOrder {
-                        id: uuid.to_string(),
-                        order_type: OrderType::Limit,
-                        side: chance.actions[i].action,
-                        symbol: chance.actions[i].ticker.clone(),
-                        amount: format!("{:.9}", chance.actions[i].volume),
-                        price: format!("{:.9}", chance.actions[i].price),
-                    };
-                    tx_order.send(OrderEvent::PlaceLimitOrder(order))?;
-                    let fill_target = chance.actions[i].price.0;
+                'legs: for i in 0..3 {
+                    let order = leg_order(cycle.leg_oid(i), &chance.actions[i], borrowed.is_some());
+                    let order_id = order.id.clone();
+                    if i == 0 {
+                        latency::record_since(Stage::OrderSend, chance.receive_time);
+                    }
+                    let sent = Instant::now();
+                    tx_order.send(OrderEvent::PlaceLimitOrder(order.clone()))?;
+                    let fill_target = chance.actions[i].volume.0;
                     let mut fill_cumulative = 0.0;
-                    while fill_cumulative < fill_target {
+                    loop {
                         tracing::info!("Waiting for TradeInfo from KuCoin server");
-                        let trade_event = rx_trade.recv().await?;
+                        let trade_event = tokio::select! {
+                            _ = tokio::time::sleep_until(sent + leg_timeout) => {
+                                tracing::warn!("Leg unresolved after {leg_timeout:?}, canceling it");
+                                tx_order.send(OrderEvent::CancelOrder(order))?;
+                                failure = Some((chance.actions[i].ticker.clone(), "timeout".to_string()));
+                                break 'legs;
+                            }
+                            _ = cancel_cycles.cancelled() => {
+                                tracing::warn!("Shutting down, abandoning cycle at leg {i}");
+                                failure = Some((chance.actions[i].ticker.clone(), "shutdown".to_string()));
+                                break 'legs;
+                            }
+                            trade_event = rx_trade.recv() => trade_event?,
+                            order_result = rx_order_result.recv() => {
+                                match order_result? {
+                                    OrderResultEvent::Accepted { client_oid, exchange_oid, latency, .. }
+                                        if client_oid == order_id =>
+                                    {
+                                        tracing::info!("Order accepted [{exchange_oid}] in {latency:?}");
+                                    }
+                                    OrderResultEvent::Rejected { client_oid, reason }
+                                        if client_oid == order_id =>
+                                    {
+                                        tracing::error!("Order rejected, aborting cycle: {reason}");
+                                        failure = Some((chance.actions[i].ticker.clone(), reason));
+                                        break 'legs;
+                                    }
+                                    _ => {}
+                                }
+                                continue;
+                            }
+                        };
                         match trade_event {
-                            TradeEvent::TradeFilled(info) => {
-                                if info.order_id.eq(&uuid.as_u128()) {
-                                    // TODO use actual data to deduct the amount_untraded
-                                    let fill_size: f64 = info.size.parse()?;
-                                    fill_cumulative += fill_size;
-                                    tracing::info!(
-                                        "Filled [{fill_cumulative}/{fill_target}] of {:?}",
-                                        info.symbol
-                                    );
+                            TradeEvent::TradeMatch(info) if info.client_oid == order_id => {
+                                if fill_cumulative == 0.0 {
+                                    latency::record(Stage::Fill, sent.elapsed());
                                 }
+                                book_fill(
+                                    &mut result,
+                                    &chance.actions[i],
+                                    &mut fill_cumulative,
+                                    &info,
+                                    taker_fee,
+                                )?;
                             }
-                            TradeEvent::TradeCanceled(info) => {
-                                if info.order_id.eq(&uuid.as_u128()) {
-                                    tracing::warn!("Trade got canceled [{}]", info.order_id);
-                                    break;
+                            TradeEvent::TradeFilled(info) if info.client_oid == order_id => {
+                                if fill_cumulative == 0.0 {
+                                    latency::record(Stage::Fill, sent.elapsed());
                                 }
+                                book_fill(
+                                    &mut result,
+                                    &chance.actions[i],
+                                    &mut fill_cumulative,
+                                    &info,
+                                    taker_fee,
+                                )?;
+                                tracing::info!(
+                                    "Filled [{fill_cumulative}/{fill_target}] of {:?}",
+                                    info.symbol
+                                );
+                                break;
+                            }
+                            TradeEvent::TradeCanceled(info) if info.client_oid == order_id => {
+                                // IOC remainder expired, the next leg has nothing to spend
+                                book_fill(
+                                    &mut result,
+                                    &chance.actions[i],
+                                    &mut fill_cumulative,
+                                    &info,
+                                    taker_fee,
+                                )?;
+                                tracing::warn!(
+                                    "Leg canceled [{}] at [{fill_cumulative}/{fill_target}], aborting cycle",
+                                    info.client_oid
+                                );
+                                failure = Some((
+                                    chance.actions[i].ticker.clone(),
+                                    "canceled".to_string(),
+                                ));
+                                break 'legs;
                             }
                             other => {
                                 // print for debugging purpose
-                                if let TradeEvent::TradeMatch(info) = other {
-                                    tracing::info!("Ignoring TradeMatch[{}]", info.order_id);
-                                } else {
-                                    tracing::info!("Ignoring [{other:?}]");
-                                }
+                                tracing::info!("Ignoring [{other:?}]");
                             }
                         }
                     }
                 }
-                tracing::info!("cycle completed!")
+                if failure.is_none() {
+                    tracing::info!("cycle [{cycle}] completed!")
+                }
+                board.end_cycle(cycle);
+                publish_cycle(&tx_cycle, result, failure);
+                // the cycle ends in the currency it started with
+                if let Some(currency) = borrowed {
+                    tx_order.send(OrderEvent::RepayLoans(currency))?;
+                }
             }
             ChanceEvent::MakerTakerTaker(_actions) => {}
         }
     }
 }
+
+/// How the legs of a cycle are funded, sequentially from the trade account by default
+#[derive(Debug, Clone, Default)]
+pub struct Funding {
+    /// balances synced from the account, None when not tracked
+    pub balances: Option<Arc<Mutex<Balances>>>,
+    /// places the legs at once when the balances cover every leg
+    pub simultaneous: bool,
+    /// runs the cycle on margin when the currency spent by the first leg is not held, borrowing
+    /// what the margin account lacks of it, repaid on completion
+    pub borrow: bool,
+}
+
+/// Publishes the outcome of a cycle, not subscribed when risk is not managed
+fn publish_cycle(
+    tx_cycle: &Sender<CycleEvent>,
+    result: CycleResult,
+    failure: Option<(String, String)>,
+) {
+    registry::global()
+        .gauge("realised_pnl", &[])
+        .add(result.pnl());
+    let event = match failure {
+        None => CycleEvent::Completed(result),
+        Some((symbol, reason)) => CycleEvent::Failed {
+            result,
+            symbol,
+            reason,
+        },
+    };
+    let _ = tx_cycle.send(event);
+}
+
+/// Books the size executed since the last update of the leg, reported cumulatively by the exchange,
+/// at the match price when reported and the limit price otherwise
+fn book_fill(
+    result: &mut CycleResult,
+    action: &ActionInfo,
+    booked: &mut f64,
+    info: &TradeInfo,
+    fee_rate: f64,
+) -> Result<()> {
+    let filled_size: f64 = info
+        .filled_size
+        .parse()
+        .map_err(|_| Error::Parse(format!("invalid filled size {}", info.filled_size)))?;
+    // updates may arrive out of order, an older one executes nothing new
+    let executed = filled_size - *booked;
+    if executed > 0.0 {
+        *booked = filled_size;
+        let mut executed_at = action.clone();
+        if let Some(price) = &info.match_price {
+            executed_at.price = price
+                .parse()
+                .map_err(|_| Error::Parse(format!("invalid match price {price}")))?;
+        }
+        add_fill(&mut result.flows, &executed_at, executed);
+        add_fee(&mut result.flows, &executed_at, executed, fee_rate);
+    }
+    Ok(())
+}
+
+/// Waits for the result of the order, false when rejected or shutting down
+async fn wait_result(
+    rx_order_result: &mut Subscriber<OrderResultEvent>,
+    cancel_cycles: &mut ShutdownToken,
+    order_id: &str,
+) -> Result<bool> {
+    loop {
+        let order_result = tokio::select! {
+            _ = cancel_cycles.cancelled() => return Ok(false),
+            order_result = rx_order_result.recv() => order_result?,
+        };
+        match order_result {
+            OrderResultEvent::Accepted { client_oid, .. } if client_oid == order_id => {
+                return Ok(true)
+            }
+            OrderResultEvent::Rejected { client_oid, .. } if client_oid == order_id => {
+                return Ok(false)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Taker order of a leg, never resting on the book
+fn leg_order(id: String, action: &ActionInfo, margin: bool) -> LimitOrder {
+    // TODO check if the is any problem with the DP format with API
+    LimitOrder {
+        id,
+        order_type: OrderType::Limit,
+        side: action.action,
+        symbol: action.ticker.clone(),
+        amount: format!("{:.9}", action.volume),
+        price: format!("{:.9}", action.price),
+        time_in_force: TimeInForce::IOC,
+        margin,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{task_gatekeep_chances, Funding};
+    use crate::broker::risk::internal::TradingState;
+    use crate::event::chance::ChanceEvent;
+    use crate::event::cycle::CycleEvent;
+    use crate::event::order::{OrderEvent, OrderResultEvent};
+    use crate::event::trade::TradeEvent;
+    use crate::model::chance::{ActionInfo, TriangularArbitrageChance};
+    use crate::model::inventory::Balances;
+    use crate::model::order::{LimitOrder, OrderType};
+    use crate::model::trade::TradeInfo;
+    use crate::shutdown::{ShutdownCoordinator, ShutdownPhase};
+    use ordered_float::OrderedFloat;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast::{channel, Receiver, Sender};
+    use tokio::sync::Mutex;
+    use tokio::time::timeout;
+
+    /// channels of a running gatekeeper
+    struct Gatekeeper {
+        tx_chance: Sender<ChanceEvent>,
+        tx_trade: Sender<TradeEvent>,
+        rx_order: Receiver<OrderEvent>,
+        tx_order_result: Sender<OrderResultEvent>,
+        rx_cycle: Receiver<CycleEvent>,
+        _shutdown: ShutdownCoordinator,
+    }
+
+    fn spawn(funding: Funding) -> Gatekeeper {
+        let (tx_chance, rx_chance) = channel(4);
+        let (tx_trade, rx_trade) = channel(16);
+        let (tx_order, rx_order) = channel(16);
+        let (tx_order_result, rx_order_result) = channel(16);
+        let (tx_cycle, rx_cycle) = channel(4);
+        let shutdown = ShutdownCoordinator::new();
+        tokio::spawn(task_gatekeep_chances(
+            rx_chance,
+            rx_trade,
+            tx_order,
+            rx_order_result,
+            tx_cycle,
+            shutdown.token(ShutdownPhase::StopChances),
+            shutdown.token(ShutdownPhase::CancelCycles),
+            funding,
+            Duration::from_millis(200),
+            0.001,
+            TradingState::default(),
+        ));
+        Gatekeeper {
+            tx_chance,
+            tx_trade,
+            rx_order,
+            tx_order_result,
+            rx_cycle,
+            _shutdown: shutdown,
+        }
+    }
+
+    /// USDT -> BTC -> ETH -> USDT
+    fn chance() -> ChanceEvent {
+        ChanceEvent::AllTaker(TriangularArbitrageChance {
+            profit: OrderedFloat(0.1),
+            actions: [
+                ActionInfo::buy(
+                    "BTC-USDT".into(),
+                    OrderedFloat(20000.0),
+                    OrderedFloat(0.001),
+                ),
+                ActionInfo::buy("ETH-BTC".into(), OrderedFloat(0.05), OrderedFloat(0.02)),
+                ActionInfo::sell("ETH-USDT".into(), OrderedFloat(1001.0), OrderedFloat(0.02)),
+            ],
+            receive_time: 0,
+        })
+    }
+
+    /// update of the leg with filled_size executed so far
+    fn trade(order: &LimitOrder, filled_size: &str) -> TradeInfo {
+        TradeInfo {
+            client_oid: order.id.clone(),
+            cycle_id: None,
+            symbol: order.symbol.clone(),
+            side: order.side,
+            order_type: OrderType::Limit,
+            size: order.amount.clone(),
+            filled_size: filled_size.to_string(),
+            match_price: None,
+        }
+    }
+
+    async fn next_leg(rx_order: &mut Receiver<OrderEvent>) -> LimitOrder {
+        match timeout(Duration::from_secs(1), rx_order.recv()).await {
+            Ok(Ok(OrderEvent::PlaceLimitOrder(order))) => order,
+            other => panic!("expected a leg, got {other:?}"),
+        }
+    }
+
+    /// holding every currency spent by the legs, so that they are placed at once
+    fn simultaneous() -> Funding {
+        let balances = Balances::from([
+            ("USDT".to_string(), 100.0),
+            ("BTC".to_string(), 1.0),
+            ("ETH".to_string(), 1.0),
+        ]);
+        Funding {
+            balances: Some(Arc::new(Mutex::new(balances))),
+            simultaneous: true,
+            borrow: false,
+        }
+    }
+
+    async fn next_batch(rx_order: &mut Receiver<OrderEvent>) -> Vec<LimitOrder> {
+        match timeout(Duration::from_secs(1), rx_order.recv()).await {
+            Ok(Ok(OrderEvent::PlaceBatch(legs))) => legs,
+            other => panic!("expected the legs at once, got {other:?}"),
+        }
+    }
+
+    async fn next_cycle(rx_cycle: &mut Receiver<CycleEvent>) -> CycleEvent {
+        timeout(Duration::from_secs(1), rx_cycle.recv())
+            .await
+            .expect("cycle not resolved")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unfilled_ioc_leg_canceled() {
+        let mut gatekeeper = spawn(Funding::default());
+        gatekeeper.tx_chance.send(chance()).unwrap();
+        let leg = next_leg(&mut gatekeeper.rx_order).await;
+        gatekeeper
+            .tx_order_result
+            .send(OrderResultEvent::Accepted {
+                client_oid: leg.id.clone(),
+                exchange_oid: "exchange".to_string(),
+                latency: Duration::ZERO,
+                attempts: 1,
+            })
+            .unwrap();
+        // IOC expired without any match
+        gatekeeper
+            .tx_trade
+            .send(TradeEvent::TradeCanceled(trade(&leg, "0")))
+            .unwrap();
+        let CycleEvent::Failed {
+            symbol,
+            reason,
+            result,
+        } = next_cycle(&mut gatekeeper.rx_cycle).await
+        else {
+            panic!("cycle completed without its first leg");
+        };
+        assert_eq!((symbol.as_str(), reason.as_str()), ("BTC-USDT", "canceled"));
+        assert!(result.flows.is_empty());
+        // the next legs are never placed
+        assert!(gatekeeper.rx_order.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_partial_leg_books_executed_size() {
+        let mut gatekeeper = spawn(Funding::default());
+        gatekeeper.tx_chance.send(chance()).unwrap();
+        let leg = next_leg(&mut gatekeeper.rx_order).await;
+        for event in [
+            TradeEvent::TradeMatch(trade(&leg, "0.0003")),
+            TradeEvent::TradeMatch(trade(&leg, "0.0004")),
+            // IOC remainder expired after the matches
+            TradeEvent::TradeCanceled(trade(&leg, "0.0004")),
+        ] {
+            gatekeeper.tx_trade.send(event).unwrap();
+        }
+        let CycleEvent::Failed { result, .. } = next_cycle(&mut gatekeeper.rx_cycle).await else {
+            panic!("cycle completed with a partial first leg");
+        };
+        assert!((result.flows["BTC"] - 0.0004).abs() < 1e-12);
+        assert!((result.flows["USDT"] + 8.008).abs() < 1e-9);
+        // the BTC bought is held at its cost, only the fee is realised
+        assert!((result.pnl() + 0.008).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_leg_canceled() {
+        let mut gatekeeper = spawn(simultaneous());
+        gatekeeper.tx_chance.send(chance()).unwrap();
+        let legs = next_batch(&mut gatekeeper.rx_order).await;
+        for leg in &legs[..2] {
+            gatekeeper
+                .tx_trade
+                .send(TradeEvent::TradeFilled(trade(leg, &leg.amount)))
+                .unwrap();
+        }
+        gatekeeper
+            .tx_trade
+            .send(TradeEvent::TradeCanceled(trade(&legs[2], "0")))
+            .unwrap();
+        let CycleEvent::Failed { symbol, reason, .. } = next_cycle(&mut gatekeeper.rx_cycle).await
+        else {
+            panic!("cycle completed without its last leg");
+        };
+        assert_eq!((symbol.as_str(), reason.as_str()), ("ETH-USDT", "canceled"));
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_legs_timeout() {
+        let mut gatekeeper = spawn(simultaneous());
+        gatekeeper.tx_chance.send(chance()).unwrap();
+        let legs = next_batch(&mut gatekeeper.rx_order).await;
+        gatekeeper
+            .tx_trade
+            .send(TradeEvent::TradeFilled(trade(&legs[0], &legs[0].amount)))
+            .unwrap();
+        let CycleEvent::Failed { reason, .. } = next_cycle(&mut gatekeeper.rx_cycle).await else {
+            panic!("cycle completed without its last legs");
+        };
+        assert_eq!(reason, "timeout");
+        let mut canceled = Vec::new();
+        while let Ok(OrderEvent::CancelOrder(order)) = gatekeeper.rx_order.try_recv() {
+            canceled.push(order.id);
+        }
+        canceled.sort();
+        assert_eq!(canceled, vec![legs[1].id.clone(), legs[2].id.clone()]);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::broker::order::kucoin_code::{classify, ResponseClass};
+use crate::config::{MarginConfig, MarginMode};
+use crate::error::{Error, Result};
+use crate::event::order::{OrderEvent, OrderResultEvent};
+use crate::model::margin::{accrued_interest, outstanding, BorrowOrder, Loan, Loans};
+use crate::monitor::channel::Subscriber;
+use crate::monitor::status;
+use crate::shutdown::ShutdownToken;
+use kucoin_api::client::Kucoin;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::{self, Duration, Instant};
+
+/// page size of the outstanding loans, more loans are not expected at once
+const LOAN_PAGE_SIZE: i32 = 50;
+
+/// Obtains the outstanding loans of the cross margin account
+pub async fn get_loans(kucoin: &Kucoin) -> Result<Loans> {
+    let res = kucoin
+        .get_repay_record(None, Some(1), Some(LOAN_PAGE_SIZE))
+        .await?;
+    Error::check_kucoin_code(&res.code, res.msg.as_deref())?;
+    let parse = |field: &str, value: &str| {
+        value
+            .parse::<f64>()
+            .map_err(|_| Error::Parse(format!("invalid loan {field} [{value}]")))
+    };
+    let mut loans = Loans::new();
+    for record in res.data.map(|page| page.items).unwrap_or_default() {
+        let loan = Loan {
+            principal: parse("principal", &record.principal)?,
+            accrued_interest: parse("accrued_interest", &record.accrued_interest)?,
+            liability: parse("liability", &record.liability)?,
+            daily_rate: parse("daily_int_rate", &record.daily_int_rate)?,
+            trade_id: record.trade_id,
+            currency: record.currency,
+        };
+        loans.insert(loan.trade_id.clone(), loan);
+    }
+    Ok(loans)
+}
+
+/// Available balance of the currency in the cross margin account
+pub async fn get_margin_available(kucoin: &Kucoin, currency: &str) -> Result<f64> {
+    let res = kucoin.get_margin_accounts().await?;
+    Error::check_kucoin_code(&res.code, res.msg.as_deref())?;
+    let Some(account) = res
+        .data
+        .into_iter()
+        .flat_map(|accounts| accounts.accounts)
+        .find(|account| account.currency == currency)
+    else {
+        return Ok(0.0);
+    };
+    account.available_balance.parse().map_err(|_| {
+        Error::Parse(format!(
+            "invalid margin balance [{}]",
+            account.available_balance
+        ))
+    })
+}
+
+/// Size matched by a borrow order, IOC borrows may be matched partly
+async fn get_borrowed(kucoin: &Kucoin, order_id: &str) -> Result<f64> {
+    let res = kucoin.get_borrow_order(order_id).await?;
+    Error::check_kucoin_code(&res.code, res.msg.as_deref())?;
+    let filled = res.data.map(|order| order.filled).unwrap_or_default();
+    filled
+        .parse()
+        .map_err(|_| Error::Parse(format!("invalid borrowed size [{filled}]")))
+}
+
+/// Handles the margin OrderEvents within the config limits, replying borrows as order results.
+/// A borrow is only accepted once the margin account holds its whole size, a partial borrow is repaid.
+/// Outstanding loans are refreshed periodically to track the accrued interest.
+pub async fn task_margin(
+    receiver: broadcast::Receiver<OrderEvent>,
+    tx_result: broadcast::Sender<OrderResultEvent>,
+    kucoin: Kucoin,
+    config: MarginConfig,
+    loans: Arc<Mutex<Loans>>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    let mut receiver = Subscriber::new(receiver, "order", "margin");
+    let mut interval = time::interval(Duration::from_secs(config.loan_sync_interval_sec as u64));
+    loop {
+        let event = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            _ = interval.tick() => {
+                sync_loans(&kucoin, &loans).await;
+                continue;
+            }
+            event = receiver.recv() => event?,
+        };
+        match event {
+            OrderEvent::PlaceBorrowOrder(order) => {
+                let (result, partial) = borrow(&kucoin, &config, &loans, &order).await?;
+                if partial || matches!(result, OrderResultEvent::Accepted { .. }) {
+                    sync_loans(&kucoin, &loans).await;
+                }
+                if partial {
+                    repay(&kucoin, &loans, &order.currency).await;
+                }
+                let _ = tx_result.send(result);
+            }
+            OrderEvent::RepayLoans(currency) => {
+                repay(&kucoin, &loans, &currency).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Borrows the shortfall of the margin account within the config limits, only failing on errors no
+/// later borrow could recover from.
+/// Also returns whether the borrow was rejected after borrowing part of its size, left to repay.
+async fn borrow(
+    kucoin: &Kucoin,
+    config: &MarginConfig,
+    loans: &Mutex<Loans>,
+    order: &BorrowOrder,
+) -> Result<(OrderResultEvent, bool)> {
+    let client_oid = order.id.clone();
+    let rejected = |reason: String| {
+        tracing::error!("Borrow rejected [{client_oid}]: {reason}");
+        Ok((
+            OrderResultEvent::Rejected {
+                client_oid: client_oid.clone(),
+                reason,
+            },
+            false,
+        ))
+    };
+    if !config.enabled {
+        return rejected("margin borrowing disabled".to_string());
+    }
+    if config.mode == MarginMode::Isolated {
+        return rejected("isolated margin is not supported by kucoin_api".to_string());
+    }
+    let Ok(required) = order.size.parse::<f64>() else {
+        return rejected(format!("invalid borrow size [{}]", order.size));
+    };
+    let available = match get_margin_available(kucoin, &order.currency).await {
+        Ok(available) => available,
+        Err(e) => return rejected(format!("margin balance unknown: {e}")),
+    };
+    let size = required - available;
+    if size <= 0.0 {
+        tracing::info!("[{}] held on margin, nothing to borrow", order.currency);
+        return Ok((
+            OrderResultEvent::Accepted {
+                client_oid,
+                exchange_oid: String::new(),
+                latency: Duration::ZERO,
+                attempts: 0,
+            },
+            false,
+        ));
+    }
+    let Some(max_borrow) = config.max_borrow.get(&order.currency) else {
+        return rejected(format!("[{}] not allowed to borrow", order.currency));
+    };
+    let liability = outstanding(&*loans.lock().await, &order.currency);
+    if liability + size > *max_borrow {
+        return rejected(format!(
+            "[{}] liability {liability} + {size} above limit {max_borrow}",
+            order.currency
+        ));
+    }
+    let sent = Instant::now();
+    // IOC to never leave a pending borrow above the max rate
+    let res = kucoin
+        .post_borrow_order(
+            &order.currency,
+            "IOC",
+            size,
+            Some(config.max_daily_rate),
+            Some(&config.term),
+        )
+        .await;
+    let latency = sent.elapsed();
+    let status = match res {
+        Ok(status) => status,
+        Err(e) => return rejected(e.to_string()),
+    };
+    match classify(&status.code) {
+        ResponseClass::Success => {
+            let exchange_oid = status.data.map(|resp| resp.order_id).unwrap_or_default();
+            // the legs are sized for the whole shortfall
+            let borrowed = match get_borrowed(kucoin, &exchange_oid).await {
+                Ok(borrowed) => borrowed,
+                Err(e) => {
+                    let (result, _) = rejected(format!("borrow not confirmed: {e}"))?;
+                    return Ok((result, true));
+                }
+            };
+            if size - borrowed > 1e-9 {
+                let (result, _) = rejected(format!("borrowed only {borrowed} of {size}"))?;
+                return Ok((result, borrowed > 0.0));
+            }
+            tracing::info!(
+                "Borrowed {size} [{}] [{client_oid}] -> [{exchange_oid}] in {latency:?}",
+                order.currency
+            );
+            Ok((
+                OrderResultEvent::Accepted {
+                    client_oid,
+                    exchange_oid,
+                    latency,
+                    attempts: 1,
+                },
+                false,
+            ))
+        }
+        ResponseClass::Retryable | ResponseClass::FatalForOrder => rejected(
+            Error::from_kucoin_code(&status.code, status.msg.as_deref())
+                .map(|e| e.to_string())
+                .unwrap_or_default(),
+        ),
+        ResponseClass::FatalForSystem => Err(Error::ExchangeRejected {
+            code: status.code,
+            msg: status.msg.unwrap_or_default(),
+        }),
+    }
+}
+
+/// Repays the outstanding loans of the currency with what the margin account holds, e.g. less
+/// than borrowed after a failed cycle. Loans failed to repay are kept, the shortfall is reported.
+async fn repay(kucoin: &Kucoin, loans: &Mutex<Loans>, currency: &str) {
+    let mut held = match get_margin_available(kucoin, currency).await {
+        Ok(held) => held,
+        Err(e) => {
+            tracing::error!("Not repaying [{currency}], balance unknown: {e}");
+            return;
+        }
+    };
+    let mut loans = loans.lock().await;
+    let due: Vec<Loan> = loans
+        .values()
+        .filter(|loan| loan.currency == currency)
+        .cloned()
+        .collect();
+    for loan in due {
+        let size = loan.liability.min(held);
+        if size <= 0.0 {
+            continue;
+        }
+        match kucoin
+            .repay_single_order(currency, &loan.trade_id, size)
+            .await
+            .map_err(Error::from)
+            .and_then(|status| Error::check_kucoin_code(&status.code, status.msg.as_deref()))
+        {
+            Ok(()) => {
+                tracing::info!("Repaid {size} [{currency}] [{}]", loan.trade_id);
+                held -= size;
+                if size < loan.liability {
+                    if let Some(loan) = loans.get_mut(&loan.trade_id) {
+                        loan.liability -= size;
+                    }
+                } else {
+                    loans.remove(&loan.trade_id);
+                }
+            }
+            Err(e) => tracing::error!("Failed repaying [{}]: {e}", loan.trade_id),
+        }
+    }
+    let shortfall = outstanding(&loans, currency);
+    if shortfall > 0.0 {
+        let message =
+            format!("{shortfall} [{currency}] left to repay, not held in the margin account");
+        tracing::error!("{message}");
+        status::global().record_error("margin", &message);
+    }
+}
+
+/// Refreshes the outstanding loans, keeping the previous ones on failure
+async fn sync_loans(kucoin: &Kucoin, loans: &Mutex<Loans>) {
+    match get_loans(kucoin).await {
+        Ok(latest) => {
+            for (currency, interest) in accrued_interest(&latest) {
+                tracing::info!("Accrued interest [{currency}]: {interest}");
+            }
+            *loans.lock().await = latest;
+        }
+        Err(e) => tracing::warn!("failed refreshing loans: {e}"),
+    }
+}
@@ -0,0 +1,2 @@
+/// Margin borrowing and repayment using KuCoin private REST API
+pub mod kucoin;
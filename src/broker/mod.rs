@@ -1,9 +1,17 @@
+/// Account balances
+pub mod account;
 /// Order placement gatekeeper for API traffic control and risk management against slippage
 pub mod gatekeeper;
+/// Margin borrowing
+pub mod margin;
 /// Order placement
 pub mod order;
 /// Public orderbook sync
 pub mod orderbook;
+/// Startup reconciliation of the orders and positions of a previous run
+pub mod reconcile;
+/// Risk management
+pub mod risk;
 /// Symbol listing
 pub mod symbol;
 /// Private trade sync
@@ -0,0 +1,36 @@
+use crate::error::Result;
+use crate::event::trade::TradeEvent;
+use crate::model::order::OpenOrders;
+use crate::monitor::channel::Subscriber;
+use crate::shutdown::ShutdownToken;
+use std::sync::Arc;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::Mutex;
+
+/// Removes filled or canceled orders from the open orders placed by this process
+pub async fn task_track_open_orders(
+    receiver: Receiver<TradeEvent>,
+    open_orders: Arc<Mutex<OpenOrders>>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    let mut receiver = Subscriber::new(receiver, "trade", "open_orders");
+    loop {
+        let event = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            event = receiver.recv() => event?,
+        };
+        match event {
+            TradeEvent::TradeFilled(info) | TradeEvent::TradeCanceled(info) => {
+                let mut open_orders = open_orders.lock().await;
+                if open_orders.remove(&info.client_oid).is_some() {
+                    tracing::debug!(
+                        "Order closed [{}] ({} open)",
+                        info.client_oid,
+                        open_orders.len()
+                    );
+                }
+            }
+            TradeEvent::TradeOpen(_) | TradeEvent::TradeMatch(_) => {}
+        }
+    }
+}
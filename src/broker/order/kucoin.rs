@@ -1,70 +1,398 @@
-use crate::event::order::OrderEvent;
-use crate::model::order::Order;
-use eyre::Result;
+use crate::broker::order::kucoin_code::{classify, ResponseClass, RetryPolicy};
+use crate::error::{Error, Result};
+use crate::event::order::{OrderEvent, OrderResultEvent};
+use crate::model::order::{LimitOrder, MarketOrder, OpenOrders, Order};
+use crate::monitor::channel::Subscriber;
+use crate::monitor::latency::{self, Stage};
+use crate::monitor::registry;
+use crate::shutdown::ShutdownToken;
+use futures::future::join_all;
 use kucoin_api::client::Kucoin;
-use tokio::sync::broadcast;
-use uuid::Uuid;
+use kucoin_api::error::APIError;
+use kucoin_api::model::{trade::OrderResp, APIDatum, Method};
+use kucoin_api::trade::OrderOptionals;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::{Duration, Instant};
 
-/// Converts received OrderEvent into REST API call
+/// Converts received OrderEvent into REST API call, replying the outcome and keeping accepted orders in open_orders.
+/// Only fails on errors no later order could recover from, e.g. invalid credentials
 pub async fn task_place_order(
-    mut receiver: broadcast::Receiver<OrderEvent>,
+    receiver: broadcast::Receiver<OrderEvent>,
+    tx_result: broadcast::Sender<OrderResultEvent>,
     kucoin: Kucoin,
+    retry: RetryPolicy,
+    open_orders: Arc<Mutex<OpenOrders>>,
+    mut shutdown: ShutdownToken,
 ) -> Result<()> {
+    // counted where consumed, as several tasks publish orders
+    let cx_received = registry::global().counter("messages", &[("channel", "order")]);
+    let cx_cancel_failed = registry::global().counter("cancel_failed", &[]);
+    let mut receiver = Subscriber::new(receiver, "order", "order");
     loop {
-        let event = receiver.recv().await?;
+        let event = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            event = receiver.recv() => event?,
+        };
+        cx_received.increment();
         // println!("Received event: {event:?}");
         match event {
             OrderEvent::GetAllOrders => {
-                let status = kucoin
-                    .get_recent_orders()
-                    .await
-                    .map_err(|e| eyre::eyre!(e))?;
+                let status = kucoin.get_recent_orders().await?;
                 tracing::info!("{status:?}");
             }
             OrderEvent::CancelOrder(order) => {
-                let status = kucoin
-                    .cancel_order(order.id().as_ref())
-                    .await
-                    .map_err(|e| eyre::eyre!(e))?;
-                tracing::info!("{status:?}");
+                match cancel_order(&kucoin, &order.id(), retry).await {
+                    Ok(_) => {
+                        open_orders.lock().await.remove(&order.id());
+                    }
+                    Err(e) => {
+                        cx_cancel_failed.increment();
+                        tracing::error!("Failed canceling [{}]: {e}", order.id());
+                    }
+                }
             }
-            OrderEvent::CancelAllOrders => {
-                todo!("implement batch order cancellation with kucoin.cancel_all_orders(symbol, trade_type)");
+            OrderEvent::CancelAllOrders(symbol) => {
+                let status =
+                    send_cancel(retry, || kucoin.cancel_all_orders(symbol.as_deref(), None))
+                        .await
+                        .and_then(|status| {
+                            Error::check_kucoin_code(&status.code, status.msg.as_deref())?;
+                            Ok(status)
+                        });
+                let status = match status {
+                    Ok(status) => status,
+                    Err(e) => {
+                        cx_cancel_failed.increment();
+                        tracing::error!("Failed canceling all orders of [{symbol:?}]: {e}");
+                        continue;
+                    }
+                };
+                let cancelled = status
+                    .data
+                    .map(|resp| resp.cancelled_order_ids)
+                    .unwrap_or_default();
+                tracing::info!("Canceled {} orders of [{symbol:?}]", cancelled.len());
+                let mut open_orders = open_orders.lock().await;
+                match symbol {
+                    Some(symbol) => open_orders.retain(|_, order_symbol| *order_symbol != symbol),
+                    None => open_orders.clear(),
+                }
             }
             OrderEvent::PlaceLimitOrder(order) => {
-                // get the broadcast duration
-                let status = kucoin
+                let result = place_order(&kucoin, Placement::Limit(&order), retry).await?;
+                if let OrderResultEvent::Accepted { client_oid, .. } = &result {
+                    open_orders
+                        .lock()
+                        .await
+                        .insert(client_oid.clone(), order.symbol());
+                }
+                // no subscriber is not an error, the requester might not wait for the reply
+                publish_result(&tx_result, result);
+            }
+            OrderEvent::PlaceBatch(orders) => {
+                // KuCoin's multi-order endpoint only takes orders of one symbol and is not
+                // exposed by kucoin_api, so the orders are sent concurrently instead
+                let sent = Instant::now();
+                let results = join_all(
+                    orders
+                        .iter()
+                        .map(|order| place_order(&kucoin, Placement::Limit(order), retry)),
+                )
+                .await;
+                let elapsed = sent.elapsed();
+                let mut sequential = Duration::ZERO;
+                // the orders accepted alongside a fatal error are still tracked and published
+                let mut fatal = None;
+                for (order, result) in orders.iter().zip(results) {
+                    let result = match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            let reason = e.to_string();
+                            fatal.get_or_insert(e);
+                            OrderResultEvent::Rejected {
+                                client_oid: order.id(),
+                                reason,
+                            }
+                        }
+                    };
+                    if let OrderResultEvent::Accepted {
+                        client_oid,
+                        latency,
+                        ..
+                    } = &result
+                    {
+                        sequential += *latency;
+                        open_orders
+                            .lock()
+                            .await
+                            .insert(client_oid.clone(), order.symbol());
+                    }
+                    publish_result(&tx_result, result);
+                }
+                tracing::info!(
+                    "Batch of {} orders placed in {elapsed:?}, saved {:?} against sequential placement",
+                    orders.len(),
+                    sequential.saturating_sub(elapsed)
+                );
+                if let Some(e) = fatal {
+                    return Err(e);
+                }
+            }
+            OrderEvent::PlaceMarketOrder(order) => {
+                // filled or canceled immediately, never kept open
+                let result = place_order(&kucoin, Placement::Market(&order), retry).await?;
+                publish_result(&tx_result, result);
+            }
+            OrderEvent::PlaceBorrowOrder(_) | OrderEvent::RepayLoans(_) => {
+                // handled by the margin task
+            }
+        };
+    }
+}
+
+/// Publishes the outcome of an order placement, counted by result
+fn publish_result(tx_result: &broadcast::Sender<OrderResultEvent>, result: OrderResultEvent) {
+    let label = match result {
+        OrderResultEvent::Accepted { .. } => "accepted",
+        OrderResultEvent::Rejected { .. } => "rejected",
+    };
+    registry::global()
+        .counter("order_result", &[("result", label)])
+        .increment();
+    let _ = tx_result.send(result);
+}
+
+enum Placement<'a> {
+    Limit(&'a LimitOrder),
+    Market(&'a MarketOrder),
+}
+
+impl Placement<'_> {
+    fn id(&self) -> String {
+        match self {
+            Placement::Limit(order) => order.id(),
+            Placement::Market(order) => order.id(),
+        }
+    }
+
+    /// sends the order with its options
+    async fn post(&self, kucoin: &Kucoin) -> Result<APIDatum<OrderResp>> {
+        let res = match self {
+            Placement::Limit(order) => {
+                order.validate()?;
+                let optionals = OrderOptionals {
+                    time_in_force: Some(order.time_in_force.as_ref()),
+                    cancel_after: order.cancel_after.map(|sec| sec as i64),
+                    post_only: order.post_only.then_some(true),
+                    hidden: order.hidden.then_some(true),
+                    iceberg: order.iceberg.is_some().then_some(true),
+                    visible_size: order.iceberg.as_deref(),
+                    trade_type: order.margin.then_some("MARGIN_TRADE"),
+                    ..OrderOptionals::new()
+                };
+                kucoin
                     .post_limit_order(
-                        order.id().as_ref(),
-                        order.symbol().as_ref(),
-                        order.side().as_ref(),
-                        order.price().as_ref(),
-                        order.amount().as_ref(),
-                        None,
+                        order.id.as_ref(),
+                        order.symbol.as_ref(),
+                        order.side.as_ref(),
+                        order.price.as_ref(),
+                        order.amount.as_ref(),
+                        Some(optionals),
                     )
                     .await
-                    .map_err(|e| eyre::eyre!(e))?;
-                match status.code.as_str() {
-                    "200000" => {
-                        let uuid = Uuid::parse_str(&order.id)?;
-                        tracing::info!("Limit order placement successful [{}]", uuid.as_u128());
-                    }
-                    "200004" => {
-                        tracing::error!(
-                            "Insufficient fund, check order placement status {order:?}"
-                        );
-                    }
-                    "400100" => {
-                        tracing::error!("Invalid order size increment {order:?}");
-                    }
-                    code => eyre::bail!("unrecognised code [{code:?}]"),
-                };
             }
-            OrderEvent::PlaceBorrowOrder(_order) => {
-                // TODO learn more about the function below
-                // kucoin.post_borrow_order(currency, trade_type, size, max_rate, term)
-                unimplemented!();
+            Placement::Market(order) => return post_market_order(kucoin, order).await,
+        };
+        Ok(res?)
+    }
+}
+
+/// Posts a market order with its size/funds as given, kucoin_api rounding them through f32
+async fn post_market_order(kucoin: &Kucoin, order: &MarketOrder) -> Result<APIDatum<OrderResp>> {
+    let endpoint = String::from("/api/v1/orders");
+    let mut params = HashMap::from([
+        ("clientOid".to_string(), order.id()),
+        ("symbol".to_string(), order.symbol()),
+        ("side".to_string(), order.side().to_string()),
+        ("type".to_string(), "market".to_string()),
+    ]);
+    if let Some(size) = order.size() {
+        params.insert("size".to_string(), size);
+    }
+    if let Some(funds) = order.funds() {
+        params.insert("funds".to_string(), funds);
+    }
+    let headers = kucoin
+        .sign_headers(endpoint.clone(), Some(&params), None, Method::POST)
+        .map_err(|e| Error::Config(format!("failed signing the order: {e}")))?;
+    let url = format!("{}{endpoint}", kucoin.prefix);
+    kucoin
+        .post(url, Some(headers), Some(params))
+        .await?
+        .json()
+        .await
+        .map_err(|e| Error::Network(e.to_string()))
+}
+
+/// Places an order, retrying transient rejections within the retry policy
+async fn place_order(
+    kucoin: &Kucoin,
+    placement: Placement<'_>,
+    retry: RetryPolicy,
+) -> Result<OrderResultEvent> {
+    let client_oid = placement.id();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let sent = Instant::now();
+        let res = placement.post(kucoin).await;
+        let latency = sent.elapsed();
+        // not retried, the order is invalid or might have reached the exchange
+        let status = match res {
+            Ok(status) => status,
+            Err(e) => {
+                let reason = e.to_string();
+                tracing::error!("Order [{client_oid}] failed: {reason}");
+                return Ok(OrderResultEvent::Rejected { client_oid, reason });
+            }
+        };
+        let code = status.code.as_str();
+        let reason = || {
+            Error::from_kucoin_code(code, status.msg.as_deref())
+                .map(|err| err.to_string())
+                .unwrap_or_default()
+        };
+        match classify(code) {
+            ResponseClass::Success => {
+                // the order is placed even if the id is missing, fills are matched by client_oid
+                let exchange_oid = status.data.map(|resp| resp.order_id).unwrap_or_default();
+                latency::record(Stage::RestAck, latency);
+                tracing::info!(
+                    "Order placement successful [{client_oid}] -> [{exchange_oid}] in {latency:?}"
+                );
+                return Ok(OrderResultEvent::Accepted {
+                    client_oid,
+                    exchange_oid,
+                    latency,
+                    attempts,
+                });
+            }
+            ResponseClass::Retryable if attempts <= retry.max_retries => {
+                let backoff = retry.backoff(attempts - 1);
+                tracing::warn!(
+                    "Order [{client_oid}] retrying in {backoff:?} ({attempts} tries): {}",
+                    reason()
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            ResponseClass::Retryable | ResponseClass::FatalForOrder => {
+                let reason = reason();
+                tracing::error!("Order rejected [{client_oid}]: {reason}");
+                return Ok(OrderResultEvent::Rejected { client_oid, reason });
             }
+            ResponseClass::FatalForSystem => {
+                return Err(Error::ExchangeRejected {
+                    code: code.to_string(),
+                    msg: status.msg.unwrap_or_default(),
+                });
+            }
+        }
+    }
+}
+
+/// Places a market order outside of the order task, e.g. before the tasks start
+pub async fn place_market_order(
+    kucoin: &Kucoin,
+    order: &MarketOrder,
+    retry: RetryPolicy,
+) -> Result<OrderResultEvent> {
+    place_order(kucoin, Placement::Market(order), retry).await
+}
+
+/// Sends a cancel request until answered with a non-transient code, retrying network errors
+/// and transient codes within the retry policy, as canceling twice is harmless
+async fn send_cancel<T, F, Fut>(retry: RetryPolicy, request: F) -> Result<APIDatum<T>>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = std::result::Result<APIDatum<T>, APIError>>,
+{
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match request().await {
+            Ok(status) => match Error::from_kucoin_code(&status.code, status.msg.as_deref()) {
+                Some(error) if classify(&status.code) == ResponseClass::Retryable => error,
+                _ => return Ok(status),
+            },
+            Err(e) => Error::from(e),
         };
+        if attempts > retry.max_retries {
+            return Err(error);
+        }
+        let backoff = retry.backoff(attempts - 1);
+        tracing::warn!("Cancel retrying in {backoff:?} ({attempts} tries): {error}");
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Cancels an order by its client_oid, true when canceled and false when it was no longer open
+pub async fn cancel_order(kucoin: &Kucoin, client_oid: &str, retry: RetryPolicy) -> Result<bool> {
+    let status = send_cancel(retry, || kucoin.cancel_order_by_client_oid(client_oid)).await?;
+    match classify(&status.code) {
+        ResponseClass::Success => {
+            tracing::info!("Canceled order [{client_oid}]");
+            Ok(true)
+        }
+        // e.g. already filled or canceled
+        ResponseClass::FatalForOrder => {
+            tracing::warn!(
+                "Order [{client_oid}] not canceled [{}]: {}",
+                status.code,
+                status.msg.unwrap_or_default()
+            );
+            Ok(false)
+        }
+        ResponseClass::Retryable | ResponseClass::FatalForSystem => {
+            Error::check_kucoin_code(&status.code, status.msg.as_deref()).map(|()| false)
+        }
+    }
+}
+
+/// Cancels every open order placed by this process, then waits within confirm_within for the
+/// private feed to report each one closed (see task_track_open_orders), which keeps running.
+/// Used on shutdown so that no order is left resting on the book.
+pub async fn cancel_open_orders(
+    kucoin: Kucoin,
+    open_orders: Arc<Mutex<OpenOrders>>,
+    retry: RetryPolicy,
+    confirm_within: Duration,
+) -> Result<()> {
+    let client_oids: Vec<String> = open_orders.lock().await.keys().cloned().collect();
+    tracing::info!("Canceling {} open orders", client_oids.len());
+    for client_oid in client_oids {
+        match cancel_order(&kucoin, &client_oid, retry).await {
+            // removed once the private feed confirms it
+            Ok(true) => {}
+            // no confirmation to wait for
+            Ok(false) => {
+                open_orders.lock().await.remove(&client_oid);
+            }
+            Err(e) => tracing::error!("Failed canceling [{client_oid}]: {e}"),
+        }
+    }
+    let deadline = Instant::now() + confirm_within;
+    loop {
+        let open = open_orders.lock().await.len();
+        if open == 0 {
+            tracing::info!("Every cancellation confirmed");
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(Error::Network(format!("{open} orders might still be open")));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
 }
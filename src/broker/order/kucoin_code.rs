@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+/// How the order task reacts to a KuCoin REST response code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResponseClass {
+    /// request accepted (200000)
+    Success,
+    /// transient failure, the same request can be sent again after a backoff
+    Retryable,
+    /// this order cannot be placed, other orders are unaffected
+    FatalForOrder,
+    /// credentials/account problem, no order can succeed until fixed
+    FatalForSystem,
+}
+
+/// Known KuCoin REST response codes
+const CODE_TABLE: &[(&str, ResponseClass)] = &[
+    ("200000", ResponseClass::Success),
+    // order creation/cancellation suspended for the symbol
+    ("200001", ResponseClass::FatalForOrder),
+    ("200002", ResponseClass::FatalForOrder),
+    // number of active orders above the limit
+    ("200003", ResponseClass::FatalForOrder),
+    // balance insufficient
+    ("200004", ResponseClass::FatalForOrder),
+    // KYC required
+    ("200009", ResponseClass::FatalForSystem),
+    // margin balance insufficient
+    ("230003", ResponseClass::FatalForOrder),
+    // request parameter illegal
+    ("300000", ResponseClass::FatalForOrder),
+    // API key header missing
+    ("400001", ResponseClass::FatalForSystem),
+    // KC-API-TIMESTAMP out of window, usually local clock jitter
+    ("400002", ResponseClass::Retryable),
+    // API key does not exist
+    ("400003", ResponseClass::FatalForSystem),
+    // wrong passphrase
+    ("400004", ResponseClass::FatalForSystem),
+    // signature error
+    ("400005", ResponseClass::FatalForSystem),
+    // IP not in the API key whitelist
+    ("400006", ResponseClass::FatalForSystem),
+    // access denied, API key lacks the permission
+    ("400007", ResponseClass::FatalForSystem),
+    // parameter error (e.g. size/price increment)
+    ("400100", ResponseClass::FatalForOrder),
+    // order placement forbidden for the account
+    ("400200", ResponseClass::FatalForSystem),
+    // holding limit reached
+    ("400350", ResponseClass::FatalForOrder),
+    // price beyond the allowed range
+    ("400370", ResponseClass::FatalForOrder),
+    ("400760", ResponseClass::FatalForOrder),
+    // region not supported
+    ("400500", ResponseClass::FatalForSystem),
+    // symbol not available
+    ("400600", ResponseClass::FatalForOrder),
+    // transaction restricted
+    ("400700", ResponseClass::FatalForSystem),
+    // account frozen
+    ("411100", ResponseClass::FatalForSystem),
+    // unsupported media type
+    ("415000", ResponseClass::FatalForSystem),
+    // too many requests
+    ("429000", ResponseClass::Retryable),
+    // internal server error
+    ("500000", ResponseClass::Retryable),
+    // symbol does not exist
+    ("900001", ResponseClass::FatalForOrder),
+];
+
+/// Classifies a KuCoin response code, unknown codes only fail the order
+pub fn classify(code: &str) -> ResponseClass {
+    CODE_TABLE
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, class)| *class)
+        .unwrap_or(ResponseClass::FatalForOrder)
+}
+
+/// Bounded retries with exponential backoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RetryPolicy {
+    /// retries after the first attempt
+    pub max_retries: u32,
+    /// wait before the first retry, doubled on each following retry
+    pub backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, backoff_ms: u64) -> Self {
+        RetryPolicy {
+            max_retries,
+            backoff_ms,
+        }
+    }
+
+    /// wait before the given retry (0 for the first retry)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << retry.min(16)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, ResponseClass, RetryPolicy};
+    use std::time::Duration;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("200000"), ResponseClass::Success);
+        assert_eq!(classify("429000"), ResponseClass::Retryable);
+        assert_eq!(classify("200004"), ResponseClass::FatalForOrder);
+        assert_eq!(classify("400005"), ResponseClass::FatalForSystem);
+        assert_eq!(classify("123456"), ResponseClass::FatalForOrder);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(3, 100);
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    }
+}
@@ -1,2 +1,6 @@
+/// Tracking orders placed by this process
+pub mod internal;
 // Order placement using KuCoin public REST API
 pub mod kucoin;
+/// KuCoin response code classification and retry policy
+pub mod kucoin_code;
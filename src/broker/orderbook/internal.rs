@@ -1,39 +1,222 @@
+use crate::error::Result;
 use crate::event::orderbook::OrderbookEvent;
-use crate::model::orderbook::FullOrderbook;
-use eyre::Result;
+use crate::model::orderbook::{
+    is_better_ask, is_better_bid, FullOrderbook, LocalOrderbook, OrderbookBackend,
+};
+use crate::monitor::channel::Subscriber;
+use crate::monitor::latency::{self, Stage};
+use crate::monitor::registry;
+use crate::shutdown::ShutdownToken;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::broadcast::{Receiver, Sender};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::Mutex;
 
 /// Subscribe OrderbookEvent, then publish OrderbookEvent after syncing local orderbook
+/// Changes that cannot be applied are skipped and counted, requesting a resync of the symbol through tx_resync.
+/// tick_sizes (symbol to price increment) are the price ticks of the orderbooks initialised by a snapshot
 pub async fn task_sync_orderbook(
-    mut receiver: Receiver<OrderbookEvent>,
+    receiver: Receiver<OrderbookEvent>,
     sender: Sender<OrderbookEvent>,
     local_full_orderbook: Arc<Mutex<FullOrderbook>>,
+    tick_sizes: HashMap<String, f64>,
+    tx_resync: Option<UnboundedSender<String>>,
+    mut shutdown: ShutdownToken,
 ) -> Result<()> {
+    let mut receiver = Subscriber::new(receiver, "orderbook", "sync");
+    let metrics = registry::global();
+    let cx_published = metrics.counter("messages", &[("channel", "best_price")]);
+    let cx_sync_error = metrics.counter("orderbook_sync_error", &[]);
+    // symbols requested for resync, not requested again until their snapshot arrives
+    let mut resyncing = HashSet::new();
     loop {
-        let event = receiver.recv().await?;
+        let event = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            event = receiver.recv() => event?,
+        };
+        if receiver.take_lagged() > 0 {
+            for symbol in tick_sizes.keys() {
+                request_resync(&tx_resync, &mut resyncing, symbol);
+            }
+        }
         let mut full_orderbook = local_full_orderbook.lock().await;
         match event {
             OrderbookEvent::OrderbookReceived((symbol, orderbook)) => {
-                (*full_orderbook).insert(symbol.clone(), orderbook);
-                tracing::info!("Initialised Orderbook for {symbol}")
+                // snapshot replaces the orderbook, published when there is increase in the best price
+                resyncing.remove(&symbol);
+                let (best_ask, best_bid) = (orderbook.best_ask(), orderbook.best_bid());
+                let tick_size = match (*full_orderbook).get(&symbol) {
+                    Some(previous) => previous.tick_size(),
+                    None => match tick_sizes.get(&symbol) {
+                        Some(&tick_size) => tick_size,
+                        None => {
+                            tracing::error!("Skipping snapshot of [{symbol}], price tick unknown");
+                            cx_sync_error.increment();
+                            continue;
+                        }
+                    },
+                };
+                let local = match LocalOrderbook::from_orderbook(&orderbook, tick_size) {
+                    Ok(local) => local,
+                    Err(e) => {
+                        tracing::error!("Skipping snapshot of [{symbol}]: {e}");
+                        cx_sync_error.increment();
+                        continue;
+                    }
+                };
+                let previous = (*full_orderbook).insert(symbol.clone(), local);
+                let improved = match previous {
+                    Some(previous) => {
+                        is_better_ask(best_ask, previous.best_ask())
+                            || is_better_bid(best_bid, previous.best_bid())
+                    }
+                    None => {
+                        tracing::info!("Initialised Orderbook for {symbol}");
+                        true
+                    }
+                };
+                if improved {
+                    latency::record_since(Stage::Merge, orderbook.receive_time);
+                    sender.send(OrderbookEvent::OrderbookReceived((symbol, orderbook)))?;
+                    cx_published.increment();
+                }
             }
             OrderbookEvent::OrderbookChangeReceived((symbol, orderbook_change)) => {
-                let orderbook = (*full_orderbook).get_mut(&symbol).ok_or(eyre::eyre!(
-                    "received {symbol} but orderbook not initialised yet."
-                ))?;
+                let Some(orderbook) = (*full_orderbook).get_mut(&symbol) else {
+                    tracing::warn!("Skipping change of [{symbol}], orderbook not initialised yet");
+                    cx_sync_error.increment();
+                    request_resync(&tx_resync, &mut resyncing, &symbol);
+                    continue;
+                };
+                // changes are missing between the orderbook and this one, snapshots carry no sequence
+                if orderbook.sequence() > 0
+                    && orderbook_change.sequence_start > orderbook.sequence() + 1
+                {
+                    tracing::warn!(
+                        "Skipping change of [{symbol}], sequence gap [{}] -> [{}]",
+                        orderbook.sequence(),
+                        orderbook_change.sequence_start
+                    );
+                    cx_sync_error.increment();
+                    request_resync(&tx_resync, &mut resyncing, &symbol);
+                    continue;
+                }
                 // tracing::info!("insertion: {orderbook_change:#?}");
-                match orderbook.merge(orderbook_change) {
-                    Ok(Some(ob)) => {
-                        sender.send(OrderbookEvent::OrderbookChangeReceived((symbol, ob)))?;
+                let applied = orderbook.apply(
+                    orderbook_change.sequence,
+                    &orderbook_change.asks,
+                    &orderbook_change.bids,
+                );
+                if applied.is_ok() {
+                    orderbook.exchange_time = orderbook_change.exchange_time;
+                    orderbook.update_time = orderbook_change.update_time;
+                    orderbook.receive_time = orderbook_change.receive_time;
+                }
+                match applied {
+                    Ok(true) => {
+                        latency::record_since(Stage::Merge, orderbook_change.receive_time);
+                        sender.send(OrderbookEvent::OrderbookChangeReceived((
+                            symbol,
+                            orderbook_change,
+                        )))?;
+                        cx_published.increment();
                     }
                     Err(e) => {
-                        tracing::error!("Merge conflict: {e}")
+                        tracing::error!("Merge conflict: {e}");
+                        cx_sync_error.increment();
                     }
-                    _ => {} // no update in best price
+                    Ok(false) => {} // no update in best price
                 }
             }
         }
     }
 }
+
+/// requests a resync of the symbol, once until its snapshot arrives
+fn request_resync(
+    tx_resync: &Option<UnboundedSender<String>>,
+    resyncing: &mut HashSet<String>,
+    symbol: &str,
+) {
+    if let Some(tx_resync) = tx_resync {
+        if resyncing.insert(symbol.to_string()) {
+            // no receiver only when the resync task ended
+            let _ = tx_resync.send(symbol.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::task_sync_orderbook;
+    use crate::event::orderbook::OrderbookEvent;
+    use crate::model::orderbook::{FullOrderbook, Orderbook, OrderbookBackend, OrderbookChange};
+    use crate::shutdown::{ShutdownCoordinator, ShutdownPhase};
+    use ordered_float::OrderedFloat;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast::channel;
+    use tokio::sync::{mpsc, Mutex};
+    use tokio::time::timeout;
+
+    fn change(sequence_start: u64, sequence: u64) -> OrderbookEvent {
+        OrderbookEvent::OrderbookChangeReceived((
+            "BTC-USDT".to_string(),
+            OrderbookChange {
+                asks: vec![(10.0, 1.0)],
+                sequence_start,
+                sequence,
+                ..Default::default()
+            },
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_desync_change_skipped_and_resynced() {
+        let (tx_orderbook, rx_orderbook) = channel(16);
+        let (tx_best, mut rx_best) = channel(16);
+        let (tx_resync, mut rx_resync) = mpsc::unbounded_channel();
+        let full_orderbook = Arc::new(Mutex::new(FullOrderbook::new()));
+        let shutdown = ShutdownCoordinator::new();
+        let sync = tokio::spawn(task_sync_orderbook(
+            rx_orderbook,
+            tx_best,
+            full_orderbook.clone(),
+            HashMap::from([("BTC-USDT".to_string(), 0.01)]),
+            Some(tx_resync),
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+        // not initialised, requested once until the snapshot arrives
+        tx_orderbook.send(change(1, 1)).unwrap();
+        tx_orderbook.send(change(2, 2)).unwrap();
+        let mut snapshot = Orderbook::new();
+        snapshot.sequence = 10;
+        snapshot.bid.insert(OrderedFloat(9.0), OrderedFloat(1.0));
+        tx_orderbook
+            .send(OrderbookEvent::OrderbookReceived((
+                "BTC-USDT".to_string(),
+                snapshot,
+            )))
+            .unwrap();
+        // following the snapshot, then after a gap
+        tx_orderbook.send(change(11, 11)).unwrap();
+        tx_orderbook.send(change(13, 13)).unwrap();
+
+        for _ in 0..2 {
+            timeout(Duration::from_secs(1), rx_best.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!sync.is_finished());
+        // once for the uninitialised orderbook, once more for the gap
+        assert_eq!(rx_resync.try_recv().as_deref(), Ok("BTC-USDT"));
+        assert_eq!(rx_resync.try_recv().as_deref(), Ok("BTC-USDT"));
+        assert!(rx_resync.try_recv().is_err());
+        let full_orderbook = full_orderbook.lock().await;
+        assert_eq!(full_orderbook["BTC-USDT"].sequence(), 11);
+    }
+}
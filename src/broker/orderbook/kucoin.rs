@@ -1,69 +1,102 @@
+use crate::error::{Error, Result};
 use crate::event::orderbook::OrderbookEvent;
-use crate::model::orderbook::FullOrderbook;
+use crate::model::orderbook::{FullOrderbook, LocalOrderbook};
 use crate::model::symbol::SymbolInfo;
+use crate::monitor::latency::{self, Stage};
+use crate::monitor::registry;
+use crate::shutdown::ShutdownToken;
+use crate::strings::topic_to_symbol;
 use crate::translator::traits::{ToOrderBook, ToOrderBookChange};
-use chrono::{TimeZone, Utc};
-use eyre::Result;
 use kucoin_api::client::Kucoin;
 use kucoin_api::futures::TryStreamExt;
 use kucoin_api::model::market::{OrderBook, OrderBookType};
 use kucoin_api::model::websocket::{KucoinWebsocketMsg, WSTopic, WSType};
+use std::collections::BTreeSet;
 use std::sync::Arc;
 use tokio::sync::broadcast::Sender;
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::Mutex;
 use tokio::task::JoinSet;
 use tokio::time::Duration;
 
 /// Subscribe Websocket API, then publish internal OrderbookEvent
+/// level2 changes are published as OrderbookChangeReceived, depth5/depth50 snapshots and tickers as OrderbookReceived
+/// messages failing conversion are skipped and counted
+/// each welcome message counts a websocket session, sessions above the subscribed ones being reconnects
 pub async fn task_pub_orderbook_event(
     api: Kucoin,
     topics: Vec<WSTopic>,
     sender: Sender<OrderbookEvent>,
+    mut shutdown: ShutdownToken,
 ) -> Result<()> {
+    let metrics = registry::global();
+    let cx_published = metrics.counter("messages", &[("channel", "orderbook")]);
+    let cx_invalid = metrics.counter("invalid_msg", &[("feed", "orderbook")]);
+    let cx_session = metrics.counter("ws_session", &[("feed", "orderbook")]);
+    let ws_connected = metrics.gauge("ws_connected", &[("feed", "orderbook")]);
+    // connected from the first welcome until the task ends
+    let mut session = None;
     let serial = 0;
-    let url_public = api
-        .get_socket_endpoint(WSType::Public)
-        .await
-        .map_err(|e| eyre::eyre!(e))?;
+    let url_public = api.get_socket_endpoint(WSType::Public).await?;
     let mut ws = api.websocket();
-    ws.subscribe(url_public.clone(), topics)
-        .await
-        .map_err(|e| eyre::eyre!(e))?;
+    ws.subscribe(url_public.clone(), topics).await?;
     loop {
-        let msg = ws.try_next().await.map_err(|e| eyre::eyre!(e))?;
-        let msg = msg.unwrap();
-        match msg {
-            KucoinWebsocketMsg::OrderBookMsg(msg) => {
-                let ts_message = msg.data.time;
-                tracing::info!("message(raw): {ts_message:?}");
-                let t_message = Utc.timestamp_millis_opt(ts_message as i64).unwrap();
-                let t_system = Utc::now();
-                let latency = t_system - t_message;
-                tracing::info!("message: {t_message:?}");
-                tracing::info!("system: {t_system:?}");
-                tracing::info!("latency: {latency:?}");
-
-                let (str, data) = msg.data.to_internal(serial);
-                let event = OrderbookEvent::OrderbookChangeReceived((str, data));
-                sender.send(event)?;
-            }
-            KucoinWebsocketMsg::TickerMsg(msg) => {
-                tracing::info!("TickerMsg: {msg:#?}");
-            }
+        let msg = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            msg = ws.try_next() => msg?,
+        };
+        let msg = msg.ok_or(Error::Network("public WS stream ended".to_string()))?;
+        let event = match msg {
+            KucoinWebsocketMsg::OrderBookMsg(msg) => msg
+                .data
+                .to_internal(serial)
+                .map(OrderbookEvent::OrderbookChangeReceived)
+                .map_err(Error::from),
+            KucoinWebsocketMsg::OrderBookDepthMsg(msg) => snapshot(&msg.topic, &msg.data),
+            KucoinWebsocketMsg::TickerMsg(msg) => snapshot(&msg.topic, &msg.data),
             KucoinWebsocketMsg::OrderBookChangeMsg(msg) => {
-                tracing::info!("OrderbookChange: {msg:#?}")
+                tracing::info!("OrderbookChange: {msg:#?}");
+                continue;
             }
             KucoinWebsocketMsg::WelcomeMsg(_) => {
-                tracing::info!("Welcome to KuCoin public WS")
+                tracing::info!("Welcome to KuCoin public WS");
+                cx_session.increment();
+                session.get_or_insert_with(|| ws_connected.track());
+                continue;
             }
-            KucoinWebsocketMsg::PongMsg(_) => {}
+            KucoinWebsocketMsg::PongMsg(_) => continue,
             other => {
-                tracing::error!("unregistered message {other:?}")
+                tracing::error!("unregistered message {other:?}");
+                continue;
             }
         };
+        match event {
+            Ok(event) => {
+                if event.exchange_time() > 0 {
+                    let latency = event
+                        .receive_time()
+                        .saturating_sub(event.exchange_time() * 1000);
+                    latency::record(Stage::Exchange, Duration::from_micros(latency));
+                }
+                sender.send(event)?;
+                cx_published.increment();
+            }
+            Err(e) => {
+                tracing::warn!("skipping invalid orderbook message ({e})");
+                cx_invalid.increment();
+            }
+        }
     }
 }
 
+/// Snapshot of the symbol of the topic, e.g. /spotMarket/level2Depth5:BTC-USDT
+fn snapshot(topic: &str, data: &impl ToOrderBook) -> Result<OrderbookEvent> {
+    let symbol = topic_to_symbol(topic.to_string())
+        .ok_or(Error::Parse(format!("symbol not found in topic [{topic}]")))?;
+    let orderbook = data.to_internal()?;
+    Ok(OrderbookEvent::OrderbookReceived((symbol, orderbook)))
+}
+
 /// Obtain current orderbook of a list of symbol from Kucoin REST API
 pub async fn task_get_orderbook(api: Kucoin, symbol: &str) -> Result<OrderBook> {
     let mut try_counter = 0;
@@ -76,15 +109,17 @@ pub async fn task_get_orderbook(api: Kucoin, symbol: &str) -> Result<OrderBook>
             tracing::warn!("orderbook[{symbol}] did not respond ({try_counter:?} tries) [{e:?}]");
             let null_err_msg = "invalid type: null, expected a string";
             if e.to_string().contains(null_err_msg) {
-                eyre::bail!("null received ffrom {symbol}");
+                return Err(Error::Parse(format!("null received from {symbol}")));
             }
             // TODO there are cases when no orderbook is obtained. Check if this is due to the network condition or the orderbook itself
             if try_counter > 100 {
-                eyre::bail!("[{try_counter:?}] has failed more than 100 times");
+                return Err(Error::Network(format!(
+                    "[{symbol}] has failed more than 100 times"
+                )));
             }
             continue;
         }
-        let response = res.map_err(|e| eyre::eyre!(e))?;
+        let response = res?;
         match response.code.as_str() {
             "200000" => {
                 if response.data.is_none() {
@@ -94,16 +129,19 @@ pub async fn task_get_orderbook(api: Kucoin, symbol: &str) -> Result<OrderBook>
                 tracing::info!("obtained [{symbol}]");
                 return Ok(response.data.unwrap());
             }
-            "400003" => eyre::bail!("API key needed not but provided"),
             "429000" => {
                 tracing::warn!("[{symbol:?}] request overloaded ({try_counter:?} tries)")
             }
-            code => eyre::bail!("unrecognised code [{code:?}]"),
+            // 400003: API key needed but not provided
+            code => {
+                Error::check_kucoin_code(code, response.msg.as_deref())?;
+            }
         }
     }
 }
 
 /// Obtain all the inital orderbook using Kucoin REST API
+/// fails on the first orderbook that cannot be fetched, invalid orderbooks are skipped
 pub async fn task_get_initial_orderbooks(
     api: Kucoin,
     symbol_infos: Vec<SymbolInfo>,
@@ -112,24 +150,83 @@ pub async fn task_get_initial_orderbooks(
     // replace spawn with or a taskpool
     let mut taskpool_aggregate = JoinSet::new();
     // collect all initial orderbook states with REST
-    let symbols: Vec<String> = symbol_infos.into_iter().map(|info| info.symbol).collect();
-    tracing::info!("Total symbols: {:?}", symbols.len());
-    for symbol in symbols {
+    tracing::info!("Total symbols: {:?}", symbol_infos.len());
+    for info in symbol_infos {
         let api = api.clone();
         let full_orderbook_arc = full_orderbook.clone();
+        let (symbol, tick_size) = (info.symbol, *info.price_increment);
         taskpool_aggregate.spawn(async move {
-            let data = task_get_orderbook(api, &symbol).await.unwrap();
-            let mut x = full_orderbook_arc.lock().await;
-            x.insert(symbol.to_string(), data.to_internal());
-            symbol
+            let data = task_get_orderbook(api, &symbol).await?;
+            let local = data
+                .to_internal()
+                .map_err(|e| e.to_string())
+                .and_then(|orderbook| LocalOrderbook::from_orderbook(&orderbook, tick_size));
+            match local {
+                Ok(local) => {
+                    let mut x = full_orderbook_arc.lock().await;
+                    x.insert(symbol.to_string(), local);
+                }
+                Err(e) => tracing::error!("skipping invalid orderbook [{symbol}] ({e})"),
+            }
+            Ok::<String, Error>(symbol)
         });
         // prevent server overloading
         tokio::time::sleep(Duration::from_millis(20)).await;
     }
-    let task_name = taskpool_aggregate
-        .join_next()
-        .await
-        .ok_or(eyre::eyre!("empty taskpool"))??;
-    tracing::info!("Initialized orderbook for [{:?}]", task_name);
+    let mut initialized = 0;
+    while let Some(joined) = taskpool_aggregate.join_next().await {
+        let symbol = joined.map_err(|e| Error::Network(e.to_string()))??;
+        tracing::debug!("Initialized orderbook for [{symbol}]");
+        initialized += 1;
+    }
+    tracing::info!("Initialized {initialized} orderbooks");
     Ok(())
 }
+
+/// Refetches the orderbooks of the requested symbols from REST, publishing them as snapshots
+/// replacing the local orderbooks.
+/// Requests made while refetching are coalesced into one more refetch.
+pub async fn task_resync_orderbooks(
+    api: Kucoin,
+    symbol_infos: Vec<SymbolInfo>,
+    sender: Sender<OrderbookEvent>,
+    mut rx_resync: UnboundedReceiver<String>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    loop {
+        let symbol = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            symbol = rx_resync.recv() => symbol.ok_or(Error::ChannelClosed)?,
+        };
+        let mut symbols = BTreeSet::from([symbol]);
+        while let Ok(symbol) = rx_resync.try_recv() {
+            symbols.insert(symbol);
+        }
+        tracing::warn!("Resyncing {} orderbooks", symbols.len());
+        let resync = async {
+            for info in symbol_infos
+                .iter()
+                .filter(|info| symbols.contains(&info.symbol))
+            {
+                let symbol = info.symbol.clone();
+                match task_get_orderbook(api.clone(), &symbol)
+                    .await?
+                    .to_internal()
+                {
+                    Ok(orderbook) => {
+                        sender.send(OrderbookEvent::OrderbookReceived((symbol, orderbook)))?;
+                    }
+                    Err(e) => tracing::error!("skipping invalid orderbook [{symbol}] ({e})"),
+                }
+                // prevent server overloading
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            Ok::<(), Error>(())
+        };
+        tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            res = resync => res?,
+        };
+        tracing::info!("Resynced orderbooks");
+    }
+}
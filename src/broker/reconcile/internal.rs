@@ -0,0 +1,88 @@
+use crate::event::cycle::CycleResult;
+use crate::journal::store::InterruptedLeg;
+use crate::model::chance::ActionInfo;
+use crate::model::cycle::CycleId;
+use crate::model::inventory::{add_fill, costs, spent};
+use crate::model::order::OrderSide;
+use chrono::{DateTime, Utc};
+use ordered_float::OrderedFloat;
+use std::collections::BTreeMap;
+
+/// Cycle the legs belong to with the amounts moved by their fills, None if the first leg is unreadable
+pub fn interrupted_cycle(legs: &[InterruptedLeg]) -> Option<CycleResult> {
+    let actions: Vec<(ActionInfo, f64)> = legs
+        .iter()
+        .filter_map(|leg| {
+            let side: OrderSide = leg.side.parse().ok()?;
+            let action = ActionInfo {
+                action: side,
+                ticker: leg.symbol.clone(),
+                price: OrderedFloat(leg.price),
+                volume: OrderedFloat(leg.filled),
+            };
+            Some((action, leg.filled))
+        })
+        .collect();
+    let (first, _) = actions.first()?;
+    let (currency, _) = spent(first)?;
+    let started = DateTime::parse_from_rfc3339(&legs[0].time)
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let mut flows = BTreeMap::new();
+    for (action, filled) in &actions {
+        add_fill(&mut flows, action, *filled);
+    }
+    let costs = costs(
+        &currency,
+        &actions
+            .iter()
+            .map(|(action, _)| action.clone())
+            .collect::<Vec<_>>(),
+    );
+    Some(CycleResult {
+        id: CycleId::from_oid(&legs[0].client_oid),
+        costs,
+        started,
+        currency,
+        symbols: legs.iter().map(|leg| leg.symbol.clone()).collect(),
+        flows,
+    })
+}
+
+/// Currencies other than the start currency held because of the cycle
+pub fn residuals(cycle: &CycleResult) -> BTreeMap<String, f64> {
+    cycle
+        .flows
+        .iter()
+        .filter(|(currency, amount)| **currency != cycle.currency && **amount > 0.0)
+        .map(|(currency, amount)| (currency.clone(), *amount))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interrupted_cycle, residuals};
+    use crate::journal::store::InterruptedLeg;
+
+    #[test]
+    fn test_interrupted_cycle() {
+        let leg = |symbol: &str, side: &str, price: f64, filled: f64| InterruptedLeg {
+            time: "2023-11-01T00:00:00.000Z".to_string(),
+            client_oid: "oid".to_string(),
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            price,
+            filled,
+        };
+        // bought ETH with BTC, stopped before selling it
+        let legs = [
+            leg("ETH-BTC", "buy", 0.05, 2.0),
+            leg("ETH-USDT", "sell", 1800.0, 0.0),
+        ];
+        let cycle = interrupted_cycle(&legs).unwrap();
+        assert_eq!(cycle.currency, "BTC");
+        // the ETH held is valued at its cost, nothing is realised yet
+        assert_eq!(cycle.pnl(), 0.0);
+        assert_eq!(residuals(&cycle).get("ETH"), Some(&2.0));
+    }
+}
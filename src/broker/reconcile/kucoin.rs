@@ -0,0 +1,187 @@
+use crate::broker::account::kucoin::get_balances;
+use crate::broker::order::kucoin::{cancel_order, place_market_order};
+use crate::broker::order::kucoin_code::RetryPolicy;
+use crate::broker::reconcile::internal::{interrupted_cycle, residuals};
+use crate::config::{OrphanOrders, ReconcileConfig};
+use crate::error::{Error, Result};
+use crate::event::cycle::CycleEvent;
+use crate::event::order::{OrderEvent, OrderResultEvent};
+use crate::journal::store::{InterruptedLeg, Journal};
+use crate::model::cycle::CycleId;
+use crate::model::order::{MarketOrder, OpenOrders, OrderSide};
+use crate::model::symbol::SymbolInfo;
+use crate::strategy::all_taker_btc_usd::adjust_amount;
+use crate::strings::symbol_to_string;
+use kucoin_api::client::Kucoin;
+use kucoin_api::model::trade::OrderInfo;
+use kucoin_api::trade::OrderInfoOptionals;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Outcome of the startup reconciliation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reconciliation {
+    /// client_oids of the orders of a previous run canceled
+    pub canceled: Vec<String>,
+    /// client_oids of the orders of a previous run now tracked as open
+    pub adopted: Vec<String>,
+    /// client_oids of the open orders not placed by this system, left untouched
+    pub foreign: Vec<String>,
+    /// currency and amount left by the interrupted cycles
+    pub residuals: BTreeMap<String, f64>,
+    /// symbols sold to unwind the residuals
+    pub unwound: Vec<String>,
+}
+
+/// accounts the legs are placed from, the margin one holding the legs of borrowing cycles
+const TRADE_TYPES: [&str; 2] = ["TRADE", "MARGIN_TRADE"];
+
+/// Active orders of the account of the trade type, every page
+pub async fn get_active_orders(api: &Kucoin, trade_type: &str) -> Result<Vec<OrderInfo>> {
+    let mut orders = Vec::new();
+    let mut page = 1;
+    loop {
+        let optionals = OrderInfoOptionals {
+            status: Some("active"),
+            trade_type: Some(trade_type),
+            current_page: Some(page),
+            page_size: Some(500),
+            ..OrderInfoOptionals::new()
+        };
+        let res = api.get_orders(Some(optionals)).await?;
+        Error::check_kucoin_code(&res.code, res.msg.as_deref())?;
+        let Some(pagination) = res.data else {
+            return Ok(orders);
+        };
+        orders.extend(pagination.items);
+        if pagination.current_page >= pagination.total_page {
+            return Ok(orders);
+        }
+        page += 1;
+    }
+}
+
+/// Reconciles the account with the journal of the previous run: open orders of this system are
+/// canceled or adopted, and cycles cut short are journaled as failed with their residuals
+pub async fn reconcile(
+    api: Kucoin,
+    journal: Option<&Journal>,
+    open_orders: Arc<Mutex<OpenOrders>>,
+    symbol_map: &BTreeMap<String, SymbolInfo>,
+    config: ReconcileConfig,
+    retry: RetryPolicy,
+) -> Result<Reconciliation> {
+    let mut reconciliation = Reconciliation::default();
+    let mut active = Vec::new();
+    for trade_type in TRADE_TYPES {
+        active.extend(get_active_orders(&api, trade_type).await?);
+    }
+    for order in active {
+        let ours = CycleId::from_oid(&order.client_oid).is_some()
+            || match journal {
+                Some(journal) => journal.is_journaled(&order.client_oid)?,
+                None => false,
+            };
+        if !ours {
+            tracing::warn!(
+                "Open order [{}] of [{}] not placed by this system, left open",
+                order.client_oid,
+                order.symbol
+            );
+            reconciliation.foreign.push(order.client_oid);
+            continue;
+        }
+        match config.orphan_orders {
+            OrphanOrders::Cancel => {
+                cancel_order(&api, &order.client_oid, retry).await?;
+                reconciliation.canceled.push(order.client_oid);
+            }
+            OrphanOrders::Adopt => {
+                tracing::info!("Adopting open order [{}]", order.client_oid);
+                open_orders
+                    .lock()
+                    .await
+                    .insert(order.client_oid.clone(), order.symbol);
+                reconciliation.adopted.push(order.client_oid);
+            }
+        }
+    }
+
+    let Some(journal) = journal else {
+        return Ok(reconciliation);
+    };
+    // legs journaled without cycle id make up one cycle
+    let mut cycles: BTreeMap<Option<CycleId>, Vec<InterruptedLeg>> = BTreeMap::new();
+    for leg in journal.interrupted_legs()? {
+        cycles
+            .entry(CycleId::from_oid(&leg.client_oid))
+            .or_default()
+            .push(leg);
+    }
+    let mut balances = None;
+    for legs in cycles.values() {
+        let Some(cycle) = interrupted_cycle(legs) else {
+            continue;
+        };
+        tracing::warn!(
+            "Cycle [{}] of {:?} interrupted, flows {:?}",
+            cycle.id.map(|id| id.to_string()).unwrap_or_default(),
+            cycle.symbols,
+            cycle.flows
+        );
+        let cycle_residuals = residuals(&cycle);
+        for (currency, amount) in &cycle_residuals {
+            *reconciliation
+                .residuals
+                .entry(currency.clone())
+                .or_default() += amount;
+        }
+        if config.unwind_residuals && !cycle_residuals.is_empty() {
+            if balances.is_none() {
+                balances = Some(get_balances(api.clone()).await?);
+            }
+            let balances = balances.as_mut().expect("balances fetched above");
+            for (currency, amount) in &cycle_residuals {
+                // back into the start currency, or the usual quotes when not traded directly
+                let Some(info) = [cycle.currency.as_str(), "USDT", "BTC"]
+                    .iter()
+                    .find_map(|quote| symbol_map.get(&symbol_to_string(currency, quote)))
+                else {
+                    tracing::warn!("Cannot unwind [{currency}], no symbol to sell it");
+                    continue;
+                };
+                let available = balances.get(currency).copied().unwrap_or_default();
+                let size = adjust_amount(*amount, *info.base_min, *info.base_increment, available);
+                if size <= 0.0 {
+                    tracing::warn!("Cannot unwind {amount} [{currency}], below the minimum size");
+                    continue;
+                }
+                let order = MarketOrder::with_size(
+                    &Uuid::new_v4().to_string(),
+                    OrderSide::Sell,
+                    &info.symbol,
+                    &format!("{size:.9}"),
+                );
+                journal.record_order(&OrderEvent::PlaceMarketOrder(order.clone()))?;
+                let result = place_market_order(&api, &order, retry).await?;
+                journal.record_order_result(&result)?;
+                if let OrderResultEvent::Accepted { .. } = result {
+                    tracing::info!("Unwound {size} [{currency}] on [{}]", info.symbol);
+                    // not available to the residuals of the next cycles
+                    balances.insert(currency.clone(), available - size);
+                    reconciliation.unwound.push(info.symbol.clone());
+                }
+            }
+        }
+        // journaled as ended, so the legs are not reconciled again
+        let symbol = cycle.symbols.last().cloned().unwrap_or_default();
+        journal.record_cycle(&CycleEvent::Failed {
+            result: cycle,
+            symbol,
+            reason: "interrupted".to_string(),
+        })?;
+    }
+    Ok(reconciliation)
+}
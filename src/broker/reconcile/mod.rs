@@ -0,0 +1,4 @@
+/// Interrupted cycle rebuilt from the journal
+pub mod internal;
+/// Reconciliation against the KuCoin account
+pub mod kucoin;
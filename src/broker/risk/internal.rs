@@ -0,0 +1,328 @@
+use crate::config::RiskConfig;
+use crate::error::Result;
+use crate::event::chance::ChanceEvent;
+use crate::event::cycle::CycleEvent;
+use crate::event::order::OrderEvent;
+use crate::model::chance::TriangularArbitrageChance;
+use crate::model::inventory::spent;
+use crate::model::order::{MarketOrder, Order};
+use crate::monitor::channel::Subscriber;
+use crate::shutdown::ShutdownToken;
+use crate::strings::split_symbol;
+use chrono::{NaiveDate, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tokio::sync::broadcast::{Receiver, Sender};
+use tokio::sync::watch;
+
+/// Keeps the risk limits over the cycles, halting for good once a loss limit is breached
+#[derive(Debug, Clone)]
+pub struct RiskManager {
+    config: RiskConfig,
+    day: NaiveDate,
+    daily_loss: f64,
+    positions: BTreeMap<String, f64>,
+    consecutive_failures: u32,
+    symbol_failures: HashMap<String, u32>,
+    blacklist: BTreeSet<String>,
+    halted: Option<String>,
+}
+
+impl RiskManager {
+    pub fn new(config: RiskConfig) -> Self {
+        RiskManager {
+            config,
+            day: Utc::now().date_naive(),
+            daily_loss: 0.0,
+            positions: BTreeMap::new(),
+            consecutive_failures: 0,
+            symbol_failures: HashMap::new(),
+            blacklist: BTreeSet::new(),
+            halted: None,
+        }
+    }
+
+    /// reason of the halt, None while running
+    pub fn halted(&self) -> Option<&str> {
+        self.halted.as_deref()
+    }
+
+    pub fn blacklist(&self) -> &BTreeSet<String> {
+        &self.blacklist
+    }
+
+    pub fn positions(&self) -> &BTreeMap<String, f64> {
+        &self.positions
+    }
+
+    /// realised loss of the current day
+    pub fn daily_loss(&self) -> f64 {
+        self.daily_loss
+    }
+
+    /// reason to ignore the chance, None when it is within the limits
+    pub fn check(&self, chance: &TriangularArbitrageChance) -> Option<String> {
+        if let Some(reason) = &self.halted {
+            return Some(format!("halted: {reason}"));
+        }
+        for action in &chance.actions {
+            if self.blacklist.contains(&action.ticker) {
+                return Some(format!("[{}] blacklisted", action.ticker));
+            }
+        }
+        if let Some((currency, notional)) = spent(&chance.actions[0]) {
+            if notional > self.config.max_cycle_notional {
+                return Some(format!(
+                    "cycle notional {notional} [{currency}] above {}",
+                    self.config.max_cycle_notional
+                ));
+            }
+        }
+        None
+    }
+
+    /// reason to drop a rebalancing order, None when it is within the limits.
+    /// Its size is held to the max position of the currency, it is not booked as a position as
+    /// it restores the inventory.
+    pub fn check_rebalance(&self, order: &MarketOrder) -> Option<String> {
+        if let Some(reason) = &self.halted {
+            return Some(format!("halted: {reason}"));
+        }
+        let Some((currency, _)) = split_symbol(order.symbol()) else {
+            return Some(format!("unknown symbol [{}]", order.symbol()));
+        };
+        let Some(size) = order.size().and_then(|size| size.parse::<f64>().ok()) else {
+            return Some(format!(
+                "rebalancing [{currency}] of unreadable size {}",
+                order.amount()
+            ));
+        };
+        let max = self.config.max_position.get(&currency)?;
+        (size > *max).then(|| format!("rebalancing {size} [{currency}] above max position {max}"))
+    }
+
+    /// records the outcome of a cycle, returning the reason when this outcome halts the system
+    pub fn record(&mut self, event: &CycleEvent, today: NaiveDate) -> Option<String> {
+        if self.halted.is_some() {
+            return None;
+        }
+        if today != self.day {
+            self.day = today;
+            self.daily_loss = 0.0;
+        }
+        let result = event.result();
+        for (currency, flow) in &result.flows {
+            *self.positions.entry(currency.clone()).or_default() += flow;
+        }
+        let pnl = result.pnl();
+        if pnl < 0.0 {
+            self.daily_loss -= pnl;
+        }
+        match event {
+            CycleEvent::Completed(_) => self.consecutive_failures = 0,
+            CycleEvent::Failed { symbol, .. } => {
+                self.consecutive_failures += 1;
+                let failures = self.symbol_failures.entry(symbol.clone()).or_default();
+                *failures += 1;
+                if *failures >= self.config.blacklist_after_failures
+                    && self.blacklist.insert(symbol.clone())
+                {
+                    tracing::warn!("Blacklisted [{symbol}] after {failures} failed cycles");
+                }
+            }
+        }
+        self.halted = self.breach(&result.currency);
+        self.halted.clone()
+    }
+
+    fn breach(&self, currency: &str) -> Option<String> {
+        if self.daily_loss > self.config.daily_loss_limit {
+            return Some(format!(
+                "daily loss {} [{currency}] above {}",
+                self.daily_loss, self.config.daily_loss_limit
+            ));
+        }
+        if self.consecutive_failures >= self.config.max_consecutive_failures {
+            return Some(format!(
+                "{} consecutive failed cycles",
+                self.consecutive_failures
+            ));
+        }
+        self.positions.iter().find_map(|(currency, position)| {
+            let max = self.config.max_position.get(currency)?;
+            (position.abs() > *max).then(|| format!("[{currency}] position {position} above {max}"))
+        })
+    }
+}
+
+/// Whether orders may be placed, checked by every task placing them
+#[derive(Debug, Clone)]
+pub struct TradingState {
+    /// set for good once a risk limit is breached
+    pub halted: watch::Receiver<bool>,
+    /// set by the operator through the status API
+    pub paused: watch::Receiver<bool>,
+}
+
+impl TradingState {
+    /// why no order may be placed, None while trading
+    pub fn blocked(&self) -> Option<&'static str> {
+        if *self.halted.borrow() {
+            Some("halted")
+        } else if *self.paused.borrow() {
+            Some("paused")
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for TradingState {
+    /// never halted nor paused
+    fn default() -> Self {
+        TradingState {
+            halted: watch::channel(false).1,
+            paused: watch::channel(false).1,
+        }
+    }
+}
+
+/// Task to forward the chances and rebalancing orders within the risk limits,
+/// canceling every order and publishing the halt to tx_halted once a limit is breached
+#[allow(clippy::too_many_arguments)]
+pub async fn task_manage_risk(
+    rx_chance: Receiver<ChanceEvent>,
+    tx_chance: Sender<ChanceEvent>,
+    rx_rebalance: Receiver<OrderEvent>,
+    rx_cycle: Receiver<CycleEvent>,
+    tx_order: Sender<OrderEvent>,
+    config: RiskConfig,
+    tx_halted: watch::Sender<bool>,
+    rx_paused: watch::Receiver<bool>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    let mut rx_chance = Subscriber::new(rx_chance, "chance", "risk");
+    let mut rx_rebalance = Subscriber::new(rx_rebalance, "rebalance", "risk");
+    let mut rx_cycle = Subscriber::new(rx_cycle, "cycle", "risk");
+    let mut risk = RiskManager::new(config);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            event = rx_chance.recv() => {
+                let event = event?;
+                if *rx_paused.borrow() {
+                    tracing::debug!("Ignoring chance: trading paused");
+                    continue;
+                }
+                let (ChanceEvent::AllTaker(chance) | ChanceEvent::MakerTakerTaker(chance)) = &event;
+                match risk.check(chance) {
+                    Some(reason) => tracing::debug!("Ignoring chance: {reason}"),
+                    None => {
+                        tx_chance.send(event)?;
+                    }
+                }
+            }
+            event = rx_rebalance.recv() => {
+                let event = event?;
+                if *rx_paused.borrow() {
+                    tracing::info!("Dropping rebalancing order: trading paused");
+                    continue;
+                }
+                if let OrderEvent::PlaceMarketOrder(order) = &event {
+                    if let Some(reason) = risk.check_rebalance(order) {
+                        tracing::warn!("Dropping rebalancing order: {reason}");
+                        continue;
+                    }
+                }
+                tx_order.send(event)?;
+            }
+            event = rx_cycle.recv() => {
+                let event = event?;
+                tracing::info!("Cycle pnl: {}", event.result().pnl());
+                if let Some(reason) = risk.record(&event, Utc::now().date_naive()) {
+                    tracing::error!("Risk limit breached, halting: {reason}");
+                    tx_halted.send_replace(true);
+                    tx_order.send(OrderEvent::CancelAllOrders(None))?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RiskManager, TradingState};
+    use crate::config::RiskConfig;
+    use crate::event::cycle::{CycleEvent, CycleResult};
+    use crate::model::order::{MarketOrder, OrderSide};
+    use chrono::NaiveDate;
+    use std::collections::BTreeMap;
+    use tokio::sync::watch;
+
+    #[test]
+    fn test_rebalance_of_unreadable_size_rejected() {
+        let risk = RiskManager::new(RiskConfig::default());
+        let order = MarketOrder::with_size("id", OrderSide::Buy, "BTC-USDT", "0.001");
+        assert_eq!(risk.check_rebalance(&order), None);
+        let order = MarketOrder::with_size("id", OrderSide::Buy, "BTC-USDT", "lots");
+        assert!(risk.check_rebalance(&order).is_some());
+        let order = MarketOrder::with_funds("id", OrderSide::Buy, "BTC-USDT", "20");
+        assert!(risk.check_rebalance(&order).is_some());
+    }
+
+    #[test]
+    fn test_failed_cycle_loses_residual_only() {
+        let mut risk = RiskManager::new(RiskConfig {
+            daily_loss_limit: 1.0,
+            ..Default::default()
+        });
+        // 20 USDT spent on BTC held at its cost, 0.02 USDT of fee realised
+        let result = CycleResult {
+            currency: "USDT".to_string(),
+            flows: BTreeMap::from([("BTC".to_string(), 0.001), ("USDT".to_string(), -20.02)]),
+            costs: BTreeMap::from([("BTC".to_string(), 20000.0), ("USDT".to_string(), 1.0)]),
+            ..Default::default()
+        };
+        let failed = CycleEvent::Failed {
+            result,
+            symbol: "ETH-BTC".to_string(),
+            reason: "canceled".to_string(),
+        };
+        let today = NaiveDate::from_ymd_opt(2023, 11, 1).unwrap();
+        assert_eq!(risk.record(&failed, today), None);
+        assert!((risk.daily_loss() - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_daily_loss_limit() {
+        let mut risk = RiskManager::new(RiskConfig {
+            daily_loss_limit: 5.0,
+            ..Default::default()
+        });
+        let today = NaiveDate::from_ymd_opt(2023, 11, 1).unwrap();
+        let loss = CycleResult {
+            currency: "USDT".to_string(),
+            flows: BTreeMap::from([("USDT".to_string(), -3.0)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            risk.record(&CycleEvent::Completed(loss.clone()), today),
+            None
+        );
+        assert!(risk
+            .record(&CycleEvent::Completed(loss.clone()), today)
+            .is_some());
+        assert!(risk.halted().is_some());
+    }
+
+    #[test]
+    fn test_blocked() {
+        let (halted, rx_halted) = watch::channel(false);
+        let trading = TradingState {
+            halted: rx_halted,
+            ..Default::default()
+        };
+        assert_eq!(trading.blocked(), None);
+        halted.send_replace(true);
+        assert_eq!(trading.blocked(), Some("halted"));
+    }
+}
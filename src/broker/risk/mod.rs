@@ -0,0 +1,2 @@
+/// Risk limits between chances and orders
+pub mod internal;
@@ -39,10 +39,8 @@ pub fn symbol_with_quotes(symbols: &Vec<SymbolInfo>, btc: &str, usd: &str) -> Ve
                 result.push(btc_symbol);
                 result.push(usd_symbol);
             }
-            (Some(usd_symbol), None) => {
-                if usd_symbol.base == btc {
-                    result.insert(0, usd_symbol);
-                }
+            (Some(usd_symbol), None) if usd_symbol.base == btc => {
+                result.insert(0, usd_symbol);
             }
             _ => {}
         }
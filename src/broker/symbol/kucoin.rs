@@ -1,6 +1,7 @@
+use crate::config::OrderbookFeed;
+use crate::error::{Error, Result};
 use crate::model::symbol::SymbolInfo;
 use crate::translator::traits::ToSymbolInfo;
-use eyre::Result;
 use kucoin_api::client::Kucoin;
 use kucoin_api::model::market::SymbolList;
 use kucoin_api::model::websocket::WSTopic;
@@ -13,13 +14,13 @@ pub async fn get_symbols(api: Kucoin) -> Result<Vec<SymbolInfo>> {
     let v_symbol_list: Vec<SymbolList> = {
         loop {
             let res = api.get_symbol_list(None).await;
-            if let Ok(response) = res {
-                break response.data.unwrap();
+            if let Ok(Some(data)) = res.map(|response| response.data) {
+                break data;
             }
             tracing::warn!("failed getting symbol list, trying again");
             tries += 1;
             if tries >= tries_limit {
-                eyre::bail!("get symbol failed {tries} times");
+                return Err(Error::Network(format!("get symbol failed {tries} times")));
             }
         }
     };
@@ -44,7 +45,13 @@ pub async fn get_symbols(api: Kucoin) -> Result<Vec<SymbolInfo>> {
             );
             continue;
         }
-        let internal_symbol_info = symbol.to_internal();
+        let internal_symbol_info = match symbol.to_internal() {
+            Ok(info) => info,
+            Err(e) => {
+                tracing::warn!("skipping symbol {:10} ({e})", symbol.symbol);
+                continue;
+            }
+        };
         result.push(internal_symbol_info);
     }
     Ok(result)
@@ -52,6 +59,14 @@ pub async fn get_symbols(api: Kucoin) -> Result<Vec<SymbolInfo>> {
 
 // TODO this bridges between API and the internal model, it should be placed in broker
 pub fn format_subscription_list(infos: &[SymbolInfo]) -> Vec<Vec<WSTopic>> {
+    format_subscription_list_feed(infos, OrderbookFeed::Level2)
+}
+
+/// Same as format_subscription_list, with topics of the selected orderbook feed
+pub fn format_subscription_list_feed(
+    infos: &[SymbolInfo],
+    feed: OrderbookFeed,
+) -> Vec<Vec<WSTopic>> {
     // Extracts the symbol name from SynbolInfo
     let symbols: Vec<String> = infos.iter().map(|info| info.symbol.clone()).collect();
 
@@ -84,7 +99,12 @@ pub fn format_subscription_list(infos: &[SymbolInfo]) -> Vec<Vec<WSTopic>> {
     let mut subs: Vec<Vec<WSTopic>> = Vec::new();
     let mut sub: Vec<WSTopic> = Vec::new();
     for sub_array in hundred_arrays {
-        sub.push(WSTopic::OrderBook(sub_array));
+        sub.push(match feed {
+            OrderbookFeed::Level2 => WSTopic::OrderBook(sub_array),
+            OrderbookFeed::Depth5 => WSTopic::OrderBookDepth5(sub_array),
+            OrderbookFeed::Depth50 => WSTopic::OrderBookDepth50(sub_array),
+            OrderbookFeed::Ticker => WSTopic::Ticker(sub_array),
+        });
         if sub.len() == 3 {
             subs.push(sub);
             sub = Vec::new();
@@ -1,6 +1,9 @@
+use crate::error::{Error, Result};
 use crate::event::trade::TradeEvent;
+use crate::monitor::registry;
+use crate::shutdown::ShutdownToken;
+use crate::translator::error::ConversionError;
 use crate::translator::traits::ToTradeInfo;
-use eyre::Result;
 use kucoin_api::client::Kucoin;
 use kucoin_api::futures::TryStreamExt;
 use kucoin_api::model::websocket::{KucoinWebsocketMsg, WSTopic, WSType};
@@ -8,11 +11,28 @@ use tokio::sync::broadcast::Sender;
 
 /// Task to publish order change events.
 /// Subscribe Kucoim Websocket API, then publish tradeEvent directly after conversion.
-pub async fn task_pub_trade_event(api: Kucoin, sender: Sender<TradeEvent>) -> Result<()> {
+/// Messages failing conversion are skipped and counted.
+pub async fn task_pub_trade_event(
+    api: Kucoin,
+    sender: Sender<TradeEvent>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    let metrics = registry::global();
+    let cx_published = metrics.counter("messages", &[("channel", "trade")]);
+    let cx_fill = metrics.counter("fill", &[]);
+    let cx_invalid = metrics.counter("invalid_msg", &[("feed", "trade")]);
+    let cx_session = metrics.counter("ws_session", &[("feed", "trade")]);
+    let ws_connected = metrics.gauge("ws_connected", &[("feed", "trade")]);
+    // connected from the first welcome until the task ends
+    let mut session = None;
     let url_private = api
         .get_socket_endpoint(WSType::Private)
         .await
-        .map_err(|e| eyre::eyre!("failed connecting private endpoint, check API key ({e})"))?;
+        .map_err(|e| {
+            Error::Network(format!(
+                "failed connecting private endpoint, check API key ({e})"
+            ))
+        })?;
     let mut ws = api.websocket();
     // TODO test TradeOrdersV2
     let topics = vec![WSTopic::TradeOrders];
@@ -21,40 +41,113 @@ pub async fn task_pub_trade_event(api: Kucoin, sender: Sender<TradeEvent>) -> Re
         .expect("failed subscribing trade event");
     loop {
         // Awaits subscription message
-        let ws_msg = ws.try_next().await.map_err(|e| eyre::eyre!(e))?;
-        let ws_msg = ws_msg.unwrap();
-        match ws_msg {
-            KucoinWebsocketMsg::TradeReceivedMsg(msg) => {
-                let tradeinfo = msg.data.to_internal()?;
-                tracing::info!(
-                    "TradeReceived[{}] (not so sure when it gets received)",
-                    tradeinfo.order_id
-                );
-                // sender.send(TradeEvent::TradeReceived(tradeinfo))?;
-            }
-            KucoinWebsocketMsg::TradeOpenMsg(msg) => {
-                let tradeinfo = msg.data.to_internal()?;
-                tracing::info!("TradeOpen[{}]", tradeinfo.order_id);
-                sender.send(TradeEvent::TradeOpen(tradeinfo))?;
-            }
-            KucoinWebsocketMsg::TradeMatchMsg(msg) => {
-                let tradeinfo = msg.data.to_internal()?;
-                tracing::info!("TradeMatch[{}]", tradeinfo.order_id);
-                sender.send(TradeEvent::TradeMatch(tradeinfo))?;
-            }
-            KucoinWebsocketMsg::TradeFilledMsg(msg) => {
-                let tradeinfo = msg.data.to_internal()?;
-                tracing::info!("TradeFilledMsg[{}]", tradeinfo.order_id);
-                sender.send(TradeEvent::TradeFilled(tradeinfo))?;
-            }
+        let ws_msg = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            ws_msg = ws.try_next() => ws_msg?,
+        };
+        let ws_msg = ws_msg.ok_or(Error::Network("private WS stream ended".to_string()))?;
+        let event = match ws_msg {
             KucoinWebsocketMsg::WelcomeMsg(_) => {
                 tracing::info!("Welcome to KuCoin private WS");
+                cx_session.increment();
+                session.get_or_insert_with(|| ws_connected.track());
+                continue;
             }
-            KucoinWebsocketMsg::PingMsg(_) => {}
-            KucoinWebsocketMsg::PongMsg(_) => {}
-            msg => {
-                tracing::info!("Unregistered message in private channel [{msg:#?}]");
+            msg => to_trade_event(msg),
+        };
+        match event {
+            Ok(Some(event)) => {
+                if let TradeEvent::TradeFilled(_) = event {
+                    cx_fill.increment();
+                }
+                sender.send(event)?;
+                cx_published.increment();
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("skipping invalid trade message ({e})");
+                cx_invalid.increment();
             }
         }
     }
 }
+
+/// Converts the order change messages of the private channel, None for the other messages
+fn to_trade_event(
+    msg: KucoinWebsocketMsg,
+) -> std::result::Result<Option<TradeEvent>, ConversionError> {
+    match msg {
+        KucoinWebsocketMsg::TradeReceivedMsg(msg) => msg.data.to_internal().map(|tradeinfo| {
+            tracing::info!(
+                "TradeReceived[{}] (not so sure when it gets received)",
+                tradeinfo.client_oid
+            );
+            // Some(TradeEvent::TradeReceived(tradeinfo))
+            None
+        }),
+        KucoinWebsocketMsg::TradeOpenMsg(msg) => msg.data.to_internal().map(|tradeinfo| {
+            tracing::info!("TradeOpen[{}]", tradeinfo.client_oid);
+            Some(TradeEvent::TradeOpen(tradeinfo))
+        }),
+        KucoinWebsocketMsg::TradeMatchMsg(msg) => msg.data.to_internal().map(|tradeinfo| {
+            tracing::info!("TradeMatch[{}]", tradeinfo.client_oid);
+            Some(TradeEvent::TradeMatch(tradeinfo))
+        }),
+        KucoinWebsocketMsg::TradeFilledMsg(msg) => msg.data.to_internal().map(|tradeinfo| {
+            tracing::info!("TradeFilledMsg[{}]", tradeinfo.client_oid);
+            Some(TradeEvent::TradeFilled(tradeinfo))
+        }),
+        KucoinWebsocketMsg::TradeCanceledMsg(msg) => msg.data.to_internal().map(|tradeinfo| {
+            tracing::info!("TradeCanceled[{}]", tradeinfo.client_oid);
+            Some(TradeEvent::TradeCanceled(tradeinfo))
+        }),
+        KucoinWebsocketMsg::PingMsg(_) => Ok(None),
+        KucoinWebsocketMsg::PongMsg(_) => Ok(None),
+        msg => {
+            tracing::info!("Unregistered message in private channel [{msg:#?}]");
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_trade_event;
+    use crate::event::trade::TradeEvent;
+    use kucoin_api::model::websocket::KucoinWebsocketMsg;
+    use tokio::sync::broadcast::channel;
+
+    #[test]
+    fn test_canceled_published() {
+        let msg = serde_json::from_str(
+            r#"{
+                "type": "message",
+                "topic": "/spotMarket/tradeOrders",
+                "subject": "orderChange",
+                "data": {
+                    "symbol": "ETH-BTC",
+                    "orderType": "limit",
+                    "side": "buy",
+                    "type": "canceled",
+                    "orderId": "exchange",
+                    "orderTime": 1,
+                    "size": "0.1",
+                    "filledSize": "0.04",
+                    "price": "0.05",
+                    "clientOid": "leg",
+                    "remainSize": "0",
+                    "status": "done",
+                    "ts": 2
+                }
+            }"#,
+        )
+        .unwrap();
+        let (tx_trade, mut rx_trade) = channel(1);
+        let event = to_trade_event(KucoinWebsocketMsg::TradeCanceledMsg(msg)).unwrap();
+        tx_trade.send(event.unwrap()).unwrap();
+        let Ok(TradeEvent::TradeCanceled(info)) = rx_trade.try_recv() else {
+            panic!("cancel not published");
+        };
+        assert_eq!(info.client_oid, "leg");
+    }
+}
@@ -1,26 +1,255 @@
 use crate::error::Error;
+use clap::{Parser, ValueEnum};
 use core::str::FromStr;
 use kucoin_api::client::Credentials;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr};
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Serialize)]
+/// environment variables overriding the credentials, so they can be kept out of the config file
+pub const ENV_API_KEY: &str = "KUCOIN_API_KEY";
+pub const ENV_SECRET_KEY: &str = "KUCOIN_SECRET_KEY";
+pub const ENV_PASSPHRASE: &str = "KUCOIN_PASSPHRASE";
+
+/// Command line arguments shared by the binaries
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+#[command(version, about)]
+pub struct Args {
+    /// config file
+    #[arg(short, long, default_value = "config.toml")]
+    pub config: String,
+    /// overrides behaviour.usd_cyclic_arbitrage
+    #[arg(long)]
+    pub usd_cyclic_arbitrage: Option<u32>,
+    /// overrides behaviour.execution_mode
+    #[arg(long, value_enum)]
+    pub execution_mode: Option<ExecutionMode>,
+    /// overrides behaviour.status_port
+    #[arg(long)]
+    pub status_port: Option<u16>,
+    /// overrides log.term_log_level
+    #[arg(long, value_enum)]
+    pub term_log_level: Option<LogLevel>,
+}
+
+impl Args {
+    /// the config file with the credentials of the environment and the command line overrides,
+    /// validated
+    pub fn load(&self) -> Result<Config, Error> {
+        let mut config = from_file(&self.config)
+            .map_err(|e| Error::Config(format!("cannot load [{}]: {e}", self.config)))?;
+        config.override_credentials(|key| std::env::var(key).ok());
+        self.override_config(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn override_config(&self, config: &mut Config) {
+        if let Some(usd_cyclic_arbitrage) = self.usd_cyclic_arbitrage {
+            config.behaviour.usd_cyclic_arbitrage = usd_cyclic_arbitrage;
+        }
+        if let Some(execution_mode) = self.execution_mode {
+            config.behaviour.execution_mode = execution_mode;
+        }
+        if let Some(status_port) = self.status_port {
+            config.behaviour.status_port = Some(status_port);
+        }
+        if let Some(term_log_level) = self.term_log_level {
+            config.log.term_log_level = term_log_level;
+        }
+    }
+}
+
+/// config of the binary, loaded from the --config argument (config.toml by default)
+pub fn load() -> Result<Config, Error> {
+    Args::parse().load()
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Serialize)]
 pub struct Config {
+    /// may be left out when every credential is set in the environment
+    #[serde(default)]
     pub kucoin: KuCoinConfig,
     pub behaviour: BehaviourConfig,
+    #[serde(default)]
+    pub inventory: InventoryConfig,
+    #[serde(default)]
+    pub margin: MarginConfig,
+    #[serde(default)]
+    pub risk: RiskConfig,
+    #[serde(default)]
+    pub journal: JournalConfig,
+    #[serde(default)]
+    pub reconcile: ReconcileConfig,
+    #[serde(default)]
+    pub dashboard: DashboardConfig,
     pub log: LogConfig,
 }
 
 impl Config {
     pub fn kucoin_credentials(self) -> Credentials {
-        Credentials::new(
-            &self.kucoin.api_key,
-            &self.kucoin.secret_key,
-            &self.kucoin.passphrase,
-        )
+        Credentials::from(&self.kucoin)
+    }
+
+    /// replaces the credentials set in the environment, see [`ENV_API_KEY`]
+    pub fn override_credentials(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let kucoin = &mut self.kucoin;
+        for (key, credential) in [
+            (ENV_API_KEY, &mut kucoin.api_key),
+            (ENV_SECRET_KEY, &mut kucoin.secret_key),
+            (ENV_PASSPHRASE, &mut kucoin.passphrase),
+        ] {
+            if let Some(value) = var(key).filter(|value| !value.is_empty()) {
+                *credential = value;
+            }
+        }
+    }
+
+    /// checks the credentials, required only by the binaries signing requests to private endpoints
+    pub fn validate_private(&self) -> Result<(), Error> {
+        let mut violations = Violations::default();
+        for (field, key, credential) in [
+            ("kucoin.api_key", ENV_API_KEY, &self.kucoin.api_key),
+            ("kucoin.secret_key", ENV_SECRET_KEY, &self.kucoin.secret_key),
+            ("kucoin.passphrase", ENV_PASSPHRASE, &self.kucoin.passphrase),
+        ] {
+            violations.require(
+                !credential.is_empty(),
+                field,
+                format!("must be set, or {key} in the environment"),
+            );
+        }
+        violations.into_result()
+    }
+
+    /// checks the values the tasks rely on, e.g. intervals they divide by, listing every invalid field
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut violations = Violations::default();
+        let behaviour = &self.behaviour;
+        violations.positive(
+            "behaviour.monitor_interval_sec",
+            behaviour.monitor_interval_sec.into(),
+        );
+        violations.positive(
+            "behaviour.usd_cyclic_arbitrage",
+            behaviour.usd_cyclic_arbitrage.into(),
+        );
+        violations.positive(
+            "behaviour.orderbook_max_age_sec",
+            behaviour.orderbook_max_age_sec.into(),
+        );
+        violations.positive(
+            "behaviour.leg_timeout_sec",
+            behaviour.leg_timeout_sec.into(),
+        );
+        violations.require(
+            behaviour.status_port.is_none() || behaviour.status_port != behaviour.metrics_port,
+            "behaviour.status_port",
+            "must differ from behaviour.metrics_port",
+        );
+
+        let inventory = &self.inventory;
+        for (currency, target) in &inventory.targets {
+            violations.non_negative(&format!("inventory.targets.{currency}"), *target);
+        }
+        violations.require(
+            !inventory.rebalance_quote.is_empty(),
+            "inventory.rebalance_quote",
+            "must be set",
+        );
+        violations.positive("inventory.drift_threshold", inventory.drift_threshold);
+        violations.positive(
+            "inventory.rebalance_interval_sec",
+            inventory.rebalance_interval_sec.into(),
+        );
+
+        let margin = &self.margin;
+        for (currency, max_borrow) in &margin.max_borrow {
+            violations.non_negative(&format!("margin.max_borrow.{currency}"), *max_borrow);
+        }
+        violations.non_negative("margin.max_daily_rate", margin.max_daily_rate);
+        violations.require(
+            margin
+                .term
+                .split(',')
+                .all(|term| ["7", "14", "28"].contains(&term.trim())),
+            "margin.term",
+            format!("must list terms of 7, 14 or 28 days, got [{}]", margin.term),
+        );
+        violations.positive(
+            "margin.loan_sync_interval_sec",
+            margin.loan_sync_interval_sec.into(),
+        );
+        violations.require(
+            !margin.enabled || margin.mode != MarginMode::Isolated,
+            "margin.mode",
+            "must be cross, isolated margin is not supported by kucoin_api",
+        );
+
+        let risk = &self.risk;
+        violations.non_negative("risk.daily_loss_limit", risk.daily_loss_limit);
+        for (currency, max_position) in &risk.max_position {
+            violations.non_negative(&format!("risk.max_position.{currency}"), *max_position);
+        }
+        violations.positive("risk.max_cycle_notional", risk.max_cycle_notional);
+        violations.positive(
+            "risk.max_consecutive_failures",
+            risk.max_consecutive_failures.into(),
+        );
+        violations.positive(
+            "risk.blacklist_after_failures",
+            risk.blacklist_after_failures.into(),
+        );
+        violations.non_negative("risk.taker_fee", risk.taker_fee);
+
+        violations.require(
+            !self.journal.enabled || !self.journal.path.is_empty(),
+            "journal.path",
+            "must be set when the journal is enabled",
+        );
+        violations.positive("dashboard.refresh_ms", self.dashboard.refresh_ms.into());
+        violations.into_result()
     }
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+/// invalid fields found by [`Config::validate`] and [`Config::validate_private`]
+#[derive(Debug, Default)]
+struct Violations(Vec<String>);
+
+impl Violations {
+    fn into_result(self) -> Result<(), Error> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Config(self.0.join("; "))),
+        }
+    }
+
+    fn require(&mut self, valid: bool, field: &str, rule: impl Display) {
+        if !valid {
+            self.0.push(format!("{field} {rule}"));
+        }
+    }
+
+    fn positive(&mut self, field: &str, value: f64) {
+        self.require(
+            value.is_finite() && value > 0.0,
+            field,
+            format!("must be greater than 0, got {value}"),
+        );
+    }
+
+    fn non_negative(&mut self, field: &str, value: f64) {
+        self.require(
+            value.is_finite() && value >= 0.0,
+            field,
+            format!("must not be negative, got {value}"),
+        );
+    }
+}
+
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(default)]
 pub struct KuCoinConfig {
     pub api_key: String,
     pub secret_key: String,
@@ -37,6 +266,359 @@ impl From<&KuCoinConfig> for Credentials {
 pub struct BehaviourConfig {
     pub monitor_interval_sec: u32,
     pub usd_cyclic_arbitrage: u32,
+    /// orderbooks not updated within this period are excluded from chance calculation
+    #[serde(default = "default_orderbook_max_age_sec")]
+    pub orderbook_max_age_sec: u32,
+    /// orderbook websocket feed to subscribe
+    #[serde(default)]
+    pub orderbook_feed: OrderbookFeed,
+    /// retries of an order rejected with a transient code (e.g. 429000)
+    #[serde(default = "default_order_max_retries")]
+    pub order_max_retries: u32,
+    /// wait before the first order retry in milliseconds, doubled on each retry
+    #[serde(default = "default_order_retry_backoff_ms")]
+    pub order_retry_backoff_ms: u32,
+    /// wait for each shutdown phase (in-flight cycle, order cancellation, flush) in seconds
+    #[serde(default = "default_shutdown_grace_sec")]
+    pub shutdown_grace_sec: u32,
+    /// wait for a leg to be filled or canceled in seconds, canceled and failing its cycle after
+    #[serde(default = "default_leg_timeout_sec")]
+    pub leg_timeout_sec: u32,
+    /// how the legs of a cycle are placed
+    #[serde(default)]
+    pub execution_mode: ExecutionMode,
+    /// port of the Prometheus /metrics endpoint, not served when missing
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    /// address the metrics endpoint listens on, local only by default
+    #[serde(default = "default_metrics_host")]
+    pub metrics_host: IpAddr,
+    /// port of the JSON status and control API, not served when missing
+    #[serde(default)]
+    pub status_port: Option<u16>,
+    /// address the status API listens on, local only by default as its control endpoints
+    /// (pause, resume, cancel all) are not authenticated
+    #[serde(default = "default_status_host")]
+    pub status_host: IpAddr,
+    /// refetches from REST the orderbooks whose level2 changes were dropped by a lagging channel,
+    /// or skipped as out of sequence
+    #[serde(default = "default_resync_on_lag")]
+    pub resync_on_lag: bool,
+}
+
+/// Placement of the three legs of a cyclic arbitrage
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionMode {
+    /// each leg is placed once the previous one is filled
+    #[default]
+    Sequential,
+    /// all legs are placed at once when the account holds enough of each spent currency,
+    /// falling back to sequential otherwise
+    Simultaneous,
+}
+
+/// KuCoin orderbook websocket feed
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderbookFeed {
+    /// incremental level2 changes merged onto the REST orderbook with sequence management
+    #[default]
+    Level2,
+    /// top 5 levels snapshot (spotMarket/level2Depth5)
+    Depth5,
+    /// top 50 levels snapshot (spotMarket/level2Depth50)
+    Depth50,
+    /// best bid/ask ticker (market/ticker)
+    Ticker,
+}
+
+/// Target inventory kept for the simultaneous execution mode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryConfig {
+    /// amount of each currency to hold, e.g. BTC = 0.001
+    #[serde(default)]
+    pub targets: BTreeMap<String, f64>,
+    /// currency traded against every other target when rebalancing, absorbing the difference
+    #[serde(default = "default_rebalance_quote")]
+    pub rebalance_quote: String,
+    /// relative drift from a target that triggers rebalancing, e.g. 0.2 for 20%
+    #[serde(default = "default_drift_threshold")]
+    pub drift_threshold: f64,
+    /// interval between rebalancing checks in seconds
+    #[serde(default = "default_rebalance_interval_sec")]
+    pub rebalance_interval_sec: u32,
+}
+
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        InventoryConfig {
+            targets: BTreeMap::new(),
+            rebalance_quote: default_rebalance_quote(),
+            drift_threshold: default_drift_threshold(),
+            rebalance_interval_sec: default_rebalance_interval_sec(),
+        }
+    }
+}
+
+/// Margin borrowing limits, borrowing is disabled unless enabled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarginConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub mode: MarginMode,
+    /// max liability of each currency, currencies not listed cannot be borrowed
+    #[serde(default)]
+    pub max_borrow: BTreeMap<String, f64>,
+    /// max daily interest rate accepted, e.g. 0.0005 for 0.05%
+    #[serde(default = "default_max_daily_rate")]
+    pub max_daily_rate: f64,
+    /// comma delimited loan terms in days, any of 7, 14 and 28
+    #[serde(default = "default_borrow_term")]
+    pub term: String,
+    /// interval between refreshing the outstanding loans and their interest in seconds
+    #[serde(default = "default_loan_sync_interval_sec")]
+    pub loan_sync_interval_sec: u32,
+}
+
+impl Default for MarginConfig {
+    fn default() -> Self {
+        MarginConfig {
+            enabled: false,
+            mode: MarginMode::default(),
+            max_borrow: BTreeMap::new(),
+            max_daily_rate: default_max_daily_rate(),
+            term: default_borrow_term(),
+            loan_sync_interval_sec: default_loan_sync_interval_sec(),
+        }
+    }
+}
+
+/// KuCoin margin account
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarginMode {
+    /// shared by every symbol
+    #[default]
+    Cross,
+    /// dedicated to a single symbol, not supported by kucoin_api yet
+    Isolated,
+}
+
+/// Risk limits, the system halts once a loss limit is breached
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskConfig {
+    /// realised loss of a UTC day in the start currency of the cycles (USDT)
+    #[serde(default = "default_daily_loss_limit")]
+    pub daily_loss_limit: f64,
+    /// max absolute position of each currency built up by cycles, currencies not listed are unlimited
+    #[serde(default)]
+    pub max_position: BTreeMap<String, f64>,
+    /// max amount spent by the first leg of a cycle
+    #[serde(default = "default_max_cycle_notional")]
+    pub max_cycle_notional: f64,
+    /// failed cycles in a row before halting
+    #[serde(default = "default_max_consecutive_failures")]
+    pub max_consecutive_failures: u32,
+    /// failed cycles of a symbol before its chances are ignored
+    #[serde(default = "default_blacklist_after_failures")]
+    pub blacklist_after_failures: u32,
+    /// fee rate of the taker fills, charged in the quote currency and deducted from the cycle pnl
+    #[serde(default = "default_taker_fee")]
+    pub taker_fee: f64,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig {
+            daily_loss_limit: default_daily_loss_limit(),
+            max_position: BTreeMap::new(),
+            max_cycle_notional: default_max_cycle_notional(),
+            max_consecutive_failures: default_max_consecutive_failures(),
+            blacklist_after_failures: default_blacklist_after_failures(),
+            taker_fee: default_taker_fee(),
+        }
+    }
+}
+
+/// Trade journal, every chance, order, acknowledgement, trade and cycle persisted in SQLite
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct JournalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// database file, created with the schema on first use
+    #[serde(default = "default_journal_path")]
+    pub path: String,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        JournalConfig {
+            enabled: false,
+            path: default_journal_path(),
+        }
+    }
+}
+
+fn default_journal_path() -> String {
+    String::from("./journal.sqlite")
+}
+
+/// Startup reconciliation of the orders and positions left by a previous run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ReconcileConfig {
+    #[serde(default = "default_reconcile_enabled")]
+    pub enabled: bool,
+    /// open orders of a previous run, recognised by their cycle id or from the journal
+    #[serde(default)]
+    pub orphan_orders: OrphanOrders,
+    /// sells the currencies left by an interrupted cycle back into the currency it started with,
+    /// only reported otherwise
+    #[serde(default)]
+    pub unwind_residuals: bool,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        ReconcileConfig {
+            enabled: default_reconcile_enabled(),
+            orphan_orders: OrphanOrders::default(),
+            unwind_residuals: false,
+        }
+    }
+}
+
+/// Handling of the orders left open by a previous run
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanOrders {
+    #[default]
+    Cancel,
+    /// tracked as placed by this process, so canceled on shutdown
+    Adopt,
+}
+
+fn default_reconcile_enabled() -> bool {
+    true
+}
+
+/// Terminal dashboard attached to the status API of a running system
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DashboardConfig {
+    /// host serving the status API on behaviour.status_port
+    #[serde(default = "default_dashboard_host")]
+    pub host: String,
+    /// symbols whose top of book is shown
+    #[serde(default = "default_dashboard_symbols")]
+    pub symbols: Vec<String>,
+    /// interval between polls of the status API in milliseconds
+    #[serde(default = "default_dashboard_refresh_ms")]
+    pub refresh_ms: u32,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        DashboardConfig {
+            host: default_dashboard_host(),
+            symbols: default_dashboard_symbols(),
+            refresh_ms: default_dashboard_refresh_ms(),
+        }
+    }
+}
+
+fn default_dashboard_host() -> String {
+    String::from("127.0.0.1")
+}
+
+fn default_dashboard_symbols() -> Vec<String> {
+    vec![
+        String::from("BTC-USDT"),
+        String::from("ETH-USDT"),
+        String::from("ETH-BTC"),
+    ]
+}
+
+fn default_dashboard_refresh_ms() -> u32 {
+    1000
+}
+
+fn default_daily_loss_limit() -> f64 {
+    10.0
+}
+
+fn default_max_cycle_notional() -> f64 {
+    100.0
+}
+
+fn default_max_consecutive_failures() -> u32 {
+    5
+}
+
+fn default_blacklist_after_failures() -> u32 {
+    3
+}
+
+fn default_taker_fee() -> f64 {
+    0.001
+}
+
+fn default_max_daily_rate() -> f64 {
+    0.0005
+}
+
+fn default_borrow_term() -> String {
+    String::from("7")
+}
+
+fn default_loan_sync_interval_sec() -> u32 {
+    60
+}
+
+fn default_rebalance_quote() -> String {
+    String::from("USDT")
+}
+
+fn default_drift_threshold() -> f64 {
+    0.2
+}
+
+fn default_rebalance_interval_sec() -> u32 {
+    60
+}
+
+fn default_orderbook_max_age_sec() -> u32 {
+    60
+}
+
+fn default_order_max_retries() -> u32 {
+    3
+}
+
+fn default_order_retry_backoff_ms() -> u32 {
+    200
+}
+
+fn default_leg_timeout_sec() -> u32 {
+    10
+}
+
+fn default_shutdown_grace_sec() -> u32 {
+    10
+}
+
+fn default_resync_on_lag() -> bool {
+    true
+}
+
+fn default_status_host() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn default_metrics_host() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
 }
 
 pub fn from_file(filename: &str) -> Result<Config, Error> {
@@ -45,7 +627,9 @@ pub fn from_file(filename: &str) -> Result<Config, Error> {
 }
 
 /// custom log level declared for the custom FromStr
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     #[default]
@@ -69,7 +653,7 @@ impl From<LogLevel> for tracing::Level {
 }
 
 impl FromStr for LogLevel {
-    type Err = eyre::Error;
+    type Err = Error;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         match s.to_ascii_lowercase().as_ref() {
             "error" => Ok(LogLevel::Error),
@@ -77,7 +661,7 @@ impl FromStr for LogLevel {
             "info" => Ok(LogLevel::Info),
             "debug" => Ok(LogLevel::Debug),
             "trace" => Ok(LogLevel::Trace),
-            _ => Err(eyre::eyre!("Invalid log level: {}", s)),
+            _ => Err(Error::Config(format!("Invalid log level: {}", s))),
         }
     }
 }
@@ -112,9 +696,132 @@ mod tests {
         let config: super::Config = res.unwrap();
         assert_eq!(config.behaviour.monitor_interval_sec, 120);
         assert_eq!(config.behaviour.usd_cyclic_arbitrage, 20);
+        assert_eq!(config.behaviour.orderbook_max_age_sec, 60);
+        assert_eq!(
+            config.behaviour.orderbook_feed,
+            super::OrderbookFeed::Level2
+        );
+        assert_eq!(config.behaviour.order_max_retries, 3);
+        assert_eq!(config.behaviour.order_retry_backoff_ms, 200);
+        assert_eq!(config.behaviour.shutdown_grace_sec, 10);
+        assert_eq!(config.behaviour.leg_timeout_sec, 10);
+        assert_eq!(
+            config.behaviour.execution_mode,
+            super::ExecutionMode::Sequential
+        );
+        assert_eq!(config.behaviour.metrics_port, None);
+        assert!(config.behaviour.metrics_host.is_loopback());
+        assert_eq!(config.behaviour.status_port, None);
+        assert!(config.behaviour.status_host.is_loopback());
+        assert!(config.behaviour.resync_on_lag);
+
+        assert!(config.inventory.targets.is_empty());
+        assert_eq!(config.inventory.rebalance_quote, "USDT");
+        assert_eq!(config.inventory.drift_threshold, 0.2);
+        assert_eq!(config.inventory.rebalance_interval_sec, 60);
+
+        assert!(!config.margin.enabled);
+        assert_eq!(config.margin.mode, super::MarginMode::Cross);
+        assert_eq!(config.margin.max_daily_rate, 0.0005);
+        assert_eq!(config.margin.term, "7");
+        assert_eq!(config.margin.loan_sync_interval_sec, 60);
+
+        assert_eq!(config.risk.daily_loss_limit, 10.0);
+        assert!(config.risk.max_position.is_empty());
+        assert_eq!(config.risk.max_cycle_notional, 100.0);
+        assert_eq!(config.risk.max_consecutive_failures, 5);
+        assert_eq!(config.risk.blacklist_after_failures, 3);
+        assert_eq!(config.risk.taker_fee, 0.001);
+
+        assert!(!config.journal.enabled);
+        assert_eq!(config.journal.path, "./journal.sqlite");
+
+        assert!(config.reconcile.enabled);
+        assert_eq!(config.reconcile.orphan_orders, super::OrphanOrders::Cancel);
+        assert!(!config.reconcile.unwind_residuals);
+
+        assert_eq!(config.dashboard.host, "127.0.0.1");
+        assert_eq!(
+            config.dashboard.symbols,
+            ["BTC-USDT", "ETH-USDT", "ETH-BTC"]
+        );
+        assert_eq!(config.dashboard.refresh_ms, 1000);
 
         assert_eq!(config.log.file_directory, "./logs/");
         assert_eq!(config.log.file_log_level, super::LogLevel::Warn);
         assert_eq!(config.log.term_log_level, super::LogLevel::Info);
     }
+
+    #[test]
+    fn validate_config() {
+        let toml_str = "
+        [behaviour]
+        monitor_interval_sec = 0
+        usd_cyclic_arbitrage = 20
+        metrics_port = 9184
+        status_port = 9184
+        [margin]
+        term = \"7,30\"
+        [log]
+        file_directory = \"./logs/\"
+        file_prefix = \"log\"
+        file_log_level = \"warn\"
+        term_log_level = \"info\"
+        ";
+        let mut config: super::Config = toml::from_str(toml_str).unwrap();
+        let Err(crate::error::Error::Config(msg)) = config.validate_private() else {
+            panic!("missing credentials accepted");
+        };
+        assert!(msg.contains("kucoin.api_key must be set, or KUCOIN_API_KEY in the environment"));
+        let Err(crate::error::Error::Config(msg)) = config.validate() else {
+            panic!("invalid config accepted");
+        };
+        assert!(!msg.contains("kucoin.api_key"));
+        assert!(msg.contains("behaviour.monitor_interval_sec must be greater than 0, got 0"));
+        assert!(msg.contains("behaviour.status_port must differ"));
+        assert!(msg.contains("margin.term"));
+
+        config.override_credentials(|key| Some(format!("{key}_VALUE")));
+        assert_eq!(config.kucoin.api_key, "KUCOIN_API_KEY_VALUE");
+        assert_eq!(config.kucoin.passphrase, "KUCOIN_PASSPHRASE_VALUE");
+        assert!(config.validate_private().is_ok());
+        config.behaviour.monitor_interval_sec = 120;
+        config.behaviour.status_port = Some(9185);
+        config.margin.term = "7, 14".to_string();
+        assert!(config.validate().is_ok());
+        config.risk.max_consecutive_failures = 0;
+        let Err(crate::error::Error::Config(msg)) = config.validate() else {
+            panic!("halting after the first cycle accepted");
+        };
+        assert_eq!(
+            msg,
+            "risk.max_consecutive_failures must be greater than 0, got 0"
+        );
+        config.risk.max_consecutive_failures = 5;
+        config.margin.enabled = true;
+        config.margin.mode = super::MarginMode::Isolated;
+        let Err(crate::error::Error::Config(msg)) = config.validate() else {
+            panic!("isolated margin accepted");
+        };
+        assert!(msg.starts_with("margin.mode"));
+        config.margin.mode = super::MarginMode::Cross;
+
+        let args = <super::Args as clap::Parser>::parse_from([
+            "kucoin_arbitrage",
+            "--config",
+            "other.toml",
+            "--usd-cyclic-arbitrage",
+            "50",
+            "--term-log-level",
+            "debug",
+        ]);
+        assert_eq!(args.config, "other.toml");
+        args.override_config(&mut config);
+        assert_eq!(config.behaviour.usd_cyclic_arbitrage, 50);
+        assert_eq!(
+            config.behaviour.execution_mode,
+            super::ExecutionMode::Sequential
+        );
+        assert_eq!(config.log.term_log_level, super::LogLevel::Debug);
+    }
 }
@@ -1,13 +1,68 @@
+use crate::translator::error::ConversionError;
+use kucoin_api::error::APIError;
 use std::error;
 use std::fmt;
 use std::io;
+use tokio::sync::broadcast;
 use toml;
 
+/// Library-wide result
+pub type Result<T> = std::result::Result<T, Error>;
+
 #[derive(Debug)]
 pub enum Error {
     IoError(io::Error),
     TomlError(toml::de::Error),
-    // Add more error variants as needed
+    /// invalid config value
+    Config(String),
+    /// REST or websocket failure before reaching the exchange logic
+    Network(String),
+    /// too many requests (429000)
+    RateLimited,
+    /// request rejected by the exchange with its response code
+    ExchangeRejected {
+        code: String,
+        msg: String,
+    },
+    /// balance insufficient for the order (200004)
+    InsufficientFunds,
+    /// API model could not be converted into the internal model
+    Conversion(ConversionError),
+    /// other string/payload that could not be parsed
+    Parse(String),
+    /// order breaking the exchange rules, not sent
+    InvalidOrder(String),
+    /// broadcast channel closed
+    ChannelClosed,
+    /// broadcast receiver lagged behind, with the number of skipped messages
+    ChannelLagged(u64),
+    /// local orderbook out of sync with the exchange
+    OrderbookDesync(String),
+    /// trade journal database failure
+    Journal(String),
+}
+
+impl Error {
+    /// Maps a KuCoin response code, None for success (200000)
+    pub fn from_kucoin_code(code: &str, msg: Option<&str>) -> Option<Error> {
+        match code {
+            "200000" => None,
+            "200004" => Some(Error::InsufficientFunds),
+            "429000" => Some(Error::RateLimited),
+            code => Some(Error::ExchangeRejected {
+                code: code.to_string(),
+                msg: msg.unwrap_or_default().to_string(),
+            }),
+        }
+    }
+
+    /// Ok for success (200000), otherwise the mapped error
+    pub fn check_kucoin_code(code: &str, msg: Option<&str>) -> Result<()> {
+        match Error::from_kucoin_code(code, msg) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -15,7 +70,20 @@ impl fmt::Display for Error {
         match *self {
             Error::IoError(ref err) => write!(f, "IO error: {}", err),
             Error::TomlError(ref err) => write!(f, "TOML error: {}", err),
-            // Add more error variants as needed
+            Error::Config(ref msg) => write!(f, "Config error: {}", msg),
+            Error::Network(ref msg) => write!(f, "Network error: {}", msg),
+            Error::RateLimited => write!(f, "Rate limited"),
+            Error::ExchangeRejected { ref code, ref msg } => {
+                write!(f, "Exchange rejected [{}]: {}", code, msg)
+            }
+            Error::InsufficientFunds => write!(f, "Insufficient funds"),
+            Error::Conversion(ref err) => write!(f, "Conversion error: {}", err),
+            Error::Parse(ref msg) => write!(f, "Parse error: {}", msg),
+            Error::InvalidOrder(ref msg) => write!(f, "Invalid order: {}", msg),
+            Error::ChannelClosed => write!(f, "Channel closed"),
+            Error::ChannelLagged(count) => write!(f, "Channel lagged by {} messages", count),
+            Error::OrderbookDesync(ref msg) => write!(f, "Orderbook desync: {}", msg),
+            Error::Journal(ref msg) => write!(f, "Journal error: {}", msg),
         }
     }
 }
@@ -25,7 +93,74 @@ impl error::Error for Error {
         match *self {
             Error::IoError(ref err) => Some(err),
             Error::TomlError(ref err) => Some(err),
-            // Add more error variants as needed
+            Error::Conversion(ref err) => Some(err),
+            _ => None,
         }
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::IoError(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::TomlError(err)
+    }
+}
+
+impl From<ConversionError> for Error {
+    fn from(err: ConversionError) -> Self {
+        Error::Conversion(err)
+    }
+}
+
+impl From<APIError> for Error {
+    fn from(err: APIError) -> Self {
+        match err {
+            APIError::Serde(err) => Error::Parse(err.to_string()),
+            err => Error::Network(err.to_string()),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Journal(err.to_string())
+    }
+}
+
+impl<T> From<broadcast::error::SendError<T>> for Error {
+    fn from(_: broadcast::error::SendError<T>) -> Self {
+        Error::ChannelClosed
+    }
+}
+
+impl From<broadcast::error::RecvError> for Error {
+    fn from(err: broadcast::error::RecvError) -> Self {
+        match err {
+            broadcast::error::RecvError::Closed => Error::ChannelClosed,
+            broadcast::error::RecvError::Lagged(count) => Error::ChannelLagged(count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+
+    #[test]
+    fn test_from_kucoin_code() {
+        assert!(Error::from_kucoin_code("200000", None).is_none());
+        assert!(matches!(
+            Error::from_kucoin_code("429000", None),
+            Some(Error::RateLimited)
+        ));
+        assert!(matches!(
+            Error::from_kucoin_code("400100", Some("Order size increment invalid.")),
+            Some(Error::ExchangeRejected { code, .. }) if code == "400100"
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::model::cycle::CycleId;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// Outcome of a cyclic arbitrage, published by the gatekeeper once the cycle ends
+#[derive(Debug, Clone, PartialEq)]
+pub enum CycleEvent {
+    /// every leg filled
+    Completed(CycleResult),
+    /// aborted at the leg of the symbol, e.g. rejected or canceled
+    Failed {
+        result: CycleResult,
+        symbol: String,
+        reason: String,
+    },
+}
+
+impl CycleEvent {
+    pub fn result(&self) -> &CycleResult {
+        match self {
+            CycleEvent::Completed(result) => result,
+            CycleEvent::Failed { result, .. } => result,
+        }
+    }
+}
+
+/// Amounts moved by the filled legs of a cycle, at the prices they executed at
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CycleResult {
+    /// None when rebuilt from legs placed without a cycle id
+    pub id: Option<CycleId>,
+    /// when the chance was accepted
+    pub started: DateTime<Utc>,
+    /// currency spent by the first leg and received by the last one
+    pub currency: String,
+    /// symbols of the legs
+    pub symbols: Vec<String>,
+    /// net change of each currency, fees deducted
+    pub flows: BTreeMap<String, f64>,
+    /// value of a unit of each currency in the start currency, at the prices the legs acquire it
+    pub costs: BTreeMap<String, f64>,
+}
+
+impl CycleResult {
+    /// realised profit (negative for loss) in the currency the cycle started with.
+    /// The currencies still held are valued at their cost, so a failed cycle only realises its
+    /// residual and not the notional of the legs left unwound.
+    pub fn pnl(&self) -> f64 {
+        self.flows
+            .iter()
+            .map(|(currency, flow)| match currency == &self.currency {
+                true => *flow,
+                false => flow * self.costs.get(currency).copied().unwrap_or_default(),
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CycleResult;
+    use crate::model::chance::ActionInfo;
+    use crate::model::inventory::{add_fee, add_fill, costs};
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn test_failed_cycle_realises_fees() {
+        let actions = [
+            ActionInfo::buy(
+                "BTC-USDT".into(),
+                OrderedFloat(20000.0),
+                OrderedFloat(0.001),
+            ),
+            ActionInfo::buy("ETH-BTC".into(), OrderedFloat(0.05), OrderedFloat(0.02)),
+            ActionInfo::sell("ETH-USDT".into(), OrderedFloat(1002.0), OrderedFloat(0.02)),
+        ];
+        let mut result = CycleResult {
+            currency: "USDT".to_string(),
+            costs: costs("USDT", &actions),
+            ..Default::default()
+        };
+        // aborted after the first leg, the BTC bought is held at its cost
+        add_fill(&mut result.flows, &actions[0], 0.001);
+        add_fee(&mut result.flows, &actions[0], 0.001, 0.001);
+        assert!((result.pnl() + 0.02).abs() < 1e-9);
+
+        // completed, the 0.04 earned on the last leg less the fee of the first
+        for action in &actions[1..] {
+            add_fill(&mut result.flows, action, 0.02);
+        }
+        assert!((result.pnl() - 0.02).abs() < 1e-9);
+    }
+}
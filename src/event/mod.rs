@@ -1,5 +1,7 @@
 /// Arbitrage chance enum for internal
 pub mod chance;
+/// Cycle outcome enum for risk management
+pub mod cycle;
 /// Order placement enum for REST
 pub mod order;
 /// Orderbook change enum for subscription
@@ -1,10 +1,33 @@
-use crate::model::order::LimitOrder;
+use crate::model::margin::BorrowOrder;
+use crate::model::order::{LimitOrder, MarketOrder};
+use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OrderEvent {
     GetAllOrders,
     CancelOrder(LimitOrder),
-    CancelAllOrders,
+    /// cancels the orders of a symbol, or of every symbol with None
+    CancelAllOrders(Option<String>),
     PlaceLimitOrder(LimitOrder),
-    PlaceBorrowOrder(LimitOrder),
+    PlaceMarketOrder(MarketOrder),
+    /// places the orders at once, each replied separately
+    PlaceBatch(Vec<LimitOrder>),
+    /// borrows on margin, handled by the margin task and replied as an order result
+    PlaceBorrowOrder(BorrowOrder),
+    /// repays every outstanding loan of the currency
+    RepayLoans(String),
+}
+
+/// Final outcome of an order request, replied by the order task
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OrderResultEvent {
+    /// accepted by the exchange, latency being the REST round trip of the accepted attempt
+    Accepted {
+        client_oid: String,
+        exchange_oid: String,
+        latency: Duration,
+        attempts: u32,
+    },
+    /// not placed, with the reason from the exchange or the network
+    Rejected { client_oid: String, reason: String },
 }
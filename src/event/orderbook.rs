@@ -1,8 +1,33 @@
-use crate::model::orderbook::Orderbook;
+use crate::model::orderbook::{Orderbook, OrderbookChange};
 
 /// public orderbook change received from exchange
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OrderbookEvent {
     OrderbookReceived((String, Orderbook)),
-    OrderbookChangeReceived((String, Orderbook)),
+    OrderbookChangeReceived((String, OrderbookChange)),
+}
+
+impl OrderbookEvent {
+    pub fn symbol(&self) -> &str {
+        match self {
+            OrderbookEvent::OrderbookReceived((symbol, _)) => symbol,
+            OrderbookEvent::OrderbookChangeReceived((symbol, _)) => symbol,
+        }
+    }
+
+    /// exchange timestamp in unix milliseconds, 0 if unknown
+    pub fn exchange_time(&self) -> u64 {
+        match self {
+            OrderbookEvent::OrderbookReceived((_, orderbook)) => orderbook.exchange_time,
+            OrderbookEvent::OrderbookChangeReceived((_, change)) => change.exchange_time,
+        }
+    }
+
+    /// local wall time the event was received in unix microseconds
+    pub fn receive_time(&self) -> u64 {
+        match self {
+            OrderbookEvent::OrderbookReceived((_, orderbook)) => orderbook.receive_time,
+            OrderbookEvent::OrderbookChangeReceived((_, change)) => change.receive_time,
+        }
+    }
 }
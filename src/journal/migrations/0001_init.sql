@@ -0,0 +1,68 @@
+-- chances published by the strategies
+CREATE TABLE chances (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    -- all_taker or maker_taker_taker
+    kind TEXT NOT NULL,
+    profit REAL NOT NULL,
+    -- JSON array of {side, symbol, price, volume}
+    actions TEXT NOT NULL
+);
+
+-- order requests sent to the order and margin tasks
+CREATE TABLE orders (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    -- limit, market, cancel, cancel_all, borrow or repay
+    kind TEXT NOT NULL,
+    client_oid TEXT,
+    -- currency for borrow and repay
+    symbol TEXT,
+    side TEXT,
+    price TEXT,
+    size TEXT
+);
+CREATE INDEX orders_time ON orders (time);
+CREATE INDEX orders_client_oid ON orders (client_oid);
+
+-- acknowledgements of the order requests
+CREATE TABLE order_results (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    client_oid TEXT NOT NULL,
+    accepted INTEGER NOT NULL,
+    exchange_oid TEXT,
+    reason TEXT,
+    latency_ms REAL
+);
+CREATE INDEX order_results_client_oid ON order_results (client_oid);
+
+-- trade updates of our orders from the private feed
+CREATE TABLE trades (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    -- open, match, filled or canceled
+    status TEXT NOT NULL,
+    client_oid TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    size TEXT NOT NULL
+);
+CREATE INDEX trades_client_oid ON trades (client_oid);
+
+-- outcomes of the cycles published by the gatekeeper
+CREATE TABLE cycles (
+    id INTEGER PRIMARY KEY,
+    started TEXT NOT NULL,
+    ended TEXT NOT NULL,
+    -- currency spent by the first leg, in which the pnl is realised
+    currency TEXT NOT NULL,
+    -- comma delimited symbols of the legs
+    symbols TEXT NOT NULL,
+    pnl REAL NOT NULL,
+    completed INTEGER NOT NULL,
+    -- symbol and reason of the failed leg
+    failed_symbol TEXT,
+    reason TEXT
+);
+CREATE INDEX cycles_ended ON cycles (ended);
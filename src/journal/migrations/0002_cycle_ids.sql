@@ -0,0 +1,9 @@
+-- cycle of each row, parsed from the client order id of the legs (NULL outside cycles)
+ALTER TABLE orders ADD COLUMN cycle_id TEXT;
+ALTER TABLE order_results ADD COLUMN cycle_id TEXT;
+ALTER TABLE trades ADD COLUMN cycle_id TEXT;
+ALTER TABLE cycles ADD COLUMN cycle_id TEXT;
+CREATE INDEX orders_cycle_id ON orders (cycle_id);
+CREATE INDEX order_results_cycle_id ON order_results (cycle_id);
+CREATE INDEX trades_cycle_id ON trades (cycle_id);
+CREATE INDEX cycles_cycle_id ON cycles (cycle_id);
@@ -0,0 +1,7 @@
+-- events dropped by a lagging journal subscription, the rows around it are incomplete
+CREATE TABLE gaps (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    dropped INTEGER NOT NULL
+);
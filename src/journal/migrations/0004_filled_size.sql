@@ -0,0 +1,2 @@
+-- base size executed so far by the order of each trade (NULL before this version)
+ALTER TABLE trades ADD COLUMN filled_size TEXT;
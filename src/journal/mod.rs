@@ -0,0 +1,4 @@
+/// SQLite store with the schema migrations and query helpers
+pub mod store;
+/// task journaling the events of the broadcast channels
+pub mod task;
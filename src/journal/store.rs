@@ -0,0 +1,783 @@
+use crate::error::{Error, Result};
+use crate::event::chance::ChanceEvent;
+use crate::event::cycle::CycleEvent;
+use crate::event::order::{OrderEvent, OrderResultEvent};
+use crate::event::trade::TradeEvent;
+use crate::model::cycle::CycleId;
+use crate::model::order::{LimitOrder, Order};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde_derive::Serialize;
+use serde_json::json;
+
+/// Schema migrations, the n-th one bringing the journal to version n (SQLite user_version)
+const MIGRATIONS: &[&str] = &[
+    include_str!("migrations/0001_init.sql"),
+    include_str!("migrations/0002_cycle_ids.sql"),
+    include_str!("migrations/0003_gaps.sql"),
+    include_str!("migrations/0004_filled_size.sql"),
+];
+
+/// selects the orders of the legs of the cycle ?1, by cycle id,
+/// or by symbol while it was in flight for cycles journaled without one
+const CYCLE_ORDERS: &str = "
+    SELECT orders.client_oid FROM orders, cycles
+    WHERE cycles.id = ?1
+        AND (orders.cycle_id = cycles.cycle_id
+            OR (cycles.cycle_id IS NULL
+                AND orders.time BETWEEN cycles.started AND cycles.ended
+                AND instr(',' || cycles.symbols || ',', ',' || orders.symbol || ',') > 0))";
+
+/// Realised pnl of the cycles ended within a UTC day, per currency the cycles started with
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyPnl {
+    /// e.g. "2023-11-01"
+    pub day: String,
+    pub currency: String,
+    pub pnl: f64,
+    pub cycles: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CycleEntry {
+    pub id: i64,
+    /// None for cycles journaled before cycle ids
+    pub cycle_id: Option<String>,
+    pub started: String,
+    pub ended: String,
+    pub currency: String,
+    pub symbols: Vec<String>,
+    pub pnl: f64,
+    pub completed: bool,
+    pub failed_symbol: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrderEntry {
+    pub time: String,
+    pub kind: String,
+    pub client_oid: Option<String>,
+    pub symbol: Option<String>,
+    pub side: Option<String>,
+    pub price: Option<String>,
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderResultEntry {
+    pub time: String,
+    pub client_oid: String,
+    pub accepted: bool,
+    pub exchange_oid: Option<String>,
+    pub reason: Option<String>,
+    pub latency_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TradeEntry {
+    pub time: String,
+    pub status: String,
+    pub client_oid: String,
+    pub symbol: String,
+    pub side: String,
+    pub size: String,
+    /// size executed so far, None when journaled before it was recorded
+    pub filled_size: Option<String>,
+}
+
+/// Limit order of a cycle without journaled outcome, i.e. a leg of a cycle cut short
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InterruptedLeg {
+    pub time: String,
+    pub client_oid: String,
+    pub symbol: String,
+    pub side: String,
+    pub price: f64,
+    /// base size filled according to the trades journaled, without the canceled remainder
+    pub filled: f64,
+}
+
+/// Cycle with the orders, acknowledgements and trades of its legs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReconstructedCycle {
+    pub cycle: CycleEntry,
+    pub orders: Vec<OrderEntry>,
+    pub order_results: Vec<OrderResultEntry>,
+    pub trades: Vec<TradeEntry>,
+}
+
+/// Journal of the chances, orders, acknowledgements, trades and cycles in SQLite, migrated on open
+#[derive(Debug)]
+pub struct Journal {
+    connection: Connection,
+}
+
+impl Journal {
+    /// opens or creates the journal file
+    pub fn open(path: &str) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        // readers such as the startup reconciliation do not block the writer
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::migrated(connection)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::migrated(Connection::open_in_memory()?)
+    }
+
+    fn migrated(mut connection: Connection) -> Result<Self> {
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(Error::Journal(format!(
+                "journal schema version {version} is newer than {} supported",
+                MIGRATIONS.len()
+            )));
+        }
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", i + 1)?;
+            transaction.commit()?;
+            tracing::info!("Journal migrated to schema version {}", i + 1);
+        }
+        Ok(Journal { connection })
+    }
+
+    /// schema version of the journal
+    pub fn version(&self) -> Result<usize> {
+        Ok(self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    pub fn record_chance(&self, event: &ChanceEvent) -> Result<()> {
+        let (kind, chance) = match event {
+            ChanceEvent::AllTaker(chance) => ("all_taker", chance),
+            ChanceEvent::MakerTakerTaker(chance) => ("maker_taker_taker", chance),
+        };
+        let actions: Vec<_> = chance
+            .actions
+            .iter()
+            .map(|a| {
+                json!({
+                    "side": a.action.to_string(),
+                    "symbol": a.ticker,
+                    "price": a.price.0,
+                    "volume": a.volume.0,
+                })
+            })
+            .collect();
+        self.connection.execute(
+            "INSERT INTO chances (time, kind, profit, actions) VALUES (?1, ?2, ?3, ?4)",
+            params![now(), kind, chance.profit.0, json!(actions).to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// records the order request, a batch as one row per order
+    pub fn record_order(&self, event: &OrderEvent) -> Result<()> {
+        match event {
+            OrderEvent::GetAllOrders => Ok(()),
+            OrderEvent::PlaceLimitOrder(order) => self.insert_limit_order("limit", order),
+            OrderEvent::PlaceBatch(orders) => {
+                for order in orders {
+                    self.insert_limit_order("limit", order)?;
+                }
+                Ok(())
+            }
+            OrderEvent::CancelOrder(order) => self.insert_limit_order("cancel", order),
+            OrderEvent::PlaceMarketOrder(order) => self.insert_order(OrderEntry {
+                time: now(),
+                kind: "market".to_string(),
+                client_oid: Some(order.id()),
+                symbol: Some(order.symbol()),
+                side: Some(order.side().to_string()),
+                price: None,
+                size: Some(order.amount()),
+            }),
+            OrderEvent::CancelAllOrders(symbol) => self.insert_order(OrderEntry {
+                symbol: symbol.clone(),
+                ..entry("cancel_all")
+            }),
+            OrderEvent::PlaceBorrowOrder(order) => self.insert_order(OrderEntry {
+                client_oid: Some(order.id.clone()),
+                symbol: Some(order.currency.clone()),
+                size: Some(order.size.clone()),
+                ..entry("borrow")
+            }),
+            OrderEvent::RepayLoans(currency) => self.insert_order(OrderEntry {
+                symbol: Some(currency.clone()),
+                ..entry("repay")
+            }),
+        }
+    }
+
+    fn insert_limit_order(&self, kind: &str, order: &LimitOrder) -> Result<()> {
+        self.insert_order(OrderEntry {
+            time: now(),
+            kind: kind.to_string(),
+            client_oid: Some(order.id.clone()),
+            symbol: Some(order.symbol.clone()),
+            side: Some(order.side.to_string()),
+            price: Some(order.price.clone()),
+            size: Some(order.amount.clone()),
+        })
+    }
+
+    fn insert_order(&self, order: OrderEntry) -> Result<()> {
+        let cycle_id = order.client_oid.as_deref().and_then(cycle_of);
+        self.connection.execute(
+            "INSERT INTO orders (time, kind, client_oid, symbol, side, price, size, cycle_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                order.time,
+                order.kind,
+                order.client_oid,
+                order.symbol,
+                order.side,
+                order.price,
+                order.size,
+                cycle_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_order_result(&self, event: &OrderResultEvent) -> Result<()> {
+        let (client_oid, accepted, exchange_oid, reason, latency_ms) = match event {
+            OrderResultEvent::Accepted {
+                client_oid,
+                exchange_oid,
+                latency,
+                ..
+            } => (
+                client_oid,
+                true,
+                Some(exchange_oid),
+                None,
+                Some(latency.as_secs_f64() * 1000.0),
+            ),
+            OrderResultEvent::Rejected { client_oid, reason } => {
+                (client_oid, false, None, Some(reason), None)
+            }
+        };
+        self.connection.execute(
+            "INSERT INTO order_results
+                (time, client_oid, accepted, exchange_oid, reason, latency_ms, cycle_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                now(),
+                client_oid,
+                accepted,
+                exchange_oid,
+                reason,
+                latency_ms,
+                cycle_of(client_oid)
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_trade(&self, event: &TradeEvent) -> Result<()> {
+        let (status, info) = match event {
+            TradeEvent::TradeOpen(info) => ("open", info),
+            TradeEvent::TradeMatch(info) => ("match", info),
+            TradeEvent::TradeFilled(info) => ("filled", info),
+            TradeEvent::TradeCanceled(info) => ("canceled", info),
+        };
+        self.connection.execute(
+            "INSERT INTO trades (time, status, client_oid, symbol, side, size, cycle_id, filled_size)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                now(),
+                status,
+                info.client_oid,
+                info.symbol,
+                info.side.to_string(),
+                info.size,
+                info.cycle_id.map(|id| id.to_string()),
+                info.filled_size
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// records the outcome of a cycle, ended now
+    pub fn record_cycle(&self, event: &CycleEvent) -> Result<()> {
+        let result = event.result();
+        let (completed, failed_symbol, reason) = match event {
+            CycleEvent::Completed(_) => (true, None, None),
+            CycleEvent::Failed { symbol, reason, .. } => (false, Some(symbol), Some(reason)),
+        };
+        self.connection.execute(
+            "INSERT INTO cycles
+                (started, ended, currency, symbols, pnl, completed, failed_symbol, reason, cycle_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                timestamp(result.started),
+                now(),
+                result.currency,
+                result.symbols.join(","),
+                result.pnl(),
+                completed,
+                failed_symbol,
+                reason,
+                result.id.map(|id| id.to_string())
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// records that events of the channel were dropped before being journaled
+    pub fn record_gap(&self, channel: &str, dropped: u64) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO gaps (time, channel, dropped) VALUES (?1, ?2, ?3)",
+            params![now(), channel, dropped],
+        )?;
+        Ok(())
+    }
+
+    /// number of events dropped before being journaled, per channel
+    pub fn gaps(&self) -> Result<Vec<(String, u64)>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT channel, SUM(dropped) FROM gaps GROUP BY channel ORDER BY channel")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// realised pnl per UTC day and currency, oldest first
+    pub fn daily_pnl(&self) -> Result<Vec<DailyPnl>> {
+        let mut statement = self.connection.prepare(
+            "SELECT substr(ended, 1, 10) AS day, currency, SUM(pnl), COUNT(*) FROM cycles
+            GROUP BY day, currency ORDER BY day, currency",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(DailyPnl {
+                day: row.get(0)?,
+                currency: row.get(1)?,
+                pnl: row.get(2)?,
+                cycles: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// true when the order was requested by this system, e.g. to recognise its open orders
+    pub fn is_journaled(&self, client_oid: &str) -> Result<bool> {
+        Ok(self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM orders WHERE client_oid = ?1)",
+            [client_oid],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// legs of the cycles left without outcome when the process stopped, oldest first.
+    /// Legs journaled without cycle id are those placed after the last cycle ended.
+    pub fn interrupted_legs(&self) -> Result<Vec<InterruptedLeg>> {
+        let mut statement = self.connection.prepare(
+            "SELECT orders.time, orders.client_oid, orders.symbol, orders.side, orders.price,
+                (SELECT COALESCE(
+                    MAX(CAST(trades.filled_size AS REAL)),
+                    SUM(CASE WHEN trades.status = 'filled' THEN CAST(trades.size AS REAL) END),
+                    0.0) FROM trades
+                WHERE trades.client_oid = orders.client_oid)
+            FROM orders
+            WHERE orders.kind = 'limit'
+                AND CASE WHEN orders.cycle_id IS NULL
+                    THEN orders.time > COALESCE((SELECT MAX(ended) FROM cycles), '')
+                    ELSE orders.cycle_id NOT IN
+                        (SELECT cycle_id FROM cycles WHERE cycle_id IS NOT NULL)
+                END
+            ORDER BY orders.id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let price: String = row.get(4)?;
+            Ok(InterruptedLeg {
+                time: row.get(0)?,
+                client_oid: row.get(1)?,
+                symbol: row.get(2)?,
+                side: row.get(3)?,
+                price: price.parse().unwrap_or_default(),
+                filled: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// ids of the last cycles journaled, most recent first
+    pub fn last_cycles(&self, count: u32) -> Result<Vec<i64>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM cycles ORDER BY id DESC LIMIT ?1")?;
+        let rows = statement.query_map([count], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// the cycle of the cycle id journaled by [`Journal::record_cycle`], with its legs
+    pub fn cycle_by_id(&self, cycle_id: CycleId) -> Result<Option<ReconstructedCycle>> {
+        let id = self
+            .connection
+            .query_row(
+                "SELECT id FROM cycles WHERE cycle_id = ?1",
+                [cycle_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match id {
+            Some(id) => self.cycle(id),
+            None => Ok(None),
+        }
+    }
+
+    /// the cycle with the orders of its legs, their acknowledgements and trades
+    pub fn cycle(&self, id: i64) -> Result<Option<ReconstructedCycle>> {
+        let cycle = self
+            .connection
+            .query_row(
+                "SELECT id, started, ended, currency, symbols, pnl, completed, failed_symbol,
+                    reason, cycle_id
+                FROM cycles WHERE id = ?1",
+                [id],
+                cycle_entry,
+            )
+            .optional()?;
+        let Some(cycle) = cycle else {
+            return Ok(None);
+        };
+
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT time, kind, client_oid, symbol, side, price, size FROM orders
+            WHERE client_oid IN ({CYCLE_ORDERS}) ORDER BY id"
+        ))?;
+        let orders = statement
+            .query_map([id], |row| {
+                Ok(OrderEntry {
+                    time: row.get(0)?,
+                    kind: row.get(1)?,
+                    client_oid: row.get(2)?,
+                    symbol: row.get(3)?,
+                    side: row.get(4)?,
+                    price: row.get(5)?,
+                    size: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT time, client_oid, accepted, exchange_oid, reason, latency_ms FROM order_results
+            WHERE client_oid IN ({CYCLE_ORDERS}) ORDER BY id"
+        ))?;
+        let order_results = statement
+            .query_map([id], |row| {
+                Ok(OrderResultEntry {
+                    time: row.get(0)?,
+                    client_oid: row.get(1)?,
+                    accepted: row.get(2)?,
+                    exchange_oid: row.get(3)?,
+                    reason: row.get(4)?,
+                    latency_ms: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT time, status, client_oid, symbol, side, size, filled_size FROM trades
+            WHERE client_oid IN ({CYCLE_ORDERS}) ORDER BY id"
+        ))?;
+        let trades = statement
+            .query_map([id], |row| {
+                Ok(TradeEntry {
+                    time: row.get(0)?,
+                    status: row.get(1)?,
+                    client_oid: row.get(2)?,
+                    symbol: row.get(3)?,
+                    side: row.get(4)?,
+                    size: row.get(5)?,
+                    filled_size: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(Some(ReconstructedCycle {
+            cycle,
+            orders,
+            order_results,
+            trades,
+        }))
+    }
+}
+
+fn cycle_entry(row: &Row) -> rusqlite::Result<CycleEntry> {
+    let symbols: String = row.get(4)?;
+    Ok(CycleEntry {
+        id: row.get(0)?,
+        started: row.get(1)?,
+        ended: row.get(2)?,
+        currency: row.get(3)?,
+        symbols: symbols.split(',').map(String::from).collect(),
+        pnl: row.get(5)?,
+        completed: row.get(6)?,
+        failed_symbol: row.get(7)?,
+        reason: row.get(8)?,
+        cycle_id: row.get(9)?,
+    })
+}
+
+/// cycle id embedded in the client order id of a leg
+fn cycle_of(client_oid: &str) -> Option<String> {
+    CycleId::from_oid(client_oid).map(|id| id.to_string())
+}
+
+/// order request of the kind with only the time set
+fn entry(kind: &str) -> OrderEntry {
+    OrderEntry {
+        time: now(),
+        kind: kind.to_string(),
+        client_oid: None,
+        symbol: None,
+        side: None,
+        price: None,
+        size: None,
+    }
+}
+
+/// fixed width, so timestamps compare as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn now() -> String {
+    timestamp(Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Journal;
+    use crate::event::cycle::{CycleEvent, CycleResult};
+    use crate::event::order::{OrderEvent, OrderResultEvent};
+    use crate::event::trade::TradeEvent;
+    use crate::model::cycle::CycleId;
+    use crate::model::order::{LimitOrder, OrderSide};
+    use crate::model::trade::TradeInfo;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn limit_order(symbol: &str) -> LimitOrder {
+        LimitOrder {
+            id: Uuid::new_v4().to_string(),
+            side: OrderSide::Buy,
+            symbol: symbol.to_string(),
+            amount: "0.1".to_string(),
+            price: "0.05".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// update of the order with filled_size executed so far, within the cycle its client order id carries
+    fn trade(order: &LimitOrder, filled_size: &str) -> TradeInfo {
+        TradeInfo {
+            client_oid: order.id.clone(),
+            cycle_id: CycleId::from_oid(&order.id),
+            symbol: order.symbol.clone(),
+            side: order.side,
+            order_type: Default::default(),
+            size: order.amount.clone(),
+            filled_size: filled_size.to_string(),
+            match_price: None,
+        }
+    }
+
+    fn fill(order: &LimitOrder) -> TradeEvent {
+        TradeEvent::TradeFilled(trade(order, &order.amount))
+    }
+
+    #[test]
+    fn test_cycle_reconstruction() {
+        let journal = Journal::open_in_memory().unwrap();
+        assert_eq!(journal.version().unwrap(), super::MIGRATIONS.len());
+
+        let started = chrono::Utc::now() - chrono::Duration::seconds(1);
+        let leg = limit_order("ETH-BTC");
+        let other = limit_order("XRP-USDT");
+        journal
+            .record_order(&OrderEvent::PlaceLimitOrder(leg.clone()))
+            .unwrap();
+        journal
+            .record_order(&OrderEvent::PlaceLimitOrder(other))
+            .unwrap();
+        journal
+            .record_order_result(&OrderResultEvent::Accepted {
+                client_oid: leg.id.clone(),
+                exchange_oid: "exchange".to_string(),
+                latency: Duration::from_millis(20),
+                attempts: 1,
+            })
+            .unwrap();
+        journal.record_trade(&fill(&leg)).unwrap();
+        let result = CycleResult {
+            started,
+            currency: "BTC".to_string(),
+            symbols: vec!["ETH-BTC".to_string(), "ETH-USDT".to_string()],
+            ..Default::default()
+        };
+        journal
+            .record_cycle(&CycleEvent::Failed {
+                result,
+                symbol: "ETH-USDT".to_string(),
+                reason: "shutdown".to_string(),
+            })
+            .unwrap();
+
+        let id = journal.last_cycles(1).unwrap()[0];
+        let cycle = journal.cycle(id).unwrap().unwrap();
+        assert!(!cycle.cycle.completed);
+        assert_eq!(cycle.cycle.failed_symbol.as_deref(), Some("ETH-USDT"));
+        // the order of another symbol is not a leg
+        assert_eq!(cycle.orders.len(), 1);
+        assert_eq!(cycle.orders[0].client_oid.as_ref(), Some(&leg.id));
+        assert_eq!(cycle.order_results[0].latency_ms, Some(20.0));
+        assert_eq!(cycle.trades[0].status, "filled");
+        assert!(journal.cycle(id + 1).unwrap().is_none());
+
+        // dropped trades are marked, not silently missing
+        journal.record_gap("trade", 3).unwrap();
+        journal.record_gap("trade", 2).unwrap();
+        assert_eq!(journal.gaps().unwrap(), vec![("trade".to_string(), 5)]);
+    }
+
+    #[test]
+    fn test_interrupted_legs() {
+        let journal = Journal::open_in_memory().unwrap();
+        let ended = limit_order("ETH-BTC");
+        journal
+            .record_order(&OrderEvent::PlaceLimitOrder(ended))
+            .unwrap();
+        journal
+            .record_cycle(&CycleEvent::Completed(CycleResult::default()))
+            .unwrap();
+        assert!(journal.interrupted_legs().unwrap().is_empty());
+
+        // placed after the last cycle ended, filled in two matches
+        std::thread::sleep(Duration::from_millis(2));
+        let leg = limit_order("ETH-BTC");
+        journal
+            .record_order(&OrderEvent::PlaceLimitOrder(leg.clone()))
+            .unwrap();
+        journal
+            .record_trade(&TradeEvent::TradeMatch(trade(&leg, "0.04")))
+            .unwrap();
+        journal.record_trade(&fill(&leg)).unwrap();
+        assert!(journal.is_journaled(&leg.id).unwrap());
+        let legs = journal.interrupted_legs().unwrap();
+        assert_eq!(legs.len(), 1);
+        assert_eq!(legs[0].client_oid, leg.id);
+        assert_eq!(legs[0].price, 0.05);
+        assert!((legs[0].filled - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_canceled_leg() {
+        let journal = Journal::open_in_memory().unwrap();
+        let cycle = CycleId::new();
+        let leg = LimitOrder {
+            id: cycle.leg_oid(0),
+            ..limit_order("ETH-BTC")
+        };
+        journal
+            .record_order(&OrderEvent::PlaceLimitOrder(leg.clone()))
+            .unwrap();
+        journal
+            .record_trade(&TradeEvent::TradeMatch(trade(&leg, "0.04")))
+            .unwrap();
+        // remainder of the immediate-or-cancel leg
+        journal
+            .record_trade(&TradeEvent::TradeCanceled(trade(&leg, "0.04")))
+            .unwrap();
+        let legs = journal.interrupted_legs().unwrap();
+        assert_eq!(legs.len(), 1);
+        assert!((legs[0].filled - 0.04).abs() < 1e-12);
+
+        let result = CycleResult {
+            id: Some(cycle),
+            currency: "BTC".to_string(),
+            symbols: vec!["ETH-BTC".to_string()],
+            ..Default::default()
+        };
+        journal
+            .record_cycle(&CycleEvent::Failed {
+                result,
+                symbol: "ETH-BTC".to_string(),
+                reason: "canceled".to_string(),
+            })
+            .unwrap();
+        // the failed cycle ends the canceled leg
+        assert!(journal.interrupted_legs().unwrap().is_empty());
+        let cycle = journal.cycle_by_id(cycle).unwrap().unwrap();
+        let canceled = cycle.trades.last().unwrap();
+        assert_eq!(canceled.status, "canceled");
+        assert_eq!(canceled.filled_size.as_deref(), Some("0.04"));
+    }
+
+    #[test]
+    fn test_cycle_ids() {
+        let journal = Journal::open_in_memory().unwrap();
+        let interrupted = CycleId::new();
+        let completed = CycleId::new();
+        for (cycle, symbol) in [(interrupted, "ETH-BTC"), (completed, "ETH-BTC")] {
+            let leg = LimitOrder {
+                id: cycle.leg_oid(0),
+                ..limit_order(symbol)
+            };
+            journal
+                .record_order(&OrderEvent::PlaceLimitOrder(leg.clone()))
+                .unwrap();
+            journal.record_trade(&fill(&leg)).unwrap();
+        }
+        let result = CycleResult {
+            id: Some(completed),
+            currency: "BTC".to_string(),
+            symbols: vec!["ETH-BTC".to_string()],
+            ..Default::default()
+        };
+        journal
+            .record_cycle(&CycleEvent::Completed(result))
+            .unwrap();
+
+        // legs of the same symbol in flight together are told apart by cycle id
+        let cycle = journal.cycle_by_id(completed).unwrap().unwrap();
+        assert_eq!(cycle.cycle.cycle_id, Some(completed.to_string()));
+        assert_eq!(cycle.orders.len(), 1);
+        assert_eq!(cycle.orders[0].client_oid, Some(completed.leg_oid(0)));
+        assert_eq!(cycle.trades.len(), 1);
+        assert!(journal.cycle_by_id(interrupted).unwrap().is_none());
+
+        // placed before the last cycle ended, still without outcome
+        let legs = journal.interrupted_legs().unwrap();
+        assert_eq!(legs.len(), 1);
+        assert_eq!(legs[0].client_oid, interrupted.leg_oid(0));
+    }
+
+    #[test]
+    fn test_daily_pnl() {
+        let journal = Journal::open_in_memory().unwrap();
+        let result = CycleResult {
+            currency: "USDT".to_string(),
+            symbols: vec!["BTC-USDT".to_string()],
+            flows: BTreeMap::from([("USDT".to_string(), 0.5)]),
+            ..Default::default()
+        };
+        journal
+            .record_cycle(&CycleEvent::Completed(result.clone()))
+            .unwrap();
+        journal
+            .record_cycle(&CycleEvent::Completed(result))
+            .unwrap();
+        let daily = journal.daily_pnl().unwrap();
+        assert_eq!(daily[0].pnl, 1.0);
+        assert_eq!(daily[0].cycles, 2);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::error::{Error, Result};
+use crate::event::chance::ChanceEvent;
+use crate::event::cycle::CycleEvent;
+use crate::event::order::{OrderEvent, OrderResultEvent};
+use crate::event::trade::TradeEvent;
+use crate::journal::store::Journal;
+use crate::monitor::channel::Subscriber;
+use crate::shutdown::ShutdownToken;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// Row to write into the journal
+#[derive(Debug)]
+enum Entry {
+    Chance(ChanceEvent),
+    Order(OrderEvent),
+    OrderResult(OrderResultEvent),
+    Trade(TradeEvent),
+    Cycle(CycleEvent),
+    /// events of the channel dropped by the lagging subscription
+    Gap(&'static str, u64),
+}
+
+impl Entry {
+    fn write(&self, journal: &Journal) -> Result<()> {
+        match self {
+            Entry::Chance(event) => journal.record_chance(event),
+            Entry::Order(event) => journal.record_order(event),
+            Entry::OrderResult(event) => journal.record_order_result(event),
+            Entry::Trade(event) => journal.record_trade(event),
+            Entry::Cycle(event) => journal.record_cycle(event),
+            Entry::Gap(channel, dropped) => journal.record_gap(channel, *dropped),
+        }
+    }
+}
+
+/// Receives the next event of the subscription, queueing a gap first when some were dropped
+async fn forward<T: Clone>(
+    subscriber: &mut Subscriber<T>,
+    channel: &'static str,
+    entry: fn(T) -> Entry,
+    tx_entry: &UnboundedSender<Entry>,
+) -> Result<()> {
+    let event = subscriber.recv().await?;
+    let dropped = subscriber.take_lagged();
+    if dropped > 0 {
+        tracing::error!("Journal lagged, {dropped} [{channel}] events not journaled");
+        tx_entry.send(Entry::Gap(channel, dropped)).ok();
+    }
+    tx_entry
+        .send(entry(event))
+        .map_err(|_| Error::ChannelClosed)
+}
+
+/// Journals every chance, order request, acknowledgement, trade and cycle outcome.
+/// The writes run on a blocking thread fed by an unbounded queue, so the subscriptions keep up
+/// with the trading tasks. Events dropped by a lagging subscription are journaled as a gap.
+/// A failed write is logged and skipped, never stopping the trading tasks.
+pub async fn task_journal_events(
+    journal: Journal,
+    rx_chance: Receiver<ChanceEvent>,
+    rx_order: Receiver<OrderEvent>,
+    rx_order_result: Receiver<OrderResultEvent>,
+    rx_trade: Receiver<TradeEvent>,
+    rx_cycle: Receiver<CycleEvent>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    let mut rx_chance = Subscriber::new(rx_chance, "chance", "journal");
+    let mut rx_order = Subscriber::new(rx_order, "order", "journal");
+    let mut rx_order_result = Subscriber::new(rx_order_result, "order_result", "journal");
+    let mut rx_trade = Subscriber::new(rx_trade, "trade", "journal");
+    let mut rx_cycle = Subscriber::new(rx_cycle, "cycle", "journal");
+    let (tx_entry, mut rx_entry) = unbounded_channel::<Entry>();
+    let writer = tokio::task::spawn_blocking(move || {
+        while let Some(entry) = rx_entry.blocking_recv() {
+            if let Err(e) = entry.write(&journal) {
+                tracing::error!("Failed journaling event: {e}");
+            }
+        }
+    });
+    let res = loop {
+        let forwarded = tokio::select! {
+            _ = shutdown.cancelled() => break Ok(()),
+            res = forward(&mut rx_chance, "chance", Entry::Chance, &tx_entry) => res,
+            res = forward(&mut rx_order, "order", Entry::Order, &tx_entry) => res,
+            res = forward(&mut rx_order_result, "order_result", Entry::OrderResult, &tx_entry) => res,
+            res = forward(&mut rx_trade, "trade", Entry::Trade, &tx_entry) => res,
+            res = forward(&mut rx_cycle, "cycle", Entry::Cycle, &tx_entry) => res,
+        };
+        if let Err(e) = forwarded {
+            break Err(e);
+        }
+    };
+    // the queued entries are written before returning
+    drop(tx_entry);
+    writer
+        .await
+        .map_err(|e| Error::Journal(format!("journal writer panicked: {e}")))?;
+    res
+}
@@ -6,12 +6,16 @@ pub mod config;
 pub mod error;
 /// Event (enums)
 pub mod event;
+/// Trade journal persisted in SQLite
+pub mod journal;
 /// Logger intialization
 pub mod logger;
 /// API independent model struct for both system and multi-exchange support
 pub mod model;
 /// MPS counter and globally-mapped string-timers for system monitoring
 pub mod monitor;
+/// Phased graceful shutdown
+pub mod shutdown;
 /// Arbitrage strategy algorithms
 pub mod strategy;
 /// String functions
@@ -1,11 +1,14 @@
 use crate::config::LogConfig;
+use crate::error::Result;
+use crate::monitor::status;
 use core::fmt::Result as FmtResult;
-use eyre::Result;
 use std::path::Path;
-use tracing::{Event, Subscriber};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
 use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
 use tracing_subscriber::fmt;
 use tracing_subscriber::fmt::{format, FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::Context;
 use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::Registry;
@@ -45,6 +48,30 @@ where
         Ok(())
     }
 }
+// records the error events on the status board
+struct StatusLayer;
+impl<S: Subscriber> Layer<S> for StatusLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() != Level::ERROR {
+            return;
+        }
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        status::global().record_error(metadata.target(), &message.0);
+    }
+}
+
+// message field of an event
+struct MessageVisitor(String);
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
 /// daily rolling log file
 pub fn non_blocking_make_writer_file(
     directory: impl AsRef<Path>,
@@ -71,7 +98,10 @@ pub fn setup_logs(config: &LogConfig) -> Result<WorkerGuard> {
         .with_filter(env_filter_level(config.term_log_level));
 
     // Combine layers into a single subscriber and set global default
-    let subscriber = Registry::default().with(stdout_layer).with(file_layer);
+    let subscriber = Registry::default()
+        .with(stdout_layer)
+        .with(file_layer)
+        .with(StatusLayer);
     tracing::subscriber::set_global_default(subscriber)
         .expect("Failed to set global default subscriber");
 
@@ -49,10 +49,12 @@ pub type ThreeActions = [ActionInfo; 3];
 /// Structure of triangular arbitrage chances
 /// profit: USD profit in OrderedFloat for ease of comparison
 /// actions: 3 sequence of Actions
+/// receive_time: local wall time the orderbook update raising the chance was received in unix microseconds, 0 if unknown
 #[derive(Debug, Clone, Default, Eq)]
 pub struct TriangularArbitrageChance {
     pub profit: OrderedFloat<f64>,
     pub actions: ThreeActions,
+    pub receive_time: u64,
 }
 
 /// Orders chances with its profit
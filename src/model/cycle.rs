@@ -0,0 +1,74 @@
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Identifier of a cyclic arbitrage, embedded in the client order ids of its legs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CycleId(u128);
+
+impl Default for CycleId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CycleId {
+    pub fn new() -> Self {
+        CycleId(Uuid::new_v4().as_u128())
+    }
+
+    /// client order id of the leg (0, 1, 2) of the cycle
+    pub fn leg_oid(&self, leg: usize) -> String {
+        format!("{self}-{leg}")
+    }
+
+    /// cycle of a leg's client order id, None for orders placed outside cycles
+    pub fn from_oid(client_oid: &str) -> Option<Self> {
+        let (cycle, leg) = client_oid.split_once('-')?;
+        if leg.is_empty() || !leg.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        cycle.parse().ok()
+    }
+}
+
+/// 32 lowercase hex digits
+impl fmt::Display for CycleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl FromStr for CycleId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 32 {
+            return Err(Error::Parse(format!("invalid cycle id: {s}")));
+        }
+        u128::from_str_radix(s, 16)
+            .map(CycleId)
+            .map_err(|_| Error::Parse(format!("invalid cycle id: {s}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CycleId;
+
+    #[test]
+    fn test_leg_oid() {
+        let cycle = CycleId::new();
+        let oid = cycle.leg_oid(2);
+        // within KuCoin's 40 characters of clientOid
+        assert_eq!(oid.len(), 34);
+        assert_eq!(CycleId::from_oid(&oid), Some(cycle));
+        assert_eq!(cycle.to_string().parse::<CycleId>().unwrap(), cycle);
+        // orders outside cycles keep a plain UUID
+        assert_eq!(
+            CycleId::from_oid("67e55044-10b1-426f-9247-bb680e5fe0c8"),
+            None
+        );
+    }
+}
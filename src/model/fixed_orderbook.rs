@@ -0,0 +1,284 @@
+use crate::model::orderbook::{
+    delta_max, delta_min, is_better_ask, is_better_bid, Orderbook, OrderbookBackend,
+};
+
+/// Sorted price levels of one side, best level at index 0.
+/// Prices are kept as integer ticks in a fixed array, so updates only shift memory in place.
+#[derive(Clone, Copy, PartialEq)]
+struct Levels<const N: usize> {
+    // ticks in ascending order (negated for bid so that the best level comes first)
+    keys: [i64; N],
+    volumes: [f64; N],
+    len: usize,
+}
+
+impl<const N: usize> Levels<N> {
+    fn new() -> Self {
+        Levels {
+            keys: [0; N],
+            volumes: [0.0; N],
+            len: 0,
+        }
+    }
+
+    fn first(&self) -> Option<(i64, f64)> {
+        (self.len > 0).then(|| (self.keys[0], self.volumes[0]))
+    }
+
+    /// updates a level, zero volume removes it, levels beyond depth N are dropped
+    fn update(&mut self, key: i64, volume: f64) {
+        let len = self.len;
+        match self.keys[..len].binary_search(&key) {
+            Ok(i) if volume == 0.0 => {
+                self.keys.copy_within(i + 1..len, i);
+                self.volumes.copy_within(i + 1..len, i);
+                self.len -= 1;
+            }
+            Ok(i) => self.volumes[i] = volume,
+            Err(i) if volume == 0.0 || i >= N => {}
+            Err(i) => {
+                // drop the worst level when full
+                let end = len.min(N - 1);
+                self.keys.copy_within(i..end, i + 1);
+                self.volumes.copy_within(i..end, i + 1);
+                self.keys[i] = key;
+                self.volumes[i] = volume;
+                self.len = end + 1;
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        self.keys[..self.len]
+            .iter()
+            .copied()
+            .zip(self.volumes[..self.len].iter().copied())
+    }
+}
+
+/// Orderbook of at most N levels per side as sorted integer price ticks, deeper levels are discarded
+#[derive(Clone, Copy, PartialEq)]
+pub struct FixedDepthOrderbook<const N: usize> {
+    tick_size: f64,
+    // tick_size as an integer number of 1/scale, e.g. 5 and 1e5 for 0.00005, so that
+    // prices are computed as integer / power of ten and come out exactly as written
+    tick_units: f64,
+    scale: f64,
+    sequence: u64,
+    /// exchange timestamp of the last update in unix milliseconds, 0 if unknown
+    pub exchange_time: u64,
+    /// local wall time of the last update in unix milliseconds
+    pub update_time: u64,
+    /// local wall time the last update was received in unix microseconds
+    pub receive_time: u64,
+    ask: Levels<N>,
+    bid: Levels<N>,
+}
+
+impl<const N: usize> std::fmt::Debug for FixedDepthOrderbook<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixedDepthOrderbook")
+            .field("sequence", &self.sequence)
+            .field("update_time", &self.update_time)
+            .field("ask", &self.asks().collect::<Vec<_>>())
+            .field("bid", &self.bids().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<const N: usize> FixedDepthOrderbook<N> {
+    /// empty orderbook, tick_size being the price increment of the symbol
+    pub fn new(tick_size: f64) -> Result<Self, String> {
+        if !(tick_size.is_finite() && tick_size > 0.0) {
+            return Err(format!("invalid price tick {tick_size}"));
+        }
+        // decimal places of the tick, at most 12 as KuCoin increments are far coarser
+        let mut scale = 1.0;
+        while scale < 1e12 && ((tick_size * scale).round() - tick_size * scale).abs() > 1e-6 {
+            scale *= 10.0;
+        }
+        Ok(FixedDepthOrderbook {
+            tick_size,
+            tick_units: (tick_size * scale).round(),
+            scale,
+            sequence: 0,
+            exchange_time: 0,
+            update_time: 0,
+            receive_time: 0,
+            ask: Levels::new(),
+            bid: Levels::new(),
+        })
+    }
+
+    /// converts the BTreeMap based orderbook (e.g. REST snapshot), keeping the best N levels
+    pub fn from_orderbook(orderbook: &Orderbook, tick_size: f64) -> Result<Self, String> {
+        let mut book = Self::new(tick_size)?;
+        book.sequence = orderbook.sequence;
+        book.exchange_time = orderbook.exchange_time;
+        book.update_time = orderbook.update_time;
+        book.receive_time = orderbook.receive_time;
+        for (price, volume) in orderbook.ask.iter().take(N) {
+            book.ask.update(book.to_tick(price.0), volume.0);
+        }
+        for (price, volume) in orderbook.bid.iter().rev().take(N) {
+            book.bid.update(-book.to_tick(price.0), volume.0);
+        }
+        Ok(book)
+    }
+
+    pub fn tick_size(&self) -> f64 {
+        self.tick_size
+    }
+
+    /// rounds the price to the nearest integer tick
+    pub fn to_tick(&self, price: f64) -> i64 {
+        (price / self.tick_size).round() as i64
+    }
+
+    pub fn to_price(&self, tick: i64) -> f64 {
+        tick as f64 * self.tick_units / self.scale
+    }
+}
+
+impl<const N: usize> OrderbookBackend for FixedDepthOrderbook<N> {
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn update_time(&self) -> u64 {
+        self.update_time
+    }
+
+    fn best_ask(&self) -> Option<(f64, f64)> {
+        self.ask
+            .first()
+            .map(|(tick, volume)| (self.to_price(tick), volume))
+    }
+
+    fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bid
+            .first()
+            .map(|(tick, volume)| (self.to_price(-tick), volume))
+    }
+
+    fn apply(
+        &mut self,
+        sequence: u64,
+        asks: &[(f64, f64)],
+        bids: &[(f64, f64)],
+    ) -> Result<bool, String> {
+        if self.sequence > sequence {
+            return Err(format!("[{}] -> [{}]", sequence, self.sequence));
+        }
+        let (best_ask, best_bid) = (self.best_ask(), self.best_bid());
+        self.sequence = sequence;
+        for &(price, volume) in asks {
+            self.ask.update(self.to_tick(price), volume);
+        }
+        for &(price, volume) in bids {
+            self.bid.update(-self.to_tick(price), volume);
+        }
+        Ok(is_better_ask(delta_min(asks), best_ask) || is_better_bid(delta_max(bids), best_bid))
+    }
+    fn asks(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.ask
+            .iter()
+            .map(|(tick, volume)| (self.to_price(tick), volume))
+    }
+
+    fn bids(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.bid
+            .iter()
+            .map(|(tick, volume)| (self.to_price(-tick), volume))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedDepthOrderbook;
+    use crate::model::orderbook::{Orderbook, OrderbookBackend};
+
+    /// (sequence, asks, bids)
+    type Change<'a> = (u64, &'a [(f64, f64)], &'a [(f64, f64)]);
+
+    #[test]
+    fn test_apply_as_btreemap() {
+        // tick, asks from the best to the worst, bids from the best to the worst
+        let books: [(f64, [&str; 3], [&str; 3]); 4] = [
+            (0.01, ["1.01", "1.02", "1.03"], ["1.00", "0.99", "0.98"]),
+            (0.25, ["10.5", "10.75", "11"], ["10.25", "10", "9.75"]),
+            (
+                0.00005,
+                ["0.00125", "0.0013", "0.00135"],
+                ["0.0012", "0.00115", "0.0011"],
+            ),
+            (1.0, ["101", "102", "103"], ["100", "99", "98"]),
+        ];
+        for (tick, asks, bids) in books {
+            let ask: Vec<f64> = asks.iter().map(|p| p.parse().unwrap()).collect();
+            let bid: Vec<f64> = bids.iter().map(|p| p.parse().unwrap()).collect();
+            let mut btreemap = Orderbook::new();
+            btreemap
+                .apply(
+                    1,
+                    &[(ask[1], 1.0), (ask[2], 2.0)],
+                    &[(bid[1], 3.0), (bid[2], 4.0)],
+                )
+                .unwrap();
+            let mut fixed = FixedDepthOrderbook::<64>::from_orderbook(&btreemap, tick).unwrap();
+            let changes: [Change; 5] = [
+                // less volume at the best ask, removing a missing level
+                (2, &[(ask[0], 0.0), (ask[1], 0.5)], &[]),
+                // new best bid and ask
+                (3, &[(ask[0], 1.0)], &[(bid[0], 1.0)]),
+                // removing the best levels
+                (4, &[(ask[0], 0.0)], &[(bid[0], 0.0)]),
+                // same price, more volume
+                (5, &[], &[(bid[1], 5.0)]),
+                // emptying the asks
+                (6, &[(ask[1], 0.0), (ask[2], 0.0)], &[]),
+            ];
+            for (sequence, asks, bids) in changes {
+                let improved = fixed.apply(sequence, asks, bids).unwrap();
+                assert_eq!(btreemap.apply(sequence, asks, bids).unwrap(), improved);
+                assert_eq!(fixed.best_ask(), btreemap.best_ask(), "tick {tick}");
+                assert_eq!(fixed.best_bid(), btreemap.best_bid(), "tick {tick}");
+            }
+            assert!(fixed.apply(5, &[], &[]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_invalid_tick_rejected() {
+        for tick in [0.0, -0.01, f64::NAN] {
+            assert!(FixedDepthOrderbook::<2>::new(tick).is_err());
+        }
+    }
+
+    #[test]
+    fn test_depth_and_ticks() {
+        let mut book = FixedDepthOrderbook::<2>::new(0.01).unwrap();
+        // prices are restored as written with ticks that are not a fraction of 1
+        assert_eq!(
+            FixedDepthOrderbook::<2>::new(0.25).unwrap().to_price(41),
+            10.25
+        );
+        assert_eq!(
+            FixedDepthOrderbook::<2>::new(0.00005)
+                .unwrap()
+                .to_tick(0.00115),
+            23
+        );
+        assert_eq!(
+            book.apply(1, &[(10.02, 1.0), (10.01, 2.0)], &[(10.0, 3.0)]),
+            Ok(true)
+        );
+        assert_eq!(book.best_ask(), Some((10.01, 2.0)));
+        // deeper than 2 levels, discarded
+        assert_eq!(book.apply(2, &[(10.03, 1.0)], &[]), Ok(false));
+        assert_eq!(book.asks().count(), 2);
+        // removing the best ask
+        assert_eq!(book.apply(3, &[(10.01, 0.0)], &[]), Ok(false));
+        assert_eq!(book.best_ask(), Some((10.02, 1.0)));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::model::chance::ActionInfo;
+use crate::model::order::OrderSide;
+use crate::strings::symbol_to_tuple;
+use std::collections::{BTreeMap, HashMap};
+
+pub type Balances = HashMap<String, f64>; // currency to available amount
+
+/// Currency and amount an action spends, the base for sell and the quote for buy
+pub fn spent(action: &ActionInfo) -> Option<(String, f64)> {
+    let (base, quote) = symbol_to_tuple(&action.ticker)?;
+    match action.action {
+        OrderSide::Buy => Some((quote.to_string(), action.price.0 * action.volume.0)),
+        OrderSide::Sell => Some((base.to_string(), action.volume.0)),
+    }
+}
+
+/// Adds the amounts moved by the filled base size of an action, valued at its price
+pub fn add_fill(flows: &mut BTreeMap<String, f64>, action: &ActionInfo, filled: f64) {
+    let Some((base, quote)) = symbol_to_tuple(&action.ticker) else {
+        return;
+    };
+    let (base_flow, quote_flow) = match action.action {
+        OrderSide::Buy => (filled, -filled * action.price.0),
+        OrderSide::Sell => (-filled, filled * action.price.0),
+    };
+    *flows.entry(base.to_string()).or_default() += base_flow;
+    *flows.entry(quote.to_string()).or_default() += quote_flow;
+}
+
+/// Deducts the fee of the filled base size of an action, charged in the quote currency
+pub fn add_fee(flows: &mut BTreeMap<String, f64>, action: &ActionInfo, filled: f64, fee_rate: f64) {
+    if let Some((_, quote)) = symbol_to_tuple(&action.ticker) {
+        *flows.entry(quote.to_string()).or_default() -= filled * action.price.0 * fee_rate;
+    }
+}
+
+/// Value of a unit of each currency the actions acquire in the currency they start with
+pub fn costs(currency: &str, actions: &[ActionInfo]) -> BTreeMap<String, f64> {
+    let mut costs = BTreeMap::from([(currency.to_string(), 1.0)]);
+    for action in actions {
+        let Some((base, quote)) = symbol_to_tuple(&action.ticker) else {
+            continue;
+        };
+        let (spent, acquired, rate) = match action.action {
+            OrderSide::Buy => (quote, base, action.price.0),
+            OrderSide::Sell => (base, quote, 1.0 / action.price.0),
+        };
+        if let Some(cost) = costs.get(spent).copied().filter(|_| rate.is_finite()) {
+            costs.entry(acquired.to_string()).or_insert(cost * rate);
+        }
+    }
+    costs
+}
+
+/// True when the balances cover every action run at the same time
+pub fn covers(balances: &Balances, actions: &[ActionInfo]) -> bool {
+    let mut required: HashMap<String, f64> = HashMap::new();
+    for action in actions {
+        match spent(action) {
+            Some((currency, amount)) => *required.entry(currency).or_default() += amount,
+            None => return false,
+        }
+    }
+    required
+        .iter()
+        .all(|(currency, amount)| balances.get(currency).is_some_and(|held| held >= amount))
+}
+
+/// Trade restoring the target of a currency against the rebalance quote
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rebalance {
+    pub currency: String,
+    pub side: OrderSide,
+    pub amount: f64,
+}
+
+/// Relative drift of the held amount from its target
+/// ```
+/// use kucoin_arbitrage::model::inventory::drift;
+/// assert_eq!(drift(1.5, 1.0), 0.5);
+/// assert_eq!(drift(0.5, 1.0), -0.5);
+/// ```
+pub fn drift(held: f64, target: f64) -> f64 {
+    if target > 0.0 {
+        (held - target) / target
+    } else {
+        0.0
+    }
+}
+
+/// Trades restoring every target that drifted beyond the threshold, the quote is left to absorb them
+pub fn rebalances(
+    balances: &Balances,
+    targets: &BTreeMap<String, f64>,
+    quote: &str,
+    threshold: f64,
+) -> Vec<Rebalance> {
+    targets
+        .iter()
+        .filter(|(currency, _)| currency.as_str() != quote)
+        .filter_map(|(currency, target)| {
+            let held = balances.get(currency).copied().unwrap_or_default();
+            if drift(held, *target).abs() <= threshold {
+                return None;
+            }
+            let side = if held < *target {
+                OrderSide::Buy
+            } else {
+                OrderSide::Sell
+            };
+            Some(Rebalance {
+                currency: currency.clone(),
+                side,
+                amount: (held - target).abs(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_fill, rebalances, spent, Balances, Rebalance};
+    use crate::model::chance::ActionInfo;
+    use crate::model::order::OrderSide;
+    use ordered_float::OrderedFloat;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_spent() {
+        let buy = ActionInfo::buy("ETH-BTC".to_string(), OrderedFloat(0.05), OrderedFloat(2.0));
+        assert_eq!(spent(&buy), Some(("BTC".to_string(), 0.1)));
+        let sell = ActionInfo::sell(
+            "ETH-USDT".to_string(),
+            OrderedFloat(1800.0),
+            OrderedFloat(2.0),
+        );
+        assert_eq!(spent(&sell), Some(("ETH".to_string(), 2.0)));
+    }
+
+    #[test]
+    fn test_add_fill() {
+        let mut flows = BTreeMap::new();
+        let buy = ActionInfo::buy("ETH-BTC".to_string(), OrderedFloat(0.05), OrderedFloat(2.0));
+        add_fill(&mut flows, &buy, 1.0);
+        assert_eq!(flows.get("ETH"), Some(&1.0));
+        assert_eq!(flows.get("BTC"), Some(&-0.05));
+    }
+
+    #[test]
+    fn test_rebalances() {
+        let targets = BTreeMap::from([
+            ("BTC".to_string(), 1.0),
+            ("ETH".to_string(), 10.0),
+            ("USDT".to_string(), 100.0),
+        ]);
+        let balances = Balances::from([
+            ("BTC".to_string(), 0.5),
+            ("ETH".to_string(), 11.0),
+            ("USDT".to_string(), 10.0),
+        ]);
+        let trades = rebalances(&balances, &targets, "USDT", 0.2);
+        assert_eq!(
+            trades,
+            vec![Rebalance {
+                currency: "BTC".to_string(),
+                side: OrderSide::Buy,
+                amount: 0.5
+            }]
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+/// Request to borrow a currency on margin, the rate and term limits are set in config
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct BorrowOrder {
+    pub id: String,
+    pub currency: String,
+    /// size the margin account must hold, only the shortfall of its available balance is borrowed
+    pub size: String,
+}
+
+/// Outstanding loan of a margin borrow
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Loan {
+    pub trade_id: String,
+    pub currency: String,
+    pub principal: f64,
+    pub accrued_interest: f64,
+    /// principal and interest left to repay
+    pub liability: f64,
+    pub daily_rate: f64,
+}
+
+pub type Loans = HashMap<String, Loan>; // trade_id to loan
+
+/// Liability of a currency left to repay
+pub fn outstanding(loans: &Loans, currency: &str) -> f64 {
+    loans
+        .values()
+        .filter(|loan| loan.currency == currency)
+        .map(|loan| loan.liability)
+        .sum()
+}
+
+/// Interest accrued over every loan, in each currency
+pub fn accrued_interest(loans: &Loans) -> HashMap<String, f64> {
+    let mut interest: HashMap<String, f64> = HashMap::new();
+    for loan in loans.values() {
+        *interest.entry(loan.currency.clone()).or_default() += loan.accrued_interest;
+    }
+    interest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{outstanding, Loan, Loans};
+
+    #[test]
+    fn test_outstanding() {
+        let loan = Loan {
+            trade_id: "1".to_string(),
+            currency: "USDT".to_string(),
+            liability: 10.5,
+            ..Default::default()
+        };
+        let loans = Loans::from([(loan.trade_id.clone(), loan)]);
+        assert_eq!(outstanding(&loans, "USDT"), 10.5);
+        assert_eq!(outstanding(&loans, "BTC"), 0.0);
+    }
+}
@@ -1,5 +1,13 @@
 /// Arbitrage chance
 pub mod chance;
+/// Cycle identifier
+pub mod cycle;
+/// Fixed-depth orderbook with integer price ticks
+pub mod fixed_orderbook;
+/// Account inventory
+pub mod inventory;
+/// Margin borrowing
+pub mod margin;
 /// Order
 pub mod order;
 /// Orderbook
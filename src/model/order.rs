@@ -1,3 +1,5 @@
+use crate::error::Error;
+use std::collections::HashMap;
 use std::str::FromStr;
 
 /// Order placement selector, default set as "Sell" for security
@@ -22,9 +24,9 @@ impl AsRef<str> for OrderSide {
 /// let buy = OrderSide::Buy;
 /// assert_eq!(buy.to_string(), "buy");
 /// ```
-impl ToString for OrderSide {
-    fn to_string(&self) -> String {
-        self.as_ref().to_string()
+impl std::fmt::Display for OrderSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
     }
 }
 
@@ -34,13 +36,13 @@ impl ToString for OrderSide {
 /// assert_eq!(buy.to_string(), "buy");
 /// ```
 impl FromStr for OrderSide {
-    type Err = eyre::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "buy" => Ok(OrderSide::Buy),
             "sell" => Ok(OrderSide::Sell),
-            unknown => eyre::bail!("unknown side: {unknown}"),
+            unknown => Err(Error::Parse(format!("unknown side: {unknown}"))),
         }
     }
 }
@@ -53,26 +55,76 @@ pub enum OrderType {
     Market,
 }
 
-impl ToString for OrderType {
-    fn to_string(&self) -> String {
-        match self {
+impl std::fmt::Display for OrderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
             OrderType::Limit => "limit",
             OrderType::Market => "market",
-        }
-        .to_string()
+        })
     }
 }
 impl FromStr for OrderType {
-    type Err = eyre::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "limit" => Ok(OrderType::Limit),
             "market" => Ok(OrderType::Market),
-            unknown => eyre::bail!("unknown ordertype {unknown}"),
+            unknown => Err(Error::Parse(format!("unknown ordertype {unknown}"))),
+        }
+    }
+}
+/// How long a limit order stays on the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum TimeInForce {
+    /// good till canceled
+    #[default]
+    GTC,
+    /// good till time, canceled after cancel_after seconds
+    GTT,
+    /// immediate or cancel, the unfilled part is canceled and never rests on the book
+    IOC,
+    /// fill or kill, either filled entirely or canceled
+    FOK,
+}
+
+impl AsRef<str> for TimeInForce {
+    fn as_ref(&self) -> &str {
+        match self {
+            TimeInForce::GTC => "GTC",
+            TimeInForce::GTT => "GTT",
+            TimeInForce::IOC => "IOC",
+            TimeInForce::FOK => "FOK",
+        }
+    }
+}
+
+impl std::fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// ```
+/// use kucoin_arbitrage::model::order::TimeInForce;
+/// let tif: TimeInForce = "IOC".parse().unwrap();
+/// assert_eq!(tif, TimeInForce::IOC);
+/// assert_eq!(tif.to_string(), "IOC");
+/// ```
+impl FromStr for TimeInForce {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GTC" => Ok(TimeInForce::GTC),
+            "GTT" => Ok(TimeInForce::GTT),
+            "IOC" => Ok(TimeInForce::IOC),
+            "FOK" => Ok(TimeInForce::FOK),
+            unknown => Err(Error::Parse(format!("unknown time in force: {unknown}"))),
         }
     }
 }
+
 pub trait Order {
     fn id(&self) -> String;
     fn side(&self) -> OrderSide;
@@ -82,13 +134,51 @@ pub trait Order {
 }
 
 // Market Order and Limit order should have predefined OrderType
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct MarketOrder {
     id: String,
     order_type: OrderType,
     side: OrderSide,
     symbol: String,
     amount: String,
+    // amount in quote currency (funds) instead of base currency (size)
+    is_funds: bool,
+}
+
+/// ```
+/// use kucoin_arbitrage::model::order::{MarketOrder, Order, OrderSide};
+/// let order = MarketOrder::with_funds("id", OrderSide::Buy, "BTC-USDT", "20");
+/// assert_eq!(order.funds(), Some("20".to_string()));
+/// assert_eq!(order.size(), None);
+/// ```
+impl MarketOrder {
+    /// market order of the size in base currency
+    pub fn with_size(id: &str, side: OrderSide, symbol: &str, size: &str) -> Self {
+        MarketOrder {
+            id: id.to_string(),
+            order_type: OrderType::Market,
+            side,
+            symbol: symbol.to_string(),
+            amount: size.to_string(),
+            is_funds: false,
+        }
+    }
+
+    /// market order of the funds in quote currency
+    pub fn with_funds(id: &str, side: OrderSide, symbol: &str, funds: &str) -> Self {
+        MarketOrder {
+            is_funds: true,
+            ..MarketOrder::with_size(id, side, symbol, funds)
+        }
+    }
+
+    pub fn size(&self) -> Option<String> {
+        (!self.is_funds).then(|| self.amount.clone())
+    }
+
+    pub fn funds(&self) -> Option<String> {
+        self.is_funds.then(|| self.amount.clone())
+    }
 }
 
 impl Order for MarketOrder {
@@ -109,7 +199,7 @@ impl Order for MarketOrder {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct LimitOrder {
     pub id: String,
     pub order_type: OrderType,
@@ -117,6 +207,34 @@ pub struct LimitOrder {
     pub symbol: String,
     pub amount: String,
     pub price: String,
+    pub time_in_force: TimeInForce,
+    /// rejected instead of taking liquidity
+    pub post_only: bool,
+    /// not shown on the orderbook
+    pub hidden: bool,
+    /// only this size is shown on the orderbook
+    pub iceberg: Option<String>,
+    /// seconds until canceled, GTT only
+    pub cancel_after: Option<u64>,
+    /// traded in the margin account, e.g. with borrowed funds
+    pub margin: bool,
+}
+
+impl LimitOrder {
+    /// checks the combination of the options before sending to the exchange
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| Err(Error::InvalidOrder(format!("{reason} [{}]", self.id)));
+        if self.cancel_after.is_some() && self.time_in_force != TimeInForce::GTT {
+            return invalid("cancel_after requires GTT");
+        }
+        if self.post_only && matches!(self.time_in_force, TimeInForce::IOC | TimeInForce::FOK) {
+            return invalid("post_only cannot be IOC/FOK");
+        }
+        if self.hidden && self.iceberg.is_some() {
+            return invalid("hidden and iceberg are exclusive");
+        }
+        Ok(())
+    }
 }
 
 impl Order for LimitOrder {
@@ -142,3 +260,24 @@ impl LimitOrder {
         self.price.clone()
     }
 }
+
+pub type OpenOrders = HashMap<String, String>; // client_oid to symbol, placed by this process
+
+#[cfg(test)]
+mod tests {
+    use super::{LimitOrder, TimeInForce};
+
+    #[test]
+    fn test_validate_time_in_force() {
+        let order = LimitOrder {
+            time_in_force: TimeInForce::IOC,
+            ..Default::default()
+        };
+        assert!(order.validate().is_ok());
+        let order = LimitOrder {
+            post_only: true,
+            ..order
+        };
+        assert!(order.validate().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::model::fixed_orderbook::FixedDepthOrderbook;
 use ordered_float::OrderedFloat;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
@@ -23,21 +24,176 @@ impl<'a> std::fmt::Debug for PVMapDebug<'a> {
 pub struct Orderbook {
     pub ask: PVMap,
     pub bid: PVMap,
+    /// exchange sequence of the last update, 0 for depth snapshots which carry none
     pub sequence: u64,
+    /// exchange timestamp of the last update in unix milliseconds, 0 if unknown
+    pub exchange_time: u64,
+    /// local wall time of the last update in unix milliseconds
+    pub update_time: u64,
+    /// local wall time the last update was received in unix microseconds, for latency tracing
+    pub receive_time: u64,
 }
 
 impl std::fmt::Debug for Orderbook {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Orderbook")
             .field("sequence", &self.sequence)
+            .field("exchange_time", &self.exchange_time)
+            .field("update_time", &self.update_time)
+            .field("receive_time", &self.receive_time)
             .field("ask", &PVMapDebug(&self.ask))
             .field("bid", &PVMapDebug(&self.bid))
             .finish()
     }
 }
 
+/// level2 change of an orderbook, levels as (price, volume) with zero volume removing the level
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderbookChange {
+    pub asks: Vec<(f64, f64)>,
+    pub bids: Vec<(f64, f64)>,
+    /// exchange sequence of the first change, following the sequence of the orderbook it applies to
+    pub sequence_start: u64,
+    /// exchange sequence of the last change
+    pub sequence: u64,
+    /// exchange timestamp in unix milliseconds, 0 if unknown
+    pub exchange_time: u64,
+    /// local wall time the change was received in unix milliseconds
+    pub update_time: u64,
+    /// local wall time the change was received in unix microseconds, for latency tracing
+    pub receive_time: u64,
+}
+
+/// Merge and best-price semantics shared by the orderbook backends
+pub trait OrderbookBackend {
+    /// sequence of the last merged delta
+    fn sequence(&self) -> u64;
+    /// local wall time of the last update in unix milliseconds
+    fn update_time(&self) -> u64;
+    /// lowest ask as (price, volume)
+    fn best_ask(&self) -> Option<(f64, f64)>;
+    /// highest bid as (price, volume)
+    fn best_bid(&self) -> Option<(f64, f64)>;
+    /// Merges a delta of (price, volume) levels, zero volume removes the level.
+    /// Returns true when there is increase in the best price (i.e. lowest ask or highest bid)
+    fn apply(
+        &mut self,
+        sequence: u64,
+        asks: &[(f64, f64)],
+        bids: &[(f64, f64)],
+    ) -> Result<bool, String>;
+    /// asks as (price, volume) from the lowest price
+    fn asks(&self) -> impl Iterator<Item = (f64, f64)> + '_;
+    /// bids as (price, volume) from the highest price
+    fn bids(&self) -> impl Iterator<Item = (f64, f64)> + '_;
+
+    /// best n asks from the lowest price
+    fn top_asks(&self, n: usize) -> Vec<(f64, f64)> {
+        self.asks().take(n).collect()
+    }
+
+    /// best n bids from the highest price
+    fn top_bids(&self, n: usize) -> Vec<(f64, f64)> {
+        self.bids().take(n).collect()
+    }
+
+    /// best ask minus best bid
+    fn spread(&self) -> Option<f64> {
+        let (ask, _) = self.best_ask()?;
+        let (bid, _) = self.best_bid()?;
+        Some(ask - bid)
+    }
+
+    /// mean of best ask and best bid
+    fn mid(&self) -> Option<f64> {
+        let (ask, _) = self.best_ask()?;
+        let (bid, _) = self.best_bid()?;
+        Some((ask + bid) / 2.0)
+    }
+
+    /// total ask volume priced at or below the price, i.e. base amount buyable up to the price
+    fn ask_volume_to(&self, price: f64) -> f64 {
+        self.asks()
+            .take_while(|&(ask, _)| ask <= price)
+            .map(|(_, volume)| volume)
+            .sum()
+    }
+
+    /// total bid volume priced at or above the price, i.e. base amount sellable down to the price
+    fn bid_volume_to(&self, price: f64) -> f64 {
+        self.bids()
+            .take_while(|&(bid, _)| bid >= price)
+            .map(|(_, volume)| volume)
+            .sum()
+    }
+
+    /// worst ask price reached when buying the base volume, None if the asks are not deep enough
+    fn ask_price_to_fill(&self, volume: f64) -> Option<f64> {
+        price_to_fill(self.asks(), volume)
+    }
+
+    /// worst bid price reached when selling the base volume, None if the bids are not deep enough
+    fn bid_price_to_fill(&self, volume: f64) -> Option<f64> {
+        price_to_fill(self.bids(), volume)
+    }
+
+    /// volume weighted average price when buying with the quote amount, None if the asks are not deep enough
+    fn ask_vwap(&self, quote_amount: f64) -> Option<f64> {
+        vwap(self.asks(), quote_amount)
+    }
+
+    /// volume weighted average price when selling for the quote amount, None if the bids are not deep enough
+    fn bid_vwap(&self, quote_amount: f64) -> Option<f64> {
+        vwap(self.bids(), quote_amount)
+    }
+}
+
+/// true when the ask (e.g. of a delta) is lower than the best ask, or same ask but increased in volume
+pub fn is_better_ask(delta: Option<(f64, f64)>, best: Option<(f64, f64)>) -> bool {
+    match (delta, best) {
+        (Some((price, volume)), Some((best_price, best_volume))) => {
+            price < best_price || price == best_price && volume > best_volume
+        }
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// true when the bid (e.g. of a delta) is higher than the best bid, or same bid but increased in volume
+pub fn is_better_bid(delta: Option<(f64, f64)>, best: Option<(f64, f64)>) -> bool {
+    match (delta, best) {
+        (Some((price, volume)), Some((best_price, best_volume))) => {
+            price > best_price || price == best_price && volume > best_volume
+        }
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// lowest price level within a delta, without sorting or allocation
+pub(crate) fn delta_min(levels: &[(f64, f64)]) -> Option<(f64, f64)> {
+    levels
+        .iter()
+        .copied()
+        .reduce(|min, level| if level.0 < min.0 { level } else { min })
+}
+
+/// highest price level within a delta, without sorting or allocation
+pub(crate) fn delta_max(levels: &[(f64, f64)]) -> Option<(f64, f64)> {
+    levels
+        .iter()
+        .copied()
+        .reduce(|max, level| if level.0 > max.0 { level } else { max })
+}
+
+/// price levels kept per side of the local orderbooks, beyond the L20 REST snapshot
+pub const LOCAL_DEPTH: usize = 64;
+
+/// local orderbook synced from the feed, merged without allocation
+pub type LocalOrderbook = FixedDepthOrderbook<LOCAL_DEPTH>;
+
 /// symbol as key, orderbook as value
-pub type FullOrderbook = HashMap<String, Orderbook>; //Symbols to Orderbook
+pub type FullOrderbook = HashMap<String, LocalOrderbook>; //Symbols to Orderbook
 
 impl Orderbook {
     pub fn new() -> Orderbook {
@@ -45,21 +201,19 @@ impl Orderbook {
             ask: PVMap::new(),
             bid: PVMap::new(),
             sequence: 0,
+            exchange_time: 0,
+            update_time: 0,
+            receive_time: 0,
         }
     }
 
     /// Merges another Orderbook, returns Orderbook when there is increase in the best price (i.e. lowest ask or highest bid)
     pub fn merge(&mut self, to_merge: Orderbook) -> Result<Option<Orderbook>, String> {
-        let to_merge_clone = to_merge.to_owned();
-
-        // tracing::info!("to_merge: {to_merge_clone:?}");
+        // tracing::info!("to_merge: {to_merge:?}");
         let zero = 0.0;
 
-        // clone the best ask/bid
-        let best_ask = self.ask.first_key_value().unwrap();
-        let (min_ask, min_ask_volume) = (best_ask.0.to_owned(), best_ask.1.to_owned());
-        let best_bid = self.bid.last_key_value().unwrap();
-        let (max_bid, max_bid_volume) = (best_bid.0.to_owned(), best_bid.1.to_owned());
+        // copy the best ask/bid, either can be missing on an emptied orderbook
+        let (best_ask, best_bid) = (self.best_ask(), self.best_bid());
         if self.sequence > to_merge.sequence {
             // This happen in the beginning when older orderbook in websocket is received after REST
             return Err(format!("[{}] -> [{}]", to_merge.sequence, self.sequence));
@@ -67,9 +221,12 @@ impl Orderbook {
         // make sure that to_merge's PVMaps are already filtered such that
         // it is all behind the starting sequence
         self.sequence = to_merge.sequence;
+        self.exchange_time = to_merge.exchange_time;
+        self.update_time = to_merge.update_time;
+        self.receive_time = to_merge.receive_time;
 
-        // merge BTreeMap with insert
-        for (price, volume) in to_merge.ask.into_iter() {
+        // merge BTreeMap with insert, to_merge is returned as is
+        for (&price, &volume) in to_merge.ask.iter() {
             if volume.eq(&zero) {
                 if self.ask.remove(&price).is_none() {
                     // tracing::error!("failed to remove ask at {}, no orderbook data", &price);
@@ -78,7 +235,7 @@ impl Orderbook {
             }
             self.ask.insert(price, volume);
         }
-        for (price, volume) in to_merge.bid.into_iter() {
+        for (&price, &volume) in to_merge.bid.iter() {
             if volume.eq(&zero) {
                 if self.bid.remove(&price).is_none() {
                     // tracing::error!("failed to remove bid at {}, no orderbook data", &price);
@@ -88,25 +245,282 @@ impl Orderbook {
             self.bid.insert(price, volume);
         }
 
-        // if best ask exists in merge
-        if let Some((&merge_min_ask, &merge_min_ask_volume)) = to_merge_clone.ask.first_key_value()
+        // either the merge has lower ask/higher bid, or merge has same price but increased in volume
+        if is_better_ask(to_merge.best_ask(), best_ask)
+            || is_better_bid(to_merge.best_bid(), best_bid)
         {
-            // either the merge has lower ask, or merge has same ask but increased in volume
-            if merge_min_ask < min_ask
-                || merge_min_ask == min_ask && merge_min_ask_volume > min_ask_volume
-            {
-                return Ok(Some(to_merge_clone));
+            return Ok(Some(to_merge));
+        }
+        Ok(None)
+    }
+}
+
+/// price of the level where the cumulative volume reaches the volume
+fn price_to_fill(levels: impl Iterator<Item = (f64, f64)>, volume: f64) -> Option<f64> {
+    let mut cumulative = 0.0;
+    for (price, level_volume) in levels {
+        cumulative += level_volume;
+        if cumulative >= volume {
+            return Some(price);
+        }
+    }
+    None
+}
+
+/// quote amount divided by base amount, walking the levels until the quote amount is consumed
+fn vwap(levels: impl Iterator<Item = (f64, f64)>, quote_amount: f64) -> Option<f64> {
+    if quote_amount <= 0.0 {
+        return None;
+    }
+    let mut quote_left = quote_amount;
+    let mut base = 0.0;
+    for (price, volume) in levels {
+        let level_quote = price * volume;
+        if level_quote >= quote_left {
+            base += quote_left / price;
+            return Some(quote_amount / base);
+        }
+        quote_left -= level_quote;
+        base += volume;
+    }
+    None
+}
+
+/// Reason for an orderbook to be excluded from chance calculation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OrderbookFault {
+    /// either side has no price level
+    Empty,
+    /// best bid is greater than or equal to best ask
+    Crossed,
+    /// not updated within the max age
+    Stale,
+}
+
+impl std::fmt::Display for OrderbookFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OrderbookFault::Empty => "empty",
+            OrderbookFault::Crossed => "crossed",
+            OrderbookFault::Stale => "stale",
+        })
+    }
+}
+
+/// Flags orderbooks that are stale, crossed or empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OrderbookValidator {
+    /// max age of the last update in milliseconds
+    pub max_age: u64,
+}
+
+impl OrderbookValidator {
+    pub fn new(max_age: u64) -> Self {
+        OrderbookValidator { max_age }
+    }
+
+    /// validates with the current wall time `now` in unix milliseconds
+    pub fn validate(
+        &self,
+        orderbook: &impl OrderbookBackend,
+        now: u64,
+    ) -> Result<(), OrderbookFault> {
+        let (Some((ask, _)), Some((bid, _))) = (orderbook.best_ask(), orderbook.best_bid()) else {
+            return Err(OrderbookFault::Empty);
+        };
+        if bid >= ask {
+            return Err(OrderbookFault::Crossed);
+        }
+        if now.saturating_sub(orderbook.update_time()) > self.max_age {
+            return Err(OrderbookFault::Stale);
+        }
+        Ok(())
+    }
+}
+
+impl OrderbookBackend for Orderbook {
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn update_time(&self) -> u64 {
+        self.update_time
+    }
+
+    fn best_ask(&self) -> Option<(f64, f64)> {
+        self.ask
+            .first_key_value()
+            .map(|(price, volume)| (price.into_inner(), volume.into_inner()))
+    }
+
+    fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bid
+            .last_key_value()
+            .map(|(price, volume)| (price.into_inner(), volume.into_inner()))
+    }
+
+    fn apply(
+        &mut self,
+        sequence: u64,
+        asks: &[(f64, f64)],
+        bids: &[(f64, f64)],
+    ) -> Result<bool, String> {
+        if self.sequence > sequence {
+            return Err(format!("[{}] -> [{}]", sequence, self.sequence));
+        }
+        let (best_ask, best_bid) = (self.best_ask(), self.best_bid());
+        self.sequence = sequence;
+        for &(price, volume) in asks {
+            if volume == 0.0 {
+                self.ask.remove(&OrderedFloat(price));
+            } else {
+                self.ask.insert(OrderedFloat(price), OrderedFloat(volume));
             }
         }
-        // if best bid exists in merge
-        if let Some((&merge_max_bid, &merge_max_bid_volume)) = to_merge_clone.bid.last_key_value() {
-            // either the merge has higher bid, or merge has same bid but increased in volume
-            if merge_max_bid > max_bid
-                || merge_max_bid == max_bid && merge_max_bid_volume > max_bid_volume
-            {
-                return Ok(Some(to_merge_clone));
+        for &(price, volume) in bids {
+            if volume == 0.0 {
+                self.bid.remove(&OrderedFloat(price));
+            } else {
+                self.bid.insert(OrderedFloat(price), OrderedFloat(volume));
             }
         }
-        Ok(None)
+        Ok(is_better_ask(delta_min(asks), best_ask) || is_better_bid(delta_max(bids), best_bid))
+    }
+
+    fn asks(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.ask
+            .iter()
+            .map(|(price, volume)| (price.into_inner(), volume.into_inner()))
+    }
+
+    fn bids(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.bid
+            .iter()
+            .rev()
+            .map(|(price, volume)| (price.into_inner(), volume.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LocalOrderbook, Orderbook, OrderbookBackend, OrderbookFault, OrderbookValidator};
+    use ordered_float::OrderedFloat;
+
+    /// asks 101 x1, 102 x2, 103 x3; bids 99 x1, 98 x2, 97 x3
+    fn orderbook() -> Orderbook {
+        let mut orderbook = Orderbook::new();
+        for (i, volume) in [1.0, 2.0, 3.0].into_iter().enumerate() {
+            let offset = i as f64 + 1.0;
+            orderbook
+                .ask
+                .insert(OrderedFloat(100.0 + offset), OrderedFloat(volume));
+            orderbook
+                .bid
+                .insert(OrderedFloat(100.0 - offset), OrderedFloat(volume));
+        }
+        orderbook
+    }
+
+    /// the same levels on each backend
+    fn backends() -> (Orderbook, LocalOrderbook) {
+        let orderbook = orderbook();
+        let local = LocalOrderbook::from_orderbook(&orderbook, 0.5).unwrap();
+        (orderbook, local)
+    }
+
+    fn top_levels(orderbook: &impl OrderbookBackend) {
+        assert_eq!(orderbook.top_asks(2), vec![(101.0, 1.0), (102.0, 2.0)]);
+        assert_eq!(orderbook.top_bids(2), vec![(99.0, 1.0), (98.0, 2.0)]);
+        assert_eq!(orderbook.top_asks(10).len(), 3);
+    }
+
+    fn spread_and_mid(orderbook: &impl OrderbookBackend) {
+        assert_eq!(orderbook.spread(), Some(2.0));
+        assert_eq!(orderbook.mid(), Some(100.0));
+    }
+
+    fn cumulative_volume(orderbook: &impl OrderbookBackend) {
+        assert_eq!(orderbook.ask_volume_to(100.0), 0.0);
+        assert_eq!(orderbook.ask_volume_to(102.0), 3.0);
+        assert_eq!(orderbook.ask_volume_to(200.0), 6.0);
+        assert_eq!(orderbook.bid_volume_to(98.5), 1.0);
+        assert_eq!(orderbook.bid_volume_to(97.0), 6.0);
+    }
+
+    fn price_to_fill(orderbook: &impl OrderbookBackend) {
+        assert_eq!(orderbook.ask_price_to_fill(1.0), Some(101.0));
+        assert_eq!(orderbook.ask_price_to_fill(1.5), Some(102.0));
+        assert_eq!(orderbook.ask_price_to_fill(6.0), Some(103.0));
+        assert_eq!(orderbook.ask_price_to_fill(6.1), None);
+        assert_eq!(orderbook.bid_price_to_fill(3.0), Some(98.0));
+        assert_eq!(orderbook.bid_price_to_fill(7.0), None);
+    }
+
+    fn vwap(orderbook: &impl OrderbookBackend) {
+        // within the best level
+        assert_eq!(orderbook.ask_vwap(50.5), Some(101.0));
+        // 101 x1 and 102 x1
+        assert_eq!(orderbook.ask_vwap(203.0), Some(101.5));
+        // 99 x1 and 98 x1
+        assert_eq!(orderbook.bid_vwap(197.0), Some(98.5));
+        // deeper than the orderbook
+        assert_eq!(orderbook.ask_vwap(1000.0), None);
+        assert_eq!(orderbook.bid_vwap(0.0), None);
+    }
+
+    #[test]
+    fn test_queries() {
+        let (orderbook, local) = backends();
+        for check in [
+            top_levels,
+            spread_and_mid,
+            cumulative_volume,
+            price_to_fill,
+            vwap,
+        ] {
+            check(&orderbook);
+        }
+        for check in [
+            top_levels,
+            spread_and_mid,
+            cumulative_volume,
+            price_to_fill,
+            vwap,
+        ] {
+            check(&local);
+        }
+    }
+
+    #[test]
+    fn test_empty_queries() {
+        let empty = LocalOrderbook::new(0.5).unwrap();
+        assert!(empty.top_bids(1).is_empty());
+        assert_eq!(empty.spread(), None);
+        assert_eq!(empty.mid(), None);
+        assert_eq!(Orderbook::new().spread(), None);
+        assert_eq!(Orderbook::new().mid(), None);
+    }
+
+    #[test]
+    fn test_validator() {
+        let validator = OrderbookValidator::new(60_000);
+        let mut orderbook = Orderbook::new();
+        assert_eq!(
+            validator.validate(&orderbook, 0),
+            Err(OrderbookFault::Empty)
+        );
+        orderbook.ask.insert(OrderedFloat(10.0), OrderedFloat(1.0));
+        orderbook.bid.insert(OrderedFloat(9.0), OrderedFloat(1.0));
+        orderbook.update_time = 1_000;
+        assert_eq!(validator.validate(&orderbook, 61_000), Ok(()));
+        assert_eq!(
+            validator.validate(&orderbook, 61_001),
+            Err(OrderbookFault::Stale)
+        );
+        orderbook.bid.insert(OrderedFloat(10.0), OrderedFloat(1.0));
+        assert_eq!(
+            validator.validate(&orderbook, 1_000),
+            Err(OrderbookFault::Crossed)
+        );
     }
 }
@@ -1,5 +1,6 @@
 use ordered_float::OrderedFloat;
-/// symbol info that has its base, quote, base_min and base_increment, used for formatting the order placement
+/// symbol info that has its base, quote, base_min, base_increment and price_increment,
+/// used for formatting the order placement and the price ticks of the local orderbook
 #[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SymbolInfo {
     // e.g. BTC-USDT (name should be BASE-QUOTE, thus use symbol instead of name)
@@ -12,4 +13,6 @@ pub struct SymbolInfo {
     pub base_min: OrderedFloat<f64>,
     // e.g. 0.001
     pub base_increment: OrderedFloat<f64>,
+    // e.g. 0.1
+    pub price_increment: OrderedFloat<f64>,
 }
@@ -1,9 +1,17 @@
+use crate::model::cycle::CycleId;
 use crate::model::order;
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TradeInfo {
-    pub order_id: u128,
+    pub client_oid: String,
+    /// cycle of the order, None for orders placed outside cycles
+    pub cycle_id: Option<CycleId>,
     pub symbol: String,
     pub side: order::OrderSide,
     pub order_type: order::OrderType,
+    /// order size
     pub size: String,
+    /// size executed so far, e.g. short of the order size for a canceled IOC remainder
+    pub filled_size: String,
+    /// price of the match, only reported by TradeMatch
+    pub match_price: Option<String>,
 }
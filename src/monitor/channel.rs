@@ -0,0 +1,86 @@
+use crate::error::{Error, Result};
+use crate::monitor::counter::Counter;
+use crate::monitor::gauge::Gauge;
+use crate::monitor::registry::{self, Registry};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+/// Broadcast subscription that keeps receiving after lagging, counting `lagged` and `queue_depth`
+#[derive(Debug)]
+pub struct Subscriber<T> {
+    receiver: Receiver<T>,
+    cx_lagged: Arc<Counter>,
+    queue_depth: Arc<Gauge>,
+    /// dropped since the last take_lagged
+    lagged: u64,
+}
+
+impl<T: Clone> Subscriber<T> {
+    /// subscription counted in the global registry
+    pub fn new(receiver: Receiver<T>, channel: &str, subscriber: &str) -> Self {
+        Self::with_registry(receiver, channel, subscriber, &registry::global())
+    }
+
+    pub fn with_registry(
+        receiver: Receiver<T>,
+        channel: &str,
+        subscriber: &str,
+        registry: &Registry,
+    ) -> Self {
+        let labels = [("channel", channel), ("subscriber", subscriber)];
+        Self {
+            receiver,
+            cx_lagged: registry.counter("lagged", &labels),
+            queue_depth: registry.gauge("queue_depth", &labels),
+            lagged: 0,
+        }
+    }
+
+    /// next message, skipping over the dropped ones, cancel safe
+    pub async fn recv(&mut self) -> Result<T> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(message) => {
+                    self.queue_depth.set(self.receiver.len() as f64);
+                    return Ok(message);
+                }
+                Err(RecvError::Lagged(count)) => {
+                    tracing::warn!("Subscriber lagged, {count} messages dropped");
+                    self.cx_lagged.add(count);
+                    self.lagged += count;
+                }
+                Err(RecvError::Closed) => return Err(Error::ChannelClosed),
+            }
+        }
+    }
+
+    /// messages dropped since the last call, e.g. to resync the state built from them
+    pub fn take_lagged(&mut self) -> u64 {
+        std::mem::take(&mut self.lagged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Subscriber;
+    use crate::monitor::registry::Registry;
+    use tokio::sync::broadcast::channel;
+
+    #[tokio::test]
+    async fn test_lagged() {
+        let registry = Registry::new();
+        let labels = [("channel", "number"), ("subscriber", "test")];
+        let tx = channel::<u32>(2).0;
+        let mut rx = Subscriber::with_registry(tx.subscribe(), "number", "test", &registry);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        // 0, 1 and 2 were overwritten by the capacity of 2
+        assert_eq!(rx.recv().await.unwrap(), 3);
+        assert_eq!(rx.take_lagged(), 3);
+        assert_eq!(rx.take_lagged(), 0);
+        assert_eq!(registry.gauge("queue_depth", &labels).get(), 1.0);
+        assert_eq!(registry.counter("lagged", &labels).total(), 3);
+    }
+}
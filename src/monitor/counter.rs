@@ -1,36 +1,68 @@
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::monitor::registry::Labels;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-/// Counter for system monitor
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Counter for system monitor, updated without locking
+#[derive(Debug, Default)]
 pub struct Counter {
     pub name: &'static str,
-    pub data_count: u64,
+    pub labels: Labels,
+    /// count since the last reset
+    data_count: AtomicU64,
+    /// count since start, never reset
+    total_count: AtomicU64,
 }
 
 impl Counter {
     // Constructs a new instance of [`Counter`].
     pub fn new(name: &'static str) -> Self {
+        Self::with_labels(name, Labels::new())
+    }
+
+    pub fn with_labels(name: &'static str, labels: Labels) -> Self {
         Self {
             name,
-            data_count: 0,
+            labels,
+            data_count: AtomicU64::new(0),
+            total_count: AtomicU64::new(0),
         }
     }
-}
 
-// TODO make use of async traits
+    pub fn increment(&self) {
+        self.add(1);
+    }
 
-pub async fn increment(counter: Arc<Mutex<Counter>>) {
-    let mut p = counter.lock().await;
-    p.data_count += 1;
-}
+    pub fn add(&self, count: u64) {
+        self.data_count.fetch_add(count, Ordering::Relaxed);
+        self.total_count.fetch_add(count, Ordering::Relaxed);
+    }
 
-pub async fn count(counter: Arc<Mutex<Counter>>) -> u64 {
-    let p = counter.lock().await;
-    p.data_count
+    /// count since the last reset
+    pub fn count(&self) -> u64 {
+        self.data_count.load(Ordering::Relaxed)
+    }
+
+    /// count since start
+    pub fn total(&self) -> u64 {
+        self.total_count.load(Ordering::Relaxed)
+    }
+
+    /// clears the count since the last reset, returning it
+    pub fn reset(&self) -> u64 {
+        self.data_count.swap(0, Ordering::Relaxed)
+    }
 }
 
-pub async fn reset(counter: Arc<Mutex<Counter>>) {
-    let mut p = counter.lock().await;
-    p.data_count = 0;
+#[cfg(test)]
+mod tests {
+    use super::Counter;
+
+    #[test]
+    fn test_counter() {
+        let counter = Counter::new("chance");
+        counter.increment();
+        counter.add(2);
+        assert_eq!(counter.reset(), 3);
+        assert_eq!(counter.count(), 0);
+        assert_eq!(counter.total(), 3);
+    }
 }
@@ -0,0 +1,238 @@
+use crate::error::{Error, Result};
+use crate::monitor::registry::{Labels, Registry};
+use crate::shutdown::ShutdownToken;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+/// prefix of every exported metric
+const NAMESPACE: &str = "kucoin_arbitrage";
+/// upper bounds of the exported histogram buckets
+const BUCKET_BOUNDS: [Duration; 12] = [
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Renders the metrics of the registry in Prometheus text format
+pub fn render(registry: &Registry) -> String {
+    let mut text = String::new();
+    let mut counters = registry.counters();
+    // instances of a metric are listed together under one TYPE line
+    counters.sort_by_key(|counter| counter.name);
+    let mut last = "";
+    for counter in counters {
+        let name = format!("{NAMESPACE}_{}_total", counter.name);
+        if counter.name != last {
+            let _ = writeln!(text, "# TYPE {name} counter");
+            last = counter.name;
+        }
+        let _ = writeln!(
+            text,
+            "{name}{} {}",
+            label_set(&counter.labels, None),
+            counter.total()
+        );
+    }
+    let mut gauges = registry.gauges();
+    gauges.sort_by_key(|gauge| gauge.name);
+    let mut last = "";
+    for gauge in gauges {
+        let name = format!("{NAMESPACE}_{}", gauge.name);
+        if gauge.name != last {
+            let _ = writeln!(text, "# TYPE {name} gauge");
+            last = gauge.name;
+        }
+        let _ = writeln!(
+            text,
+            "{name}{} {}",
+            label_set(&gauge.labels, None),
+            gauge.get()
+        );
+    }
+    let mut histograms = registry.histograms();
+    histograms.sort_by_key(|histogram| histogram.name);
+    let mut last = "";
+    for histogram in histograms {
+        let name = format!("{NAMESPACE}_{}_seconds", histogram.name);
+        if histogram.name != last {
+            let _ = writeln!(text, "# TYPE {name} histogram");
+            last = histogram.name;
+        }
+        for bound in BUCKET_BOUNDS {
+            let _ = writeln!(
+                text,
+                "{name}_bucket{} {}",
+                label_set(&histogram.labels, Some(&bound.as_secs_f64().to_string())),
+                histogram.count_below(bound)
+            );
+        }
+        let count = histogram.count();
+        let labels = label_set(&histogram.labels, None);
+        let _ = writeln!(
+            text,
+            "{name}_bucket{} {count}",
+            label_set(&histogram.labels, Some("+Inf"))
+        );
+        let _ = writeln!(text, "{name}_sum{labels} {}", histogram.sum().as_secs_f64());
+        let _ = writeln!(text, "{name}_count{labels} {count}");
+    }
+    text
+}
+
+/// `{key="value",...}` with the bucket bound appended as `le`, empty without any label
+fn label_set(labels: &Labels, le: Option<&str>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .chain(le.map(|le| ("le", le)))
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{key}=\"{value}\"")
+        })
+        .collect();
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+async fn handle(request: Request<Body>, registry: Arc<Registry>) -> Response<Body> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(render(&registry))),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    }
+    .unwrap_or_default()
+}
+
+/// Serves /metrics on the listener until shutdown
+pub async fn serve_metrics(
+    listener: TcpListener,
+    registry: Arc<Registry>,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    listener.set_nonblocking(true)?;
+    let make_service = make_service_fn(move |_| {
+        let registry = registry.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let registry = registry.clone();
+                async move { Ok::<_, Infallible>(handle(request, registry).await) }
+            }))
+        }
+    });
+    Server::from_tcp(listener)
+        .map_err(|e| Error::Network(format!("failed serving metrics: {e}")))?
+        .serve(make_service)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await
+        .map_err(|e| Error::Network(format!("metrics server failed: {e}")))
+}
+
+/// Task to serve the Prometheus /metrics endpoint on the address
+pub async fn task_serve_metrics(
+    address: SocketAddr,
+    registry: Arc<Registry>,
+    shutdown: ShutdownToken,
+) -> Result<()> {
+    let listener = TcpListener::bind(address)?;
+    tracing::info!("Serving metrics on http://{address}/metrics");
+    serve_metrics(listener, registry, shutdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, serve_metrics};
+    use crate::monitor::registry::Registry;
+    use crate::shutdown::{ShutdownCoordinator, ShutdownPhase};
+    use hyper::{body, Client, StatusCode};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let registry = Arc::new(Registry::new());
+        let cx_order = registry.counter("order", &[]);
+        cx_order.increment();
+        cx_order.increment();
+        registry
+            .histogram("latency", &[("stage", "rest_ack")])
+            .record(Duration::from_millis(20));
+        registry
+            .histogram("latency", &[("stage", "fill")])
+            .record(Duration::from_millis(2));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let shutdown = ShutdownCoordinator::new();
+        let server = tokio::spawn(serve_metrics(
+            listener,
+            registry,
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+
+        let client = Client::new();
+        let url = format!("http://{address}/metrics").parse().unwrap();
+        let response = client.get(url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = body::to_bytes(response.into_body()).await.unwrap();
+        let text = String::from_utf8(text.to_vec()).unwrap();
+        assert!(text.contains("kucoin_arbitrage_order_total 2\n"));
+        let latency = "kucoin_arbitrage_latency_seconds";
+        assert_eq!(
+            text.matches(&format!("# TYPE {latency} histogram")).count(),
+            1
+        );
+        assert!(text.contains(&format!(
+            "{latency}_bucket{{stage=\"rest_ack\",le=\"0.01\"}} 0\n"
+        )));
+        assert!(text.contains(&format!(
+            "{latency}_bucket{{stage=\"rest_ack\",le=\"0.025\"}} 1\n"
+        )));
+        assert!(text.contains(&format!(
+            "{latency}_bucket{{stage=\"fill\",le=\"0.005\"}} 1\n"
+        )));
+        assert!(text.contains(&format!("{latency}_count{{stage=\"rest_ack\"}} 1\n")));
+
+        let url = format!("http://{address}/unknown").parse().unwrap();
+        let response = client.get(url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        shutdown.advance(ShutdownPhase::Flush);
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::new();
+        registry.counter("chance", &[]).add(3);
+        registry
+            .counter("messages", &[("channel", "trade")])
+            .increment();
+        let text = render(&registry);
+        assert!(text.contains("kucoin_arbitrage_chance_total 3\n"));
+        assert!(text.contains("kucoin_arbitrage_messages_total{channel=\"trade\"} 1\n"));
+    }
+}
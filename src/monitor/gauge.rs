@@ -0,0 +1,83 @@
+use crate::monitor::registry::Labels;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Gauge for system monitor, a value that goes up and down, stored as the bits of an f64
+#[derive(Debug, Default)]
+pub struct Gauge {
+    pub name: &'static str,
+    pub labels: Labels,
+    bits: AtomicU64,
+}
+
+impl Gauge {
+    pub fn new(name: &'static str) -> Self {
+        Self::with_labels(name, Labels::new())
+    }
+
+    pub fn with_labels(name: &'static str, labels: Labels) -> Self {
+        Self {
+            name,
+            labels,
+            bits: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, value: f64) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, delta: f64) {
+        // the closure always returns Some, so the update cannot fail
+        let _ = self
+            .bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + delta).to_bits())
+            });
+    }
+
+    /// adds one until the returned guard is dropped, e.g. for connected sessions
+    pub fn track(self: &Arc<Self>) -> GaugeGuard {
+        self.add(1.0);
+        GaugeGuard(self.clone())
+    }
+}
+
+/// Subtracts the one added by [`Gauge::track`] when dropped
+#[derive(Debug)]
+pub struct GaugeGuard(Arc<Gauge>);
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.add(-1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gauge;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_gauge() {
+        let gauge = Gauge::new("realised_pnl");
+        gauge.add(1.5);
+        gauge.add(-0.5);
+        assert_eq!(gauge.get(), 1.0);
+        gauge.set(3.0);
+        assert_eq!(gauge.get(), 3.0);
+    }
+
+    #[test]
+    fn test_track() {
+        let connected = Arc::new(Gauge::new("ws_connected"));
+        let session = connected.track();
+        assert_eq!(connected.get(), 1.0);
+        drop(session);
+        assert_eq!(connected.get(), 0.0);
+    }
+}
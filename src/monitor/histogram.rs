@@ -0,0 +1,126 @@
+use crate::monitor::registry::Labels;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// linear sub-buckets per power of two, bounding the relative error to 1/16
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = ((64 - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS) as usize;
+
+/// HDR-style latency histogram in microseconds, with log-linear buckets of bounded relative error
+#[derive(Debug)]
+pub struct Histogram {
+    pub name: &'static str,
+    pub labels: Labels,
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(name: &'static str) -> Self {
+        Self::with_labels(name, Labels::new())
+    }
+
+    pub fn with_labels(name: &'static str, labels: Labels) -> Self {
+        Self {
+            name,
+            labels,
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let us = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets[index(us)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_us.load(Ordering::Relaxed))
+    }
+
+    /// latency below which the quantile (0.0 to 1.0) of the records are, zero when empty
+    pub fn percentile(&self, quantile: f64) -> Duration {
+        let target = ((quantile.clamp(0.0, 1.0) * self.count() as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            if cumulative >= target {
+                return Duration::from_micros(highest_equivalent(i));
+            }
+        }
+        Duration::ZERO
+    }
+
+    /// records at or below the latency, counted by whole buckets
+    pub fn count_below(&self, latency: Duration) -> u64 {
+        let us = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets
+            .iter()
+            .enumerate()
+            .take_while(|(i, _)| highest_equivalent(*i) <= us)
+            .map(|(_, count)| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn reset(&self) {
+        self.buckets
+            .iter()
+            .for_each(|count| count.store(0, Ordering::Relaxed));
+        self.count.store(0, Ordering::Relaxed);
+        self.sum_us.store(0, Ordering::Relaxed);
+    }
+}
+
+fn index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let shift = 63 - value.leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) - SUB_BUCKETS;
+    ((shift as u64 + 1) * SUB_BUCKETS + sub_bucket) as usize
+}
+
+fn lowest_equivalent(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    (SUB_BUCKETS + index % SUB_BUCKETS) << shift
+}
+
+fn highest_equivalent(index: usize) -> u64 {
+    if index + 1 >= BUCKETS {
+        u64::MAX
+    } else {
+        lowest_equivalent(index + 1) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+    use std::time::Duration;
+
+    #[test]
+    fn test_percentile() {
+        let histogram = Histogram::new("order_ack");
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 100);
+        let p50 = histogram.percentile(0.5).as_secs_f64();
+        assert!((0.048..=0.053).contains(&p50));
+        let p99 = histogram.percentile(0.99).as_secs_f64();
+        assert!((0.097..=0.104).contains(&p99));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::monitor::histogram::Histogram;
+use crate::monitor::registry;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+/// Stages of the tick-to-order path, each measured from the previous timestamp available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    /// exchange timestamp to websocket receive, includes the clock offset to the exchange
+    Exchange,
+    /// websocket receive to the local orderbook merged
+    Merge,
+    /// websocket receive to the chance published
+    Chance,
+    /// websocket receive to the first order of the cycle sent
+    OrderSend,
+    /// REST round trip of the accepted order
+    RestAck,
+    /// order sent to the private fill received
+    Fill,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Exchange,
+        Stage::Merge,
+        Stage::Chance,
+        Stage::OrderSend,
+        Stage::RestAck,
+        Stage::Fill,
+    ];
+
+    /// value of the stage label
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Exchange => "exchange",
+            Stage::Merge => "merge",
+            Stage::Chance => "chance",
+            Stage::OrderSend => "order_send",
+            Stage::RestAck => "rest_ack",
+            Stage::Fill => "fill",
+        }
+    }
+}
+
+// histograms of the global registry, kept to skip the lookup on every record
+lazy_static::lazy_static! {
+    static ref STAGES: BTreeMap<Stage, Arc<Histogram>> = Stage::ALL
+        .into_iter()
+        .map(|stage| {
+            let histogram = registry::global().histogram("latency", &[("stage", stage.name())]);
+            (stage, histogram)
+        })
+        .collect();
+}
+
+/// local wall time in unix microseconds, the unit of the receive timestamps
+pub fn now_micros() -> u64 {
+    chrono::Utc::now().timestamp_micros() as u64
+}
+
+/// records the latency of a stage
+pub fn record(stage: Stage, latency: Duration) {
+    STAGES[&stage].record(latency);
+}
+
+/// records the latency of a stage since the wall time in unix microseconds, ignored if unknown (0)
+pub fn record_since(stage: Stage, since_micros: u64) {
+    if since_micros == 0 {
+        return;
+    }
+    let latency = Duration::from_micros(now_micros().saturating_sub(since_micros));
+    record(stage, latency);
+}
+
+/// logs the percentiles of every stage recorded since start
+pub fn report() {
+    tracing::info!("Latency percentiles (p50/p90/p99/max)");
+    for (stage, histogram) in STAGES.iter() {
+        if histogram.count() == 0 {
+            continue;
+        }
+        tracing::info!(
+            "{:10}: {:?}/{:?}/{:?}/{:?} ({} samples)",
+            format!("{stage:?}"),
+            histogram.percentile(0.5),
+            histogram.percentile(0.9),
+            histogram.percentile(0.99),
+            histogram.percentile(1.0),
+            histogram.count()
+        );
+    }
+}
@@ -1,5 +1,21 @@
+/// broadcast subscription counting lag and queue depth
+pub mod channel;
 /// counter
 pub mod counter;
+/// Prometheus /metrics endpoint
+pub mod exporter;
+/// gauge
+pub mod gauge;
+/// latency histogram
+pub mod histogram;
+/// tick-to-order latency per stage
+pub mod latency;
+/// metrics looked up by name and labels
+pub mod registry;
+/// recent activity shown by the status API
+pub mod status;
+/// health, status and control HTTP API
+pub mod status_api;
 /// task for system monitor
 pub mod task;
 /// timer
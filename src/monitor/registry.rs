@@ -0,0 +1,128 @@
+use crate::monitor::counter::Counter;
+use crate::monitor::gauge::Gauge;
+use crate::monitor::histogram::Histogram;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Label pairs distinguishing the instances of a metric, e.g. `channel="orderbook"`
+pub type Labels = Vec<(&'static str, String)>;
+
+/// Registry of the metrics by name and labels, hot paths keep the returned handle
+#[derive(Debug, Default)]
+pub struct Registry {
+    counters: RwLock<Vec<Arc<Counter>>>,
+    gauges: RwLock<Vec<Arc<Gauge>>>,
+    histograms: RwLock<Vec<Arc<Histogram>>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// counter of the name and labels, registered on first use
+    pub fn counter(&self, name: &'static str, labels: &[(&'static str, &str)]) -> Arc<Counter> {
+        get_or_register(
+            &self.counters,
+            |counter| counter.name == name && same_labels(&counter.labels, labels),
+            || Counter::with_labels(name, to_labels(labels)),
+        )
+    }
+
+    /// gauge of the name and labels, registered on first use
+    pub fn gauge(&self, name: &'static str, labels: &[(&'static str, &str)]) -> Arc<Gauge> {
+        get_or_register(
+            &self.gauges,
+            |gauge| gauge.name == name && same_labels(&gauge.labels, labels),
+            || Gauge::with_labels(name, to_labels(labels)),
+        )
+    }
+
+    /// histogram of the name and labels, registered on first use
+    pub fn histogram(&self, name: &'static str, labels: &[(&'static str, &str)]) -> Arc<Histogram> {
+        get_or_register(
+            &self.histograms,
+            |histogram| histogram.name == name && same_labels(&histogram.labels, labels),
+            || Histogram::with_labels(name, to_labels(labels)),
+        )
+    }
+
+    /// registered counters, in registration order
+    pub fn counters(&self) -> Vec<Arc<Counter>> {
+        read(&self.counters)
+    }
+
+    pub fn gauges(&self) -> Vec<Arc<Gauge>> {
+        read(&self.gauges)
+    }
+
+    pub fn histograms(&self) -> Vec<Arc<Histogram>> {
+        read(&self.histograms)
+    }
+}
+
+// static registry for global access
+lazy_static::lazy_static! {
+    static ref GLOBAL: Arc<Registry> = Arc::new(Registry::new());
+}
+
+/// registry shared by every task of the process
+pub fn global() -> Arc<Registry> {
+    GLOBAL.clone()
+}
+
+fn to_labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(key, value)| (*key, value.to_string()))
+        .collect()
+}
+
+fn same_labels(registered: &Labels, labels: &[(&'static str, &str)]) -> bool {
+    registered.len() == labels.len()
+        && registered
+            .iter()
+            .zip(labels)
+            .all(|((k1, v1), (k2, v2))| k1 == k2 && v1 == v2)
+}
+
+fn read<T>(metrics: &RwLock<Vec<Arc<T>>>) -> Vec<Arc<T>> {
+    // metrics are only appended, a poisoned list is still consistent
+    metrics
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+fn get_or_register<T>(
+    metrics: &RwLock<Vec<Arc<T>>>,
+    matches: impl Fn(&T) -> bool,
+    create: impl FnOnce() -> T,
+) -> Arc<T> {
+    let mut metrics = metrics.write().unwrap_or_else(PoisonError::into_inner);
+    if let Some(metric) = metrics.iter().find(|metric| matches(metric)) {
+        return metric.clone();
+    }
+    let metric = Arc::new(create());
+    metrics.push(metric.clone());
+    metric
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registry;
+
+    #[test]
+    fn test_same_counter() {
+        let registry = Registry::new();
+        let orderbook = registry.counter("messages", &[("channel", "orderbook")]);
+        let chance = registry.counter("messages", &[("channel", "chance")]);
+        orderbook.increment();
+        // the same name and labels give the same counter
+        registry
+            .counter("messages", &[("channel", "orderbook")])
+            .increment();
+        assert_eq!(orderbook.total(), 2);
+        assert_eq!(chance.total(), 0);
+        assert_eq!(registry.counters().len(), 2);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::model::chance::TriangularArbitrageChance;
+use crate::model::cycle::CycleId;
+use serde_derive::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// records kept of each recent kind
+const RECENT: usize = 50;
+
+/// Chance found by a strategy
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChanceRecord {
+    pub time: String,
+    pub profit: f64,
+    /// e.g. "Buy ETH-BTC 0.1 at 0.05"
+    pub actions: Vec<String>,
+}
+
+/// Error logged by any task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorRecord {
+    pub time: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// Cycle placed by the gatekeeper and not ended yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CycleRecord {
+    pub id: String,
+    pub started: String,
+    pub symbols: Vec<String>,
+}
+
+/// Recent activity shown by the status API, bounded to the last records of each kind
+#[derive(Debug, Default)]
+pub struct StatusBoard {
+    chances: Mutex<VecDeque<ChanceRecord>>,
+    errors: Mutex<VecDeque<ErrorRecord>>,
+    cycles: Mutex<Vec<CycleRecord>>,
+}
+
+impl StatusBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_chance(&self, chance: &TriangularArbitrageChance) {
+        let record = ChanceRecord {
+            time: now(),
+            profit: chance.profit.into_inner(),
+            actions: chance
+                .actions
+                .iter()
+                .map(|a| format!("{} {} {} at {}", a.action, a.ticker, a.volume, a.price))
+                .collect(),
+        };
+        push(&self.chances, record);
+    }
+
+    pub fn record_error(&self, target: &str, message: &str) {
+        let record = ErrorRecord {
+            time: now(),
+            target: target.to_string(),
+            message: message.to_string(),
+        };
+        push(&self.errors, record);
+    }
+
+    /// lists the cycle until ended once its outcome is known
+    pub fn start_cycle(&self, id: CycleId, symbols: Vec<String>) {
+        let record = CycleRecord {
+            id: id.to_string(),
+            started: now(),
+            symbols,
+        };
+        lock(&self.cycles).push(record);
+    }
+
+    pub fn end_cycle(&self, id: CycleId) {
+        let id = id.to_string();
+        lock(&self.cycles).retain(|cycle| cycle.id != id);
+    }
+
+    /// most recent first
+    pub fn chances(&self) -> Vec<ChanceRecord> {
+        lock(&self.chances).iter().rev().cloned().collect()
+    }
+
+    /// most recent first
+    pub fn errors(&self) -> Vec<ErrorRecord> {
+        lock(&self.errors).iter().rev().cloned().collect()
+    }
+
+    /// cycles in flight, oldest first
+    pub fn cycles(&self) -> Vec<CycleRecord> {
+        lock(&self.cycles).clone()
+    }
+}
+
+// static board for global access
+lazy_static::lazy_static! {
+    static ref GLOBAL: Arc<StatusBoard> = Arc::new(StatusBoard::new());
+}
+
+/// board shared by every task of the process
+pub fn global() -> Arc<StatusBoard> {
+    GLOBAL.clone()
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // records are replaced whole, a poisoned board is still consistent
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn push<T>(records: &Mutex<VecDeque<T>>, record: T) {
+    let mut records = lock(records);
+    if records.len() == RECENT {
+        records.pop_front();
+    }
+    records.push_back(record);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatusBoard;
+    use crate::model::cycle::CycleId;
+
+    #[test]
+    fn test_board() {
+        let board = StatusBoard::new();
+        board.record_error("gatekeeper", "Order rejected");
+        let id = CycleId::new();
+        board.start_cycle(id, vec!["BTC-USDT".to_string()]);
+        assert_eq!(board.cycles()[0].id, id.to_string());
+        board.end_cycle(id);
+        assert!(board.cycles().is_empty());
+        assert_eq!(board.errors()[0].message, "Order rejected");
+    }
+}
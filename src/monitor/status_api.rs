@@ -0,0 +1,358 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::event::order::OrderEvent;
+use crate::model::inventory::Balances;
+use crate::model::order::OpenOrders;
+use crate::model::orderbook::{FullOrderbook, OrderbookBackend};
+use crate::monitor::registry::Labels;
+use crate::monitor::{registry, status};
+use crate::shutdown::ShutdownToken;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Map, Value};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use tokio::sync::broadcast::Sender;
+use tokio::sync::{watch, Mutex};
+
+/// shown in place of the credentials
+const REDACTED: &str = "<redacted>";
+
+/// State read and controlled through the status API
+#[derive(Debug, Clone)]
+pub struct StatusSources {
+    pub config: Config,
+    /// None when the balances are not tracked
+    pub balances: Option<Arc<Mutex<Balances>>>,
+    pub open_orders: Arc<Mutex<OpenOrders>>,
+    pub full_orderbook: Arc<Mutex<FullOrderbook>>,
+    /// orderbooks synced once ready
+    pub books_expected: usize,
+    /// websocket sessions (public and private) connected once ready
+    pub sessions_expected: usize,
+    /// true while trading is paused, no chance is taken and no inventory rebalanced
+    pub paused: Arc<watch::Sender<bool>>,
+    /// receives CancelAllOrders
+    pub tx_order: Sender<OrderEvent>,
+}
+
+/// Config with the credentials replaced
+pub fn redacted(config: &Config) -> Value {
+    let mut config = config.clone();
+    config.kucoin.api_key = REDACTED.to_string();
+    config.kucoin.secret_key = REDACTED.to_string();
+    config.kucoin.passphrase = REDACTED.to_string();
+    serde_json::to_value(config).unwrap_or_default()
+}
+
+fn json(status: StatusCode, value: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap_or_default()
+}
+
+/// websocket sessions connected, books synced and whether trading is paused
+async fn readiness(sources: &StatusSources) -> (bool, Value) {
+    let sessions: f64 = registry::global()
+        .gauges()
+        .iter()
+        .filter(|gauge| gauge.name == "ws_connected")
+        .map(|gauge| gauge.get())
+        .sum();
+    let sessions = sessions as usize;
+    let books = sources.full_orderbook.lock().await.len();
+    let ready = sessions >= sources.sessions_expected && books >= sources.books_expected;
+    let body = json!({
+        "ready": ready,
+        "paused": *sources.paused.borrow(),
+        "ws_sessions": { "connected": sessions, "expected": sources.sessions_expected },
+        "books": { "synced": books, "expected": sources.books_expected },
+    });
+    (ready, body)
+}
+
+/// best bid and ask as [price, volume] of the symbols, every symbol synced when none is given
+async fn top_of_book(sources: &StatusSources, symbols: Option<&str>) -> Value {
+    let full_orderbook = sources.full_orderbook.lock().await;
+    let mut books = Map::new();
+    for (symbol, orderbook) in full_orderbook.iter() {
+        if symbols.is_some_and(|symbols| !symbols.split(',').any(|s| s == symbol)) {
+            continue;
+        }
+        let level = |level: Option<(f64, f64)>| json!(level.map(|(p, v)| [p, v]));
+        let top = json!({ "bid": level(orderbook.best_bid()), "ask": level(orderbook.best_ask()) });
+        books.insert(symbol.clone(), top);
+    }
+    Value::Object(books)
+}
+
+/// totals of the counters and values of the gauges in the registry
+fn metrics() -> Value {
+    let labels = |labels: &Labels| {
+        let labels: Map<String, Value> = labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), json!(value)))
+            .collect();
+        Value::Object(labels)
+    };
+    let registry = registry::global();
+    let counters: Vec<Value> = registry
+        .counters()
+        .iter()
+        .map(|c| json!({ "name": c.name, "labels": labels(&c.labels), "total": c.total() }))
+        .collect();
+    let gauges: Vec<Value> = registry
+        .gauges()
+        .iter()
+        .map(|g| json!({ "name": g.name, "labels": labels(&g.labels), "value": g.get() }))
+        .collect();
+    json!({ "counters": counters, "gauges": gauges })
+}
+
+async fn handle(request: Request<Body>, sources: Arc<StatusSources>) -> Response<Body> {
+    let board = status::global();
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/health") => json(StatusCode::OK, json!({ "status": "ok" })),
+        (&Method::GET, "/ready") => {
+            let (ready, body) = readiness(&sources).await;
+            if ready {
+                json(StatusCode::OK, body)
+            } else {
+                json(StatusCode::SERVICE_UNAVAILABLE, body)
+            }
+        }
+        (&Method::GET, "/config") => json(StatusCode::OK, redacted(&sources.config)),
+        (&Method::GET, "/balances") => match &sources.balances {
+            Some(balances) => json(StatusCode::OK, json!(*balances.lock().await)),
+            None => json(
+                StatusCode::NOT_FOUND,
+                json!({ "error": "balances not tracked" }),
+            ),
+        },
+        (&Method::GET, "/orders") => json(StatusCode::OK, json!(*sources.open_orders.lock().await)),
+        (&Method::GET, "/books") => {
+            let symbols = request
+                .uri()
+                .query()
+                .and_then(|query| query.strip_prefix("symbols="));
+            json(StatusCode::OK, top_of_book(&sources, symbols).await)
+        }
+        (&Method::GET, "/counters") => json(StatusCode::OK, metrics()),
+        (&Method::GET, "/cycles") => json(StatusCode::OK, json!(board.cycles())),
+        (&Method::GET, "/chances") => json(StatusCode::OK, json!(board.chances())),
+        (&Method::GET, "/errors") => json(StatusCode::OK, json!(board.errors())),
+        (&Method::POST, "/pause") => {
+            tracing::warn!("Trading paused from the status API");
+            sources.paused.send_replace(true);
+            json(StatusCode::OK, json!({ "paused": true }))
+        }
+        (&Method::POST, "/resume") => {
+            tracing::warn!("Trading resumed from the status API");
+            sources.paused.send_replace(false);
+            json(StatusCode::OK, json!({ "paused": false }))
+        }
+        (&Method::POST, "/cancel_all") => {
+            tracing::warn!("Canceling all orders from the status API");
+            match sources.tx_order.send(OrderEvent::CancelAllOrders(None)) {
+                Ok(_) => json(StatusCode::ACCEPTED, json!({ "canceling": true })),
+                Err(_) => json(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    json!({ "error": "order task not running" }),
+                ),
+            }
+        }
+        _ => json(StatusCode::NOT_FOUND, json!({ "error": "not found" })),
+    }
+}
+
+/// Serves the status API on the listener until shutdown
+pub async fn serve_status(
+    listener: TcpListener,
+    sources: StatusSources,
+    mut shutdown: ShutdownToken,
+) -> Result<()> {
+    listener.set_nonblocking(true)?;
+    let sources = Arc::new(sources);
+    let make_service = make_service_fn(move |_| {
+        let sources = sources.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let sources = sources.clone();
+                async move { Ok::<_, Infallible>(handle(request, sources).await) }
+            }))
+        }
+    });
+    Server::from_tcp(listener)
+        .map_err(|e| Error::Network(format!("failed serving status: {e}")))?
+        .serve(make_service)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await
+        .map_err(|e| Error::Network(format!("status server failed: {e}")))
+}
+
+/// Task to serve the JSON status and control API on the address
+pub async fn task_serve_status(
+    address: SocketAddr,
+    sources: StatusSources,
+    shutdown: ShutdownToken,
+) -> Result<()> {
+    let listener = TcpListener::bind(address)?;
+    tracing::info!("Serving status on http://{address}");
+    serve_status(listener, sources, shutdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redacted, serve_status, StatusSources};
+    use crate::event::order::OrderEvent;
+    use crate::model::orderbook::{LocalOrderbook, Orderbook};
+    use crate::monitor::registry;
+    use crate::shutdown::{ShutdownCoordinator, ShutdownPhase};
+    use hyper::{body, Body, Client, Method, Request, StatusCode};
+    use ordered_float::OrderedFloat;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use tokio::sync::{broadcast, watch, Mutex};
+
+    async fn request(method: Method, url: String) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(url)
+            .body(Body::empty())
+            .unwrap();
+        let response = Client::new().request(request).await.unwrap();
+        let status = response.status();
+        let bytes = body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_status_endpoints() {
+        let config = toml::from_str(
+            "
+            [kucoin]
+            api_key = \"key\"
+            secret_key = \"secret\"
+            passphrase = \"passphrase\"
+            [behaviour]
+            monitor_interval_sec = 120
+            usd_cyclic_arbitrage = 20
+            [log]
+            file_directory = \"./logs/\"
+            file_prefix = \"log\"
+            file_log_level = \"warn\"
+            term_log_level = \"info\"
+            ",
+        )
+        .unwrap();
+        let (paused, rx_paused) = watch::channel(false);
+        let tx_order = broadcast::channel(16).0;
+        let mut rx_order = tx_order.subscribe();
+        let open_orders = HashMap::from([("oid".to_string(), "BTC-USDT".to_string())]);
+        let sources = StatusSources {
+            config,
+            balances: None,
+            open_orders: Arc::new(Mutex::new(open_orders)),
+            full_orderbook: Arc::new(Mutex::new(HashMap::new())),
+            books_expected: 3,
+            sessions_expected: 0,
+            paused: Arc::new(paused),
+            tx_order,
+        };
+
+        let mut orderbook = Orderbook::new();
+        orderbook.ask.insert(OrderedFloat(10.0), OrderedFloat(1.0));
+        orderbook.bid.insert(OrderedFloat(9.0), OrderedFloat(2.0));
+        let full_orderbook = sources.full_orderbook.clone();
+        full_orderbook.lock().await.insert(
+            "BTC-USDT".to_string(),
+            LocalOrderbook::from_orderbook(&orderbook, 0.01).unwrap(),
+        );
+        full_orderbook
+            .lock()
+            .await
+            .insert("ETH-USDT".to_string(), LocalOrderbook::new(0.01).unwrap());
+        registry::global()
+            .counter("messages", &[("channel", "status_test")])
+            .add(3);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let shutdown = ShutdownCoordinator::new();
+        let server = tokio::spawn(serve_status(
+            listener,
+            sources,
+            shutdown.token(ShutdownPhase::Flush),
+        ));
+        let url = |path: &str| format!("http://{address}{path}");
+
+        let (status, body) = request(Method::GET, url("/health")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+        // one orderbook not synced yet
+        let (status, body) = request(Method::GET, url("/ready")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["books"]["synced"], 2);
+        let (_, body) = request(Method::GET, url("/books?symbols=BTC-USDT")).await;
+        assert_eq!(body["BTC-USDT"]["ask"][0], 10.0);
+        assert_eq!(body["BTC-USDT"]["bid"][1], 2.0);
+        assert!(body.get("ETH-USDT").is_none());
+        let (_, body) = request(Method::GET, url("/books")).await;
+        assert!(body["ETH-USDT"]["ask"].is_null());
+        let (_, body) = request(Method::GET, url("/counters")).await;
+        let counters = body["counters"].as_array().unwrap();
+        assert!(counters
+            .iter()
+            .any(|c| c["labels"]["channel"] == "status_test" && c["total"] == 3));
+        let (_, body) = request(Method::GET, url("/config")).await;
+        assert_eq!(body["kucoin"]["api_key"], "<redacted>");
+        let (_, body) = request(Method::GET, url("/orders")).await;
+        assert_eq!(body["oid"], "BTC-USDT");
+        let (status, _) = request(Method::GET, url("/balances")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = request(Method::POST, url("/pause")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(*rx_paused.borrow());
+        request(Method::POST, url("/resume")).await;
+        assert!(!*rx_paused.borrow());
+        let (status, _) = request(Method::POST, url("/cancel_all")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(
+            rx_order.recv().await.unwrap(),
+            OrderEvent::CancelAllOrders(None)
+        );
+
+        shutdown.advance(ShutdownPhase::Flush);
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_redacted() {
+        let config: crate::config::Config = toml::from_str(
+            "
+            [kucoin]
+            api_key = \"key\"
+            secret_key = \"secret\"
+            passphrase = \"passphrase\"
+            [behaviour]
+            monitor_interval_sec = 120
+            usd_cyclic_arbitrage = 20
+            [log]
+            file_directory = \"./logs/\"
+            file_prefix = \"log\"
+            file_log_level = \"warn\"
+            term_log_level = \"info\"
+        ",
+        )
+        .unwrap();
+        let json = redacted(&config);
+        assert_eq!(json["kucoin"]["secret_key"], "<redacted>");
+        assert_eq!(json["behaviour"]["usd_cyclic_arbitrage"], 20);
+    }
+}
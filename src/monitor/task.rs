@@ -1,47 +1,59 @@
-use crate::monitor::counter;
-use eyre::Result;
+use crate::error::Result;
+use crate::monitor::latency;
+use crate::monitor::registry::{Labels, Registry};
+use crate::shutdown::ShutdownToken;
 use std::sync::Arc;
-use tokio::sync::broadcast::Receiver;
-use tokio::sync::Mutex;
 use tokio::time;
-/// log counters
-async fn log_mps(counters: Vec<Arc<Mutex<counter::Counter>>>, interval: u64) -> Result<()> {
-    tracing::info!("Broadcast channel MPS");
-    for counter in counters.iter() {
-        let (name, count) = {
-            let p = counter.lock().await;
-            (p.name, p.data_count)
-        };
-        tracing::info!("{name:12}: {count:5} messages ({:5}mps)", count / interval);
-        // clear the data
-        counter::reset(counter.clone()).await;
+/// log the counters of the registry, clearing their counts, then the gauges (e.g. queue depth)
+fn log_mps(registry: &Registry, interval: u64) {
+    tracing::info!("Counters MPS");
+    for counter in registry.counters() {
+        let name = display_name(counter.name, &counter.labels);
+        let count = counter.reset();
+        tracing::info!("{name:32}: {count:5} messages ({:5}mps)", count / interval);
     }
-    Ok(())
+    for gauge in registry.gauges() {
+        let name = display_name(gauge.name, &gauge.labels);
+        tracing::info!("{name:32}: {}", gauge.get());
+    }
+}
+
+/// `name[value,...]` of a labelled metric
+fn display_name(name: &str, labels: &Labels) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let values: Vec<&str> = labels.iter().map(|(_, value)| value.as_str()).collect();
+    format!("{name}[{}]", values.join(","))
 }
-/// log counters as a task
+/// log the metrics of the registry as a task, logging the last counts on shutdown
 pub async fn task_log_mps(
-    counters: Vec<Arc<Mutex<counter::Counter>>>,
+    registry: Arc<Registry>,
     interval: u64,
+    mut shutdown: ShutdownToken,
 ) -> Result<()> {
     let monitor_delay = time::Duration::from_secs(interval);
     loop {
-        time::sleep(monitor_delay).await;
-        log_mps(counters.clone(), interval)
-            .await
-            .expect("report status error");
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                log_mps(&registry, interval);
+                return Ok(());
+            }
+            _ = time::sleep(monitor_delay) => log_mps(&registry, interval),
+        }
     }
 }
 
-/// increment counter
-pub async fn task_monitor_channel_mps<T: Clone>(
-    mut receiver: Receiver<T>,
-    counter: Arc<Mutex<counter::Counter>>,
-) -> Result<()> {
+/// log the latency percentiles as a task, logging the last percentiles on shutdown
+pub async fn task_log_latency(interval: u64, mut shutdown: ShutdownToken) -> Result<()> {
+    let monitor_delay = time::Duration::from_secs(interval);
     loop {
-        receiver
-            .recv()
-            .await
-            .map_err(|e| eyre::eyre!("channel closed by other channels ({e})"))?;
-        counter::increment(counter.clone()).await;
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                latency::report();
+                return Ok(());
+            }
+            _ = time::sleep(monitor_delay) => latency::report(),
+        }
     }
 }
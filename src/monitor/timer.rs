@@ -1,4 +1,4 @@
-use eyre::Result;
+use crate::error::{Error, Result};
 use std::collections::HashMap;
 use tokio::sync::Mutex;
 use tokio::time::{Duration, Instant};
@@ -18,6 +18,8 @@ pub async fn start(name: String) {
 pub async fn stop(name: String) -> Result<Duration> {
     let now = Instant::now();
     let timer = TIMERS.lock().await;
-    let stat = timer.get(&name).ok_or(eyre::eyre!("stat was empty"))?;
+    let stat = timer
+        .get(&name)
+        .ok_or(Error::Config(format!("timer [{name}] not started")))?;
     Ok(now.duration_since(*stat))
 }
@@ -0,0 +1,121 @@
+use crate::error::Result;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{timeout, Duration};
+
+/// Shutdown phases in the order they are reached
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShutdownPhase {
+    #[default]
+    Running,
+    /// strategies stop publishing chances, the gatekeeper finishes its in-flight cycle
+    StopChances,
+    /// in-flight cycles are abandoned and the order task stops, so open orders can be canceled
+    CancelCycles,
+    /// subscriptions stop and monitors report their last counts
+    Flush,
+    /// every task has stopped
+    Exit,
+}
+
+/// Moves all the tasks through the shutdown phases, cancelling the token of each at its phase
+#[derive(Debug)]
+pub struct ShutdownCoordinator {
+    sender: watch::Sender<ShutdownPhase>,
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        ShutdownCoordinator {
+            sender: watch::channel(ShutdownPhase::Running).0,
+        }
+    }
+
+    /// token for a task that stops at the given phase
+    pub fn token(&self, stop_at: ShutdownPhase) -> ShutdownToken {
+        ShutdownToken {
+            receiver: self.sender.subscribe(),
+            stop_at,
+        }
+    }
+
+    pub fn phase(&self) -> ShutdownPhase {
+        *self.sender.borrow()
+    }
+
+    /// moves forward to the phase, ignored if already reached
+    pub fn advance(&self, phase: ShutdownPhase) {
+        self.sender.send_if_modified(|current| {
+            if phase > *current {
+                tracing::info!("Shutdown phase [{current:?}] -> [{phase:?}]");
+                *current = phase;
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
+
+/// Held by a task to know when to stop
+#[derive(Debug, Clone)]
+pub struct ShutdownToken {
+    receiver: watch::Receiver<ShutdownPhase>,
+    stop_at: ShutdownPhase,
+}
+
+impl ShutdownToken {
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow() >= self.stop_at
+    }
+
+    /// resolves once the stop phase is reached or the coordinator is dropped, cancel safe
+    pub async fn cancelled(&mut self) {
+        let stop_at = self.stop_at;
+        // Err only when the coordinator is dropped, which also means stopping
+        let _ = self.receiver.wait_for(|phase| *phase >= stop_at).await;
+    }
+}
+
+/// Waits for every task of the pool to end within the grace period.
+/// Returns false if any task is still running, which is aborted once the pool is dropped.
+pub async fn join_within(taskpool: &mut JoinSet<Result<()>>, grace: Duration) -> bool {
+    let joined = timeout(grace, async {
+        while let Some(res) = taskpool.join_next().await {
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::error!("task ended with error during shutdown: {e}"),
+                Err(e) => tracing::error!("task failed during shutdown: {e}"),
+            }
+        }
+    })
+    .await;
+    if joined.is_err() {
+        tracing::warn!("{} tasks still running after {grace:?}", taskpool.len());
+    }
+    joined.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShutdownCoordinator, ShutdownPhase};
+
+    #[test]
+    fn test_phases() {
+        let shutdown = ShutdownCoordinator::new();
+        let strategy = shutdown.token(ShutdownPhase::StopChances);
+        let monitor = shutdown.token(ShutdownPhase::Flush);
+        shutdown.advance(ShutdownPhase::StopChances);
+        assert!(strategy.is_cancelled());
+        assert!(!monitor.is_cancelled());
+        // phases never go backward
+        shutdown.advance(ShutdownPhase::Running);
+        assert_eq!(shutdown.phase(), ShutdownPhase::StopChances);
+    }
+}
@@ -1,42 +1,58 @@
+use crate::config::Config;
+use crate::error::Result;
 use crate::event::{chance::ChanceEvent, orderbook::OrderbookEvent};
 use crate::model::chance::{ActionInfo, TriangularArbitrageChance};
-use crate::model::orderbook::{FullOrderbook, Orderbook};
+use crate::model::orderbook::{
+    FullOrderbook, LocalOrderbook, OrderbookBackend, OrderbookValidator,
+};
 use crate::model::symbol::SymbolInfo;
+use crate::monitor::channel::Subscriber;
+use crate::monitor::latency::{self, Stage};
+use crate::monitor::registry;
+use crate::monitor::status;
+use crate::shutdown::ShutdownToken;
 use crate::strings::split_symbol;
-use eyre::Result;
 use ordered_float::OrderedFloat;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::broadcast::{Receiver, Sender};
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 
 /// Async task to subscribe to hte websocket events, calculate chances,  
+/// chances involving an unhealthy orderbook (stale, crossed or empty) are skipped and counted.
+/// The budget and orderbook max age are read from the latest config, so that they can be reloaded while running.
 pub async fn task_pub_chance_all_taker_btc_usd(
-    mut receiver: Receiver<OrderbookEvent>,
+    receiver: Receiver<OrderbookEvent>,
     sender: Sender<ChanceEvent>,
     local_full_orderbook: Arc<Mutex<FullOrderbook>>,
     symbol_map: Arc<Mutex<BTreeMap<String, SymbolInfo>>>,
-    usd_budget: f64,
+    config: watch::Receiver<Config>,
+    halted: watch::Receiver<bool>,
+    mut shutdown: ShutdownToken,
 ) -> Result<()> {
+    let mut receiver = Subscriber::new(receiver, "best_price", "strategy");
+    let metrics = registry::global();
+    let cx_published = metrics.counter("messages", &[("channel", "chance")]);
+    let cx_unhealthy = metrics.counter("unhealthy", &[]);
+    let board = status::global();
     let btc = String::from("BTC");
     let usd = String::from("USDT");
     let btc_usd = std::format!("{btc}-{usd}");
     loop {
-        let event = receiver.recv().await?;
-        // tracing::info!("received orderbook_update");
-        let alt: Option<String> = match event {
-            OrderbookEvent::OrderbookChangeReceived((symbol, _delta)) => {
-                if symbol == btc_usd {
-                    continue;
-                }
-                let (coin, _) = split_symbol(symbol).unwrap();
-                Some(coin)
-            }
-            _ => {
-                tracing::error!("Unrecognised event {event:?}");
-                continue;
-            }
+        let event = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            event = receiver.recv() => event?,
         };
+        // stopped for good by the risk manager, the feed is still drained
+        if *halted.borrow() {
+            continue;
+        }
+        // tracing::info!("received orderbook_update");
+        if event.symbol() == btc_usd {
+            continue;
+        }
+        let (coin, _) = split_symbol(event.symbol().to_string()).unwrap();
+        let (alt, receive_time): (Option<String>, u64) = (Some(coin), event.receive_time());
         let alt = alt.unwrap();
         let alt_btc = std::format!("{alt}-{btc}");
         let alt_usd = std::format!("{alt}-{usd}");
@@ -59,6 +75,35 @@ pub async fn task_pub_chance_all_taker_btc_usd(
             continue;
         }
 
+        // skip chances with unhealthy orderbooks
+        let (usd_budget, max_age_sec) = {
+            let config = config.borrow();
+            let behaviour = &config.behaviour;
+            (
+                behaviour.usd_cyclic_arbitrage,
+                behaviour.orderbook_max_age_sec,
+            )
+        };
+        let validator = OrderbookValidator::new(max_age_sec as u64 * 1000);
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let fault = [
+            (&btc_usd, orderbook_btc_usd.unwrap()),
+            (&alt_btc, orderbook_alt_btc.unwrap()),
+            (&alt_usd, orderbook_alt_usd.unwrap()),
+        ]
+        .into_iter()
+        .find_map(|(symbol, orderbook)| {
+            validator
+                .validate(orderbook, now)
+                .err()
+                .map(|fault| (symbol, fault))
+        });
+        if let Some((symbol, fault)) = fault {
+            tracing::debug!("skipping chance with {fault} orderbook [{symbol}]");
+            cx_unhealthy.increment();
+            continue;
+        }
+
         // clone symbol info from Arc Mutex
         let (info_btc_usd, info_alt_btc, info_alt_usd) = {
             let symbol_map = symbol_map.lock().await;
@@ -76,17 +121,21 @@ pub async fn task_pub_chance_all_taker_btc_usd(
             orderbook_btc_usd.unwrap(),
             orderbook_alt_btc.unwrap(),
             orderbook_alt_usd.unwrap(),
-            usd_budget,
+            usd_budget as f64,
         );
 
         if chance.is_none() {
             continue;
         }
-        let chance = chance.unwrap();
+        let mut chance = chance.unwrap();
 
         // found profitable chance
         if chance.profit > OrderedFloat(0.0) {
+            chance.receive_time = receive_time;
+            latency::record_since(Stage::Chance, receive_time);
+            board.record_chance(&chance);
             sender.send(ChanceEvent::AllTaker(chance)).unwrap();
+            cx_published.increment();
         }
     }
 }
@@ -95,20 +144,20 @@ fn triangular_chance_sequence(
     info_btc_usd: SymbolInfo,
     info_alt_btc: SymbolInfo,
     info_alt_usd: SymbolInfo,
-    orderbook_btc_usd: &Orderbook,
-    orderbook_alt_btc: &Orderbook,
-    orderbook_alt_usd: &Orderbook,
+    orderbook_btc_usd: &LocalOrderbook,
+    orderbook_alt_btc: &LocalOrderbook,
+    orderbook_alt_usd: &LocalOrderbook,
     usd_amount: f64,
 ) -> Option<TriangularArbitrageChance> {
     // tracing::info!("TSC: {}", info_alt_btc.base);
     // get the least ask
-    let (btc_usd_ask, btc_usd_ask_volume) = orderbook_btc_usd.ask.first_key_value()?;
-    let (alt_btc_ask, alt_btc_ask_volume) = orderbook_alt_btc.ask.first_key_value()?;
-    let (alt_usd_ask, alt_usd_ask_volume) = orderbook_alt_usd.ask.first_key_value()?;
+    let (btc_usd_ask, btc_usd_ask_volume) = orderbook_btc_usd.best_ask()?;
+    let (alt_btc_ask, alt_btc_ask_volume) = orderbook_alt_btc.best_ask()?;
+    let (alt_usd_ask, alt_usd_ask_volume) = orderbook_alt_usd.best_ask()?;
     // get the largest bid
-    let (btc_usd_bid, btc_usd_bid_volume) = orderbook_btc_usd.bid.last_key_value()?;
-    let (alt_btc_bid, alt_btc_bid_volume) = orderbook_alt_btc.bid.last_key_value()?;
-    let (alt_usd_bid, alt_usd_bid_volume) = orderbook_alt_usd.bid.last_key_value()?;
+    let (btc_usd_bid, btc_usd_bid_volume) = orderbook_btc_usd.best_bid()?;
+    let (alt_btc_bid, alt_btc_bid_volume) = orderbook_alt_btc.best_bid()?;
+    let (alt_usd_bid, alt_usd_bid_volume) = orderbook_alt_usd.best_bid()?;
 
     // This should be obtained from the API
     let trading_fee = 0.001;
@@ -116,30 +165,30 @@ fn triangular_chance_sequence(
     triangular_chance_sequence_f64(
         PairProfile {
             symbol: info_btc_usd.symbol,
-            ask: btc_usd_ask.into_inner(),
-            ask_volume: btc_usd_ask_volume.into_inner(),
-            bid: btc_usd_bid.into_inner(),
-            bid_volume: btc_usd_bid_volume.into_inner(),
+            ask: btc_usd_ask,
+            ask_volume: btc_usd_ask_volume,
+            bid: btc_usd_bid,
+            bid_volume: btc_usd_bid_volume,
             trading_min: *info_btc_usd.base_min,
             trading_increment: *info_btc_usd.base_increment,
             trading_fee,
         },
         PairProfile {
             symbol: info_alt_btc.symbol,
-            ask: alt_btc_ask.into_inner(),
-            ask_volume: alt_btc_ask_volume.into_inner(),
-            bid: alt_btc_bid.into_inner(),
-            bid_volume: alt_btc_bid_volume.into_inner(),
+            ask: alt_btc_ask,
+            ask_volume: alt_btc_ask_volume,
+            bid: alt_btc_bid,
+            bid_volume: alt_btc_bid_volume,
             trading_min: *info_alt_btc.base_min,
             trading_increment: *info_alt_btc.base_increment,
             trading_fee,
         },
         PairProfile {
             symbol: info_alt_usd.symbol,
-            ask: alt_usd_ask.into_inner(),
-            ask_volume: alt_usd_ask_volume.into_inner(),
-            bid: alt_usd_bid.into_inner(),
-            bid_volume: alt_usd_bid_volume.into_inner(),
+            ask: alt_usd_ask,
+            ask_volume: alt_usd_ask_volume,
+            bid: alt_usd_bid,
+            bid_volume: alt_usd_bid_volume,
             trading_min: *info_alt_usd.base_min,
             trading_increment: *info_alt_usd.base_increment,
             trading_fee,
@@ -246,6 +295,8 @@ fn triangular_chance_sequence_f64(
                     OrderedFloat(bbs_s_alt_amount),
                 ),
             ],
+            // set by the task with the orderbook update raising the chance
+            receive_time: 0,
         });
     }
 
@@ -270,6 +321,8 @@ fn triangular_chance_sequence_f64(
                     OrderedFloat(bss_s_btc_amount),
                 ),
             ],
+            // set by the task with the orderbook update raising the chance
+            receive_time: 0,
         });
     }
 
@@ -1,5 +1,8 @@
+use crate::config::{Args, Config};
+use crate::error::Result;
 use std::io;
 use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
 
 /// task to wait for any external terminating signal
 pub async fn task_signal_handle() -> io::Result<()> {
@@ -16,3 +19,28 @@ pub async fn task_signal_handle() -> io::Result<()> {
 fn exit_program(signal_alias: &str) {
     println!("Received [{signal_alias}] signal. Cleaning up and shutting down gracefully.");
 }
+
+/// task to reload the config file on SIGHUP with the overrides of args, keeping the current config if invalid.
+/// Only usd_cyclic_arbitrage and orderbook_max_age_sec are applied while running, the other values require a restart.
+pub async fn task_reload_config(args: Args, sender: watch::Sender<Config>) -> Result<()> {
+    let filename = &args.config;
+    let mut sighup = signal(SignalKind::hangup())?;
+    while sighup.recv().await.is_some() {
+        let config = match args.load() {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("Received [SIGHUP], keeping current config [{filename}]: {e}");
+                continue;
+            }
+        };
+        let previous = sender.send_replace(config.clone());
+        let mut unapplied = config;
+        unapplied.behaviour.usd_cyclic_arbitrage = previous.behaviour.usd_cyclic_arbitrage;
+        unapplied.behaviour.orderbook_max_age_sec = previous.behaviour.orderbook_max_age_sec;
+        if unapplied != previous {
+            tracing::warn!("Config changes other than usd_cyclic_arbitrage and orderbook_max_age_sec require a restart");
+        }
+        tracing::info!("Received [SIGHUP], reloaded [{filename}]");
+    }
+    Ok(())
+}
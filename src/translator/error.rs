@@ -0,0 +1,64 @@
+use std::error;
+use std::fmt;
+
+/// Failure converting an API model into an internal model, naming the field and its raw value
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConversionError {
+    /// field value could not be parsed
+    InvalidField { field: &'static str, value: String },
+    /// field (e.g. an element of a price level) was not present
+    MissingField { field: &'static str },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConversionError::InvalidField { field, ref value } => {
+                write!(f, "invalid {field}: {value:?}")
+            }
+            ConversionError::MissingField { field } => write!(f, "missing {field}"),
+        }
+    }
+}
+
+impl error::Error for ConversionError {}
+
+/// Parses the raw value of a field
+pub fn parse_field<T: std::str::FromStr>(
+    field: &'static str,
+    value: &str,
+) -> Result<T, ConversionError> {
+    value.parse().map_err(|_| ConversionError::InvalidField {
+        field,
+        value: value.to_string(),
+    })
+}
+
+/// Parses the index-th element of a price level, e.g. ["price", "size", "sequence"]
+pub fn parse_level_field<T: std::str::FromStr>(
+    field: &'static str,
+    level: &[String],
+    index: usize,
+) -> Result<T, ConversionError> {
+    let value = level
+        .get(index)
+        .ok_or(ConversionError::MissingField { field })?;
+    parse_field(field, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_field, ConversionError};
+
+    #[test]
+    fn test_parse_field() {
+        assert_eq!(parse_field::<u64>("sequence", "42"), Ok(42));
+        assert_eq!(
+            parse_field::<u64>("sequence", "4x2"),
+            Err(ConversionError::InvalidField {
+                field: "sequence",
+                value: "4x2".to_string()
+            })
+        );
+    }
+}
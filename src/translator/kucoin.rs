@@ -2,166 +2,255 @@
     Translates from kucoin_api crates model to out internal model
 */
 
-use std::str::FromStr;
-
 use crate::model;
+use crate::model::cycle::CycleId;
+use crate::translator::error::{parse_field, parse_level_field, ConversionError};
 use crate::translator::traits;
-use eyre::Result;
 use kucoin_api::model as api_model;
 use ordered_float::OrderedFloat;
-use uuid::Uuid;
+
+/// converts [price, volume, ..] levels into PVMap
+fn to_pvmap(levels: &[Vec<String>]) -> Result<model::orderbook::PVMap, ConversionError> {
+    let mut pvmap = model::orderbook::PVMap::new();
+    for level in levels {
+        let price: OrderedFloat<f64> = parse_level_field("level.price", level, 0)?;
+        let volume: OrderedFloat<f64> = parse_level_field("level.size", level, 1)?;
+        pvmap.insert(price, volume);
+    }
+    Ok(pvmap)
+}
+
+/// converts [price, volume, sequence] levels into (price, volume), levels with sequence <= last_serial
+/// are ignored
+fn to_levels(levels: &[Vec<String>], last_serial: u64) -> Result<Vec<(f64, f64)>, ConversionError> {
+    let mut changes = Vec::with_capacity(levels.len());
+    for level in levels {
+        let sequence: u64 = parse_level_field("level.sequence", level, 2)?;
+        if sequence <= last_serial {
+            continue;
+        }
+        let price: f64 = parse_level_field("level.price", level, 0)?;
+        let volume: f64 = parse_level_field("level.size", level, 1)?;
+        changes.push((price, volume));
+    }
+    Ok(changes)
+}
 
 impl traits::ToOrderBook for api_model::market::OrderBook {
-    fn to_internal(&self) -> model::orderbook::Orderbook {
-        let parse_err_msg = "Failed to parse input";
-        let sequence = self.sequence.parse::<u64>().unwrap();
+    fn to_internal(&self) -> Result<model::orderbook::Orderbook, ConversionError> {
+        Ok(model::orderbook::Orderbook {
+            ask: to_pvmap(&self.asks)?,
+            bid: to_pvmap(&self.bids)?,
+            sequence: parse_field("sequence", &self.sequence)?,
+            exchange_time: self.time as u64,
+            update_time: chrono::Utc::now().timestamp_millis() as u64,
+            receive_time: chrono::Utc::now().timestamp_micros() as u64,
+        })
+    }
+}
+
+impl traits::ToOrderBook for api_model::websocket::Level2Depth {
+    /// snapshot without sequence, left 0 (unknown) as a timestamp would not compare with the
+    /// level2 sequences, snapshots replace the orderbook rather than being merged
+    fn to_internal(&self) -> Result<model::orderbook::Orderbook, ConversionError> {
+        Ok(model::orderbook::Orderbook {
+            ask: to_pvmap(&self.asks)?,
+            bid: to_pvmap(&self.bids)?,
+            sequence: 0,
+            exchange_time: self.timestamp,
+            update_time: chrono::Utc::now().timestamp_millis() as u64,
+            receive_time: chrono::Utc::now().timestamp_micros() as u64,
+        })
+    }
+}
+
+impl traits::ToOrderBook for api_model::websocket::SymbolTicker {
+    /// best bid/ask as a single level orderbook, the ticker carries no timestamp
+    fn to_internal(&self) -> Result<model::orderbook::Orderbook, ConversionError> {
         let mut ask = model::orderbook::PVMap::new();
         let mut bid = model::orderbook::PVMap::new();
-
-        for ask_pv in self.asks.clone() {
-            let price: OrderedFloat<f64> = ask_pv[0].parse().expect(parse_err_msg);
-            let volume: OrderedFloat<f64> = ask_pv[1].parse().expect(parse_err_msg);
-            ask.insert(price, volume);
-        }
-        for bid_pv in self.bids.clone() {
-            let price: OrderedFloat<f64> = bid_pv[0].parse().expect(parse_err_msg);
-            let volume: OrderedFloat<f64> = bid_pv[1].parse().expect(parse_err_msg);
-            bid.insert(price, volume);
-        }
-        model::orderbook::Orderbook { ask, bid, sequence }
+        ask.insert(
+            parse_field("best_ask", &self.best_ask)?,
+            parse_field("best_ask_size", &self.best_ask_size)?,
+        );
+        bid.insert(
+            parse_field("best_bid", &self.best_bid)?,
+            parse_field("best_bid_size", &self.best_bid_size)?,
+        );
+        Ok(model::orderbook::Orderbook {
+            ask,
+            bid,
+            sequence: parse_field("sequence", &self.sequence)?,
+            exchange_time: 0,
+            update_time: chrono::Utc::now().timestamp_millis() as u64,
+            receive_time: chrono::Utc::now().timestamp_micros() as u64,
+        })
     }
 }
 
 impl traits::ToOrderBookChange for api_model::websocket::Level2 {
     /// converts to (symbol, orderbook)
-    fn to_internal(&self, last_serial: u64) -> (String, model::orderbook::Orderbook) {
-        // return Orderbook::new();
-        let mut ask = model::orderbook::PVMap::new();
-        let mut bid = model::orderbook::PVMap::new();
-        let parse_err_msg = "Failed to parse input";
-
-        for ask_change in self.changes.asks.clone() {
-            // ignore if sequence <=serial
-            if ask_change[2].parse::<u64>().unwrap() > last_serial {
-                let price: OrderedFloat<f64> = ask_change[0].parse().expect(parse_err_msg);
-                let volume: OrderedFloat<f64> = ask_change[1].parse().expect(parse_err_msg);
-                ask.insert(price, volume);
-            }
-        }
-        for bid_change in self.changes.bids.clone() {
-            // ignore if sequence <=serial
-            if bid_change[2].parse::<u64>().unwrap() > last_serial {
-                let price: OrderedFloat<f64> = bid_change[0].parse().expect(parse_err_msg);
-                let volume: OrderedFloat<f64> = bid_change[1].parse().expect(parse_err_msg);
-                bid.insert(price, volume);
-            }
-        }
-        let sequence = self.sequence_end as u64;
-        (
+    fn to_internal(
+        &self,
+        last_serial: u64,
+    ) -> Result<(String, model::orderbook::OrderbookChange), ConversionError> {
+        let sequence =
+            u64::try_from(self.sequence_end).map_err(|_| ConversionError::InvalidField {
+                field: "sequence_end",
+                value: self.sequence_end.to_string(),
+            })?;
+        let sequence_start =
+            u64::try_from(self.sequence_start).map_err(|_| ConversionError::InvalidField {
+                field: "sequence_start",
+                value: self.sequence_start.to_string(),
+            })?;
+        Ok((
             self.symbol.clone(),
-            model::orderbook::Orderbook { ask, bid, sequence },
-        )
+            model::orderbook::OrderbookChange {
+                asks: to_levels(&self.changes.asks, last_serial)?,
+                bids: to_levels(&self.changes.bids, last_serial)?,
+                sequence_start,
+                sequence,
+                exchange_time: self.time,
+                update_time: chrono::Utc::now().timestamp_millis() as u64,
+                receive_time: chrono::Utc::now().timestamp_micros() as u64,
+            },
+        ))
     }
 }
 
 impl traits::ToSymbolInfo for api_model::market::SymbolList {
-    fn to_internal(&self) -> model::symbol::SymbolInfo {
-        model::symbol::SymbolInfo {
+    fn to_internal(&self) -> Result<model::symbol::SymbolInfo, ConversionError> {
+        Ok(model::symbol::SymbolInfo {
             symbol: self.symbol.clone(),
             base: self.base_currency.clone(),
             quote: self.quote_currency.clone(),
-            base_increment: self.base_increment.parse().unwrap(),
-            base_min: self.base_min_size.parse().unwrap(),
-        }
+            base_increment: parse_field("base_increment", &self.base_increment)?,
+            base_min: parse_field("base_min_size", &self.base_min_size)?,
+            price_increment: parse_field("price_increment", &self.price_increment)?,
+        })
     }
 }
 
 impl traits::ToTradeInfo for api_model::websocket::TradeReceived {
-    fn to_internal(&self) -> Result<model::trade::TradeInfo> {
-        let order_id = Uuid::parse_str(&self.client_oid)?.as_u128();
-        let symbol = self.symbol.clone();
-        let side = model::order::OrderSide::from_str(self.side.as_ref())?;
-        let size = self.size.clone();
-        let order_type: model::order::OrderType =
-            model::order::OrderType::from_str(self.order_type.as_ref())?;
+    fn to_internal(&self) -> Result<model::trade::TradeInfo, ConversionError> {
         Ok(model::trade::TradeInfo {
-            order_id,
-            symbol,
-            side,
-            order_type,
-            size,
+            client_oid: self.client_oid.clone(),
+            cycle_id: CycleId::from_oid(&self.client_oid),
+            symbol: self.symbol.clone(),
+            side: parse_field("side", &self.side)?,
+            order_type: parse_field("order_type", &self.order_type)?,
+            size: self.size.clone(),
+            filled_size: "0".to_string(),
+            match_price: None,
         })
     }
 }
 
 impl traits::ToTradeInfo for api_model::websocket::TradeOpen {
-    fn to_internal(&self) -> Result<model::trade::TradeInfo> {
-        let order_id = Uuid::parse_str(&self.client_oid)?.as_u128();
-        let symbol = self.symbol.clone();
-        let side = model::order::OrderSide::from_str(self.side.as_ref())?;
-        let size = self.size.clone();
-        let order_type: model::order::OrderType =
-            model::order::OrderType::from_str(self.order_type.as_ref())?;
+    fn to_internal(&self) -> Result<model::trade::TradeInfo, ConversionError> {
         Ok(model::trade::TradeInfo {
-            order_id,
-            symbol,
-            side,
-            order_type,
-            size,
+            client_oid: self.client_oid.clone(),
+            cycle_id: CycleId::from_oid(&self.client_oid),
+            symbol: self.symbol.clone(),
+            side: parse_field("side", &self.side)?,
+            order_type: parse_field("order_type", &self.order_type)?,
+            size: self.size.clone(),
+            filled_size: self.filled_size.clone(),
+            match_price: None,
         })
     }
 }
 
 impl traits::ToTradeInfo for api_model::websocket::TradeFilled {
-    fn to_internal(&self) -> Result<model::trade::TradeInfo> {
-        let order_id = Uuid::parse_str(&self.client_oid)?.as_u128();
-        let symbol = self.symbol.clone();
-        let side = model::order::OrderSide::from_str(self.side.as_ref())?;
-        let size = self.size.clone();
-        let order_type: model::order::OrderType =
-            model::order::OrderType::from_str(self.order_type.as_ref())?;
+    fn to_internal(&self) -> Result<model::trade::TradeInfo, ConversionError> {
         Ok(model::trade::TradeInfo {
-            order_id,
-            symbol,
-            side,
-            order_type,
-            size,
+            client_oid: self.client_oid.clone(),
+            cycle_id: CycleId::from_oid(&self.client_oid),
+            symbol: self.symbol.clone(),
+            side: parse_field("side", &self.side)?,
+            order_type: parse_field("order_type", &self.order_type)?,
+            size: self.size.clone(),
+            filled_size: self.filled_size.clone(),
+            match_price: None,
         })
     }
 }
 
 impl traits::ToTradeInfo for api_model::websocket::TradeMatch {
-    fn to_internal(&self) -> Result<model::trade::TradeInfo> {
-        let order_id = Uuid::parse_str(&self.client_oid)?.as_u128();
-        let symbol = self.symbol.clone();
-        let side = model::order::OrderSide::from_str(self.side.as_ref())?;
-        let size = self.size.clone();
-        let order_type: model::order::OrderType =
-            model::order::OrderType::from_str(self.order_type.as_ref())?;
+    fn to_internal(&self) -> Result<model::trade::TradeInfo, ConversionError> {
         Ok(model::trade::TradeInfo {
-            order_id,
-            symbol,
-            side,
-            order_type,
-            size,
+            client_oid: self.client_oid.clone(),
+            cycle_id: CycleId::from_oid(&self.client_oid),
+            symbol: self.symbol.clone(),
+            side: parse_field("side", &self.side)?,
+            order_type: parse_field("order_type", &self.order_type)?,
+            size: self.size.clone(),
+            filled_size: self.filled_size.clone(),
+            match_price: Some(self.match_price.clone()),
         })
     }
 }
 
 impl traits::ToTradeInfo for api_model::websocket::TradeCanceled {
-    fn to_internal(&self) -> Result<model::trade::TradeInfo> {
-        let order_id = Uuid::parse_str(&self.client_oid)?.as_u128();
-        let symbol = self.symbol.clone();
-        let side = model::order::OrderSide::from_str(self.side.as_ref())?;
-        let size = self.size.clone();
-        let order_type: model::order::OrderType =
-            model::order::OrderType::from_str(self.order_type.as_ref())?;
+    fn to_internal(&self) -> Result<model::trade::TradeInfo, ConversionError> {
         Ok(model::trade::TradeInfo {
-            order_id,
-            symbol,
-            side,
-            order_type,
-            size,
+            client_oid: self.client_oid.clone(),
+            cycle_id: CycleId::from_oid(&self.client_oid),
+            symbol: self.symbol.clone(),
+            side: parse_field("side", &self.side)?,
+            order_type: parse_field("order_type", &self.order_type)?,
+            size: self.size.clone(),
+            filled_size: self.filled_size.clone(),
+            match_price: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::translator::error::ConversionError;
+    use crate::translator::traits::{ToOrderBook, ToOrderBookChange};
+    use kucoin_api::model as api_model;
+
+    fn level(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn orderbook_invalid_price() {
+        let orderbook = api_model::market::OrderBook {
+            sequence: "10".to_string(),
+            time: 0,
+            bids: vec![level(&["9.9", "1"])],
+            asks: vec![level(&["ten", "1"])],
+        };
+        assert_eq!(
+            orderbook.to_internal(),
+            Err(ConversionError::InvalidField {
+                field: "level.price",
+                value: "ten".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn orderbook_change_missing_sequence() {
+        let change = api_model::websocket::Level2 {
+            sequence_start: 1,
+            sequence_end: 2,
+            symbol: "BTC-USDT".to_string(),
+            changes: api_model::websocket::Level2Changes {
+                asks: vec![level(&["10.1", "1", "2"])],
+                bids: vec![level(&["9.9", "1"])],
+            },
+            time: 0,
+        };
+        assert_eq!(
+            change.to_internal(0),
+            Err(ConversionError::MissingField {
+                field: "level.sequence"
+            })
+        );
+    }
+}
@@ -1,5 +1,7 @@
 /// Implementation on Binance API
 pub mod binance;
+/// Conversion error
+pub mod error;
 /// Implementation on KuCoin API
 pub mod kucoin;
 /// Traits to be implemented
@@ -1,20 +1,20 @@
-use crate::model::orderbook::Orderbook;
+use crate::model::orderbook::{Orderbook, OrderbookChange};
 use crate::model::symbol::SymbolInfo;
 use crate::model::trade::TradeInfo;
-use eyre::Result;
+use crate::translator::error::ConversionError;
 
 pub trait ToOrderBook {
-    fn to_internal(&self) -> Orderbook;
+    fn to_internal(&self) -> Result<Orderbook, ConversionError>;
 }
 
 pub trait ToOrderBookChange {
-    fn to_internal(&self, serial: u64) -> (String, Orderbook);
+    fn to_internal(&self, serial: u64) -> Result<(String, OrderbookChange), ConversionError>;
 }
 
 pub trait ToSymbolInfo {
-    fn to_internal(&self) -> SymbolInfo;
+    fn to_internal(&self) -> Result<SymbolInfo, ConversionError>;
 }
 
 pub trait ToTradeInfo {
-    fn to_internal(&self) -> Result<TradeInfo>;
+    fn to_internal(&self) -> Result<TradeInfo, ConversionError>;
 }